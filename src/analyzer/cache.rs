@@ -0,0 +1,357 @@
+use std::{
+    collections::HashMap,
+    fs::Metadata,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::lsp::state::State;
+
+use super::indexer::Symbol;
+
+/// Bump whenever the shape of the cached data changes so old caches are ignored.
+const CACHE_VERSION: u32 = 9;
+
+/// Identifies the revision of a file on disk. The modification time and size
+/// are checked first, the content hash only when those do not match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub modified: u128,
+    pub size: u64,
+    pub hash: u64,
+}
+
+impl FileStamp {
+    pub fn new(metadata: &Metadata, content: &[u8]) -> Self {
+        Self {
+            modified: modified_nanos(metadata),
+            size: metadata.len(),
+            hash: hash_content(content),
+        }
+    }
+
+    pub fn matches_metadata(&self, metadata: &Metadata) -> bool {
+        self.modified == modified_nanos(metadata) && self.size == metadata.len()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedFile {
+    pub stamp: FileStamp,
+    pub symbols: Vec<Symbol>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedAutoloadMap {
+    pub stamp: FileStamp,
+    pub entries: Vec<(String, String)>,
+}
+
+/// Symbols and composer autoload maps from a previous run of the server. File
+/// entries are handed out once and afterwards live in the state.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IndexCache {
+    pub version: u32,
+    pub files: HashMap<String, CachedFile>,
+    pub autoload_maps: HashMap<String, CachedAutoloadMap>,
+}
+
+/// Returns where the index of a workspace is stored, `$XDG_CACHE_HOME/pherris`
+/// or `~/.cache/pherris`, falling back to a `.pherris` directory in the workspace.
+pub fn cache_path(root_path: &str) -> PathBuf {
+    let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")));
+
+    match cache_dir {
+        Some(dir) => dir
+            .join("pherris")
+            .join(format!("{:016x}.json", hash_content(root_path.as_bytes()))),
+        None => Path::new(root_path).join(".pherris").join("index.json"),
+    }
+}
+
+pub fn read_index_cache(path: &Path) -> Option<IndexCache> {
+    let contents = std::fs::read(path).ok()?;
+    let cache: IndexCache = serde_json::from_slice(&contents).ok()?;
+
+    (cache.version == CACHE_VERSION).then_some(cache)
+}
+
+pub fn write_index_cache(path: &Path, cache: &IndexCache) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    // write to a temporary file first so a crash never leaves a truncated cache behind
+    let temp_path = path.with_extension("json.tmp");
+    std::fs::write(&temp_path, serde_json::to_vec(cache)?)?;
+    std::fs::rename(temp_path, path)
+}
+
+/// Loads the index cache of the current workspace into the state.
+pub fn load_index_cache(state: &State) {
+    let root_path = state.root_path.read().unwrap().clone();
+    if root_path.is_empty() {
+        return;
+    }
+
+    if let Some(cache) = read_index_cache(&cache_path(&root_path)) {
+        *state.index_cache.write().unwrap() = cache;
+    }
+}
+
+/// Writes the current index to the index cache of the workspace.
+pub fn save_index_cache(state: &State) -> std::io::Result<()> {
+    let root_path = state.root_path.read().unwrap().clone();
+    if root_path.is_empty() {
+        return Ok(());
+    }
+
+    write_index_cache(&cache_path(&root_path), &snapshot_index_cache(state))
+}
+
+/// Collects every indexed file with a known revision together with the
+/// autoload maps read so far. Cached files that were not needed this session,
+/// e.g. classes that are only indexed once they are looked up, are kept as
+/// long as they still exist.
+pub fn snapshot_index_cache(state: &State) -> IndexCache {
+    let (mut files, autoload_maps) = {
+        let index_cache = state.index_cache.read().unwrap();
+        let unused: HashMap<String, CachedFile> = index_cache
+            .files
+            .iter()
+            .filter(|(path, _)| Path::new(path).is_file())
+            .map(|(path, cached)| (path.clone(), cached.clone()))
+            .collect();
+
+        (unused, index_cache.autoload_maps.clone())
+    };
+
+    files.extend(state.file_stamps.iter().filter_map(|stamp| {
+        let symbols = state.symbol_index.get(stamp.key())?.clone();
+        Some((
+            stamp.key().clone(),
+            CachedFile {
+                stamp: *stamp.value(),
+                symbols,
+            },
+        ))
+    }));
+
+    IndexCache {
+        version: CACHE_VERSION,
+        files,
+        autoload_maps,
+    }
+}
+
+/// Takes the cached symbols of a file if it did not change since they were stored.
+/// Returns the content of the file when it has to be parsed again.
+pub fn take_cached_symbols(
+    path: &str,
+    metadata: &Metadata,
+    state: &State,
+) -> Result<(FileStamp, Vec<Symbol>), String> {
+    let cached = state.index_cache.write().unwrap().files.remove(path);
+
+    if let Some(cached) = &cached {
+        if cached.stamp.matches_metadata(metadata) {
+            return Ok((cached.stamp, cached.symbols.clone()));
+        }
+    }
+
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    let stamp = FileStamp::new(metadata, content.as_bytes());
+
+    match cached {
+        // touched but not modified, e.g. after a checkout of the same revision
+        Some(cached) if cached.stamp.hash == stamp.hash => Ok((stamp, cached.symbols)),
+        _ => Err(content),
+    }
+}
+
+/// Returns the entries of a generated composer autoload file, parsing it only
+/// when it changed since the entries were cached.
+pub fn load_autoload_map<F>(path: &str, state: &State, parse: F) -> Option<Vec<(String, String)>>
+where
+    F: FnOnce(&[u8]) -> Vec<(String, String)>,
+{
+    let metadata = std::fs::metadata(path).ok()?;
+
+    if let Some(cached) = state.index_cache.read().unwrap().autoload_maps.get(path) {
+        if cached.stamp.matches_metadata(&metadata) {
+            return Some(cached.entries.clone());
+        }
+    }
+
+    let contents = std::fs::read(path).ok()?;
+    let stamp = FileStamp::new(&metadata, &contents);
+
+    // parse without holding the lock, lookups keep reading the cache meanwhile
+    let cached = state
+        .index_cache
+        .read()
+        .unwrap()
+        .autoload_maps
+        .get(path)
+        .filter(|cached| cached.stamp.hash == stamp.hash)
+        .map(|cached| cached.entries.clone());
+    let entries = cached.unwrap_or_else(|| parse(&contents));
+
+    state.index_cache.write().unwrap().autoload_maps.insert(
+        path.to_string(),
+        CachedAutoloadMap {
+            stamp,
+            entries: entries.clone(),
+        },
+    );
+
+    Some(entries)
+}
+
+fn modified_nanos(metadata: &Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default()
+}
+
+/// 64 bit FNV-1a, stable across builds unlike the std hasher.
+fn hash_content(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::TempDir;
+
+    use crate::analyzer::cache::{
+        cache_path, load_autoload_map, read_index_cache, snapshot_index_cache, take_cached_symbols,
+        write_index_cache, IndexCache, CACHE_VERSION,
+    };
+    use crate::analyzer::indexer::{index_file, index_path, SymbolKind};
+    use crate::lsp::state::State;
+
+    #[test]
+    fn test_cache_path_is_keyed_by_workspace() {
+        assert_ne!(cache_path("/var/www/first"), cache_path("/var/www/second"));
+        assert_eq!(cache_path("/var/www/first"), cache_path("/var/www/first"));
+    }
+
+    #[test]
+    fn test_read_index_cache_ignores_other_versions() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let path = temp_dir.path().join("index.json");
+
+        write_index_cache(
+            &path,
+            &IndexCache {
+                version: CACHE_VERSION + 1,
+                ..Default::default()
+            },
+        )
+        .expect("to write cache");
+        assert!(read_index_cache(&path).is_none());
+
+        std::fs::write(&path, "{ not json").expect("to write cache");
+        assert!(read_index_cache(&path).is_none());
+    }
+
+    #[test]
+    fn test_cached_symbols_are_invalidated_per_file() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let unchanged = temp_dir.path().join("Unchanged.php");
+        let changed = temp_dir.path().join("Changed.php");
+        std::fs::write(&unchanged, "<?php\nclass Unchanged {}\n").expect("to write file");
+        std::fs::write(&changed, "<?php\nclass Changed {}\n").expect("to write file");
+
+        let state = State::default();
+        for path in [&unchanged, &changed] {
+            index_file(path, &std::fs::read_to_string(path).unwrap(), &state);
+        }
+
+        let cache_file = temp_dir.path().join("index.json");
+        write_index_cache(&cache_file, &snapshot_index_cache(&state)).expect("to write cache");
+
+        std::fs::write(&changed, "<?php\nclass RenamedClass {}\n").expect("to write file");
+
+        let warm_state = State::default();
+        *warm_state.index_cache.write().unwrap() = read_index_cache(&cache_file).unwrap();
+
+        let unchanged_str = unchanged.to_str().unwrap();
+        let metadata = std::fs::metadata(&unchanged).unwrap();
+        let (_, symbols) = take_cached_symbols(unchanged_str, &metadata, &warm_state)
+            .expect("unchanged file to be cached");
+        assert_eq!(symbols[0].fqn, "Unchanged");
+        assert_eq!(symbols[0].kind, SymbolKind::Class);
+
+        let changed_str = changed.to_str().unwrap();
+        let metadata = std::fs::metadata(&changed).unwrap();
+        let content = take_cached_symbols(changed_str, &metadata, &warm_state)
+            .expect_err("changed file to be parsed again");
+        assert!(content.contains("class RenamedClass"));
+    }
+
+    #[test]
+    fn test_unused_cached_files_are_kept() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let paths =
+            ["Used.php", "Unused.php", "Deleted.php"].map(|name| temp_dir.path().join(name));
+        for path in paths.iter() {
+            let class = path.file_stem().unwrap().to_str().unwrap();
+            std::fs::write(path, format!("<?php\nclass {} {{}}\n", class)).expect("to write file");
+        }
+
+        let state = State::default();
+        for path in paths.iter() {
+            index_file(path, &std::fs::read_to_string(path).unwrap(), &state);
+        }
+        let cache_file = temp_dir.path().join("index.json");
+        write_index_cache(&cache_file, &snapshot_index_cache(&state)).expect("to write cache");
+
+        // only the files that are looked up get indexed when composer maps the classes
+        let [used, unused, deleted] = paths.map(|path| path.to_str().unwrap().to_string());
+        std::fs::remove_file(&deleted).expect("to remove file");
+        let warm_state = State::default();
+        *warm_state.index_cache.write().unwrap() = read_index_cache(&cache_file).unwrap();
+        index_path(Path::new(&used), &warm_state);
+        write_index_cache(&cache_file, &snapshot_index_cache(&warm_state)).expect("to write cache");
+
+        let cache = read_index_cache(&cache_file).unwrap();
+        assert_eq!(cache.files[&used].symbols[0].fqn, "Used");
+        assert_eq!(cache.files[&unused].symbols[0].fqn, "Unused");
+        assert!(!cache.files.contains_key(&deleted));
+    }
+
+    #[test]
+    fn test_autoload_map_is_parsed_once() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let path = temp_dir.path().join("autoload_classmap.php");
+        std::fs::write(&path, "<?php return [];").expect("to write file");
+        let path = path.to_str().unwrap();
+
+        let state = State::default();
+        let parse = |_: &[u8]| vec![(String::from("App\\Foo"), String::from("/src/Foo.php"))];
+        let entries = load_autoload_map(path, &state, parse).unwrap();
+        assert_eq!(entries.len(), 1);
+
+        let entries = load_autoload_map(path, &state, |_| panic!("not to parse again")).unwrap();
+        assert_eq!(entries[0].0, "App\\Foo");
+        assert!(load_autoload_map(
+            Path::new("/does/not/exist").to_str().unwrap(),
+            &state,
+            |_| vec![]
+        )
+        .is_none());
+    }
+}
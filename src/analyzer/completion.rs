@@ -0,0 +1,757 @@
+use mago_ast::{Expression, Node};
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use mago_span::HasSpan;
+use streaming_iterator::StreamingIterator;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionList, CompletionTextEdit, Position, Range,
+    TextEdit, Url,
+};
+use tree_sitter::QueryCursor;
+
+use crate::lsp::state::State;
+
+use super::{
+    composer::resolve_file_namespace,
+    imports::Imports,
+    indexer::{qualify_name, MemberKind, SymbolKind, Visibility},
+    inference::{get_current_class, infer_expression_type, resolve_variable_type, Scope},
+    members::{find_class_symbol, get_members, is_subclass_of, ClassMember},
+    names::{get_name_context, is_relative_class_name},
+    query::variable_declaration_query,
+    tree::{get_node_name, get_nodes_for_position},
+    types::{format_types, get_classes, shorten_class_names, Type},
+    utils::{
+        get_function_like_parent, get_node_for_point, get_offset_from_position,
+        get_point_from_offset, get_position_from_offset,
+    },
+};
+
+/// Stands in for the member name while the document is parsed, `$user->` alone
+/// is not something the parser can make sense of.
+const PLACEHOLDER: &str = "__pherris_completion";
+
+/// The variables every scope can use.
+const SUPERGLOBALS: [&str; 9] = [
+    "$GLOBALS",
+    "$_SERVER",
+    "$_GET",
+    "$_POST",
+    "$_FILES",
+    "$_COOKIE",
+    "$_SESSION",
+    "$_REQUEST",
+    "$_ENV",
+];
+
+/// Class names are completed from every class we know about, this many are
+/// sent at most while the client keeps asking for more as the name is typed.
+const MAX_CLASS_NAMES: usize = 100;
+
+/// Returns the completion items for the position of a document.
+pub fn get_completion_list(uri: &Url, position: &Position, state: &State) -> CompletionList {
+    let Some(document) = state.document_map.get(uri).map(|document| document.clone()) else {
+        return CompletionList::default();
+    };
+    let offset = get_offset_from_position(&document, position);
+
+    let members =
+        get_member_items(&document, offset, position, state).map(|items| CompletionList {
+            is_incomplete: false,
+            items,
+        });
+
+    members
+        .or_else(|| get_variable_list(uri, &document, offset, position, state))
+        .or_else(|| get_namespace_list(uri, &document, offset, state))
+        .or_else(|| get_class_name_list(uri, &document, offset, state))
+        .unwrap_or_default()
+}
+
+/// Completes the members after `->`, `?->` and `::`.
+fn get_member_items(
+    document: &str,
+    offset: usize,
+    position: &Position,
+    state: &State,
+) -> Option<Vec<CompletionItem>> {
+    let before = document[..offset].trim_end_matches(is_name_char);
+    let (before, is_static) = match before.strip_suffix("::$") {
+        Some(before) => (before, true),
+        None => match before.strip_suffix("::") {
+            Some(before) => (before, true),
+            None => (before.strip_suffix("->")?, false),
+        },
+    };
+    if before.trim_end().is_empty() {
+        return None;
+    }
+
+    // the incomplete statement is closed as well when that is what it takes to parse it
+    [PLACEHOLDER.to_string(), format!("{};", PLACEHOLDER)]
+        .iter()
+        .find_map(|placeholder| {
+            let content = format!(
+                "{}{}{}",
+                &document[..offset],
+                placeholder,
+                &document[offset..]
+            );
+            complete_members(&content, offset, position, is_static, state)
+        })
+}
+
+fn complete_members(
+    content: &str,
+    offset: usize,
+    position: &Position,
+    is_static: bool,
+    state: &State,
+) -> Option<Vec<CompletionItem>> {
+    let interner = ThreadedInterner::new();
+    let (program, _) = parse(
+        &interner,
+        Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+    );
+    let nodes = get_nodes_for_position(&Node::Program(&program), content, position);
+
+    let (depth, receiver, selector) =
+        nodes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, node)| match node {
+                Node::PropertyAccess(access) => {
+                    Some((depth, &*access.object, access.property.span()))
+                }
+                Node::NullSafePropertyAccess(access) => {
+                    Some((depth, &*access.object, access.property.span()))
+                }
+                Node::MethodCall(call) => Some((depth, &*call.object, call.method.span())),
+                Node::NullSafeMethodCall(call) => Some((depth, &*call.object, call.method.span())),
+                Node::StaticMethodCall(call) => Some((depth, &*call.class, call.method.span())),
+                Node::StaticPropertyAccess(access) => {
+                    Some((depth, &*access.class, access.property.span()))
+                }
+                Node::ClassConstantAccess(access) => {
+                    Some((depth, &*access.class, access.constant.span()))
+                }
+                _ => None,
+            })?;
+    if selector.start.offset > offset || offset > selector.end.offset {
+        return None;
+    }
+
+    let names = get_name_context(&program, content, offset);
+    let scope = Scope {
+        document: content,
+        nodes: &nodes[..depth],
+        names: &names,
+    };
+    let current_class = get_current_class(&scope);
+    // `parent::foo()` calls an instance method the same way `$this->foo()` does
+    let is_relative = is_static && is_relative_class(receiver, content);
+
+    let mut items: Vec<CompletionItem> = vec![];
+    for class in get_classes(&infer_expression_type(receiver, &scope, state)) {
+        for found in get_members(&class, state) {
+            let member = &found.member;
+            let is_accessible = match member.kind {
+                MemberKind::Method => match is_static {
+                    true => member.is_static || is_relative,
+                    false => !member.is_static,
+                },
+                MemberKind::Property => member.is_static == is_static,
+                MemberKind::Constant | MemberKind::EnumCase => is_static,
+            };
+            if !is_accessible || !is_visible(&found, current_class.as_deref(), state) {
+                continue;
+            }
+
+            let label = get_label(&found);
+            let kind = Some(match member.kind {
+                MemberKind::Method => CompletionItemKind::METHOD,
+                MemberKind::Property => CompletionItemKind::PROPERTY,
+                MemberKind::Constant => CompletionItemKind::CONSTANT,
+                MemberKind::EnumCase => CompletionItemKind::ENUM_MEMBER,
+            });
+            // the receiver may be one of several classes sharing members
+            if items
+                .iter()
+                .any(|item| item.label == label && item.kind == kind)
+            {
+                continue;
+            }
+
+            items.push(CompletionItem {
+                label,
+                kind,
+                detail: Some(get_signature(&found)),
+                ..Default::default()
+            });
+        }
+    }
+
+    Some(items)
+}
+
+/// Completes the variables defined before the cursor in the function-like it
+/// is in, arrow functions seeing the variables of the scope around them as
+/// well. The definitions are found in the tree sitter tree, which survives the
+/// incomplete code being typed.
+fn get_variable_list(
+    uri: &Url,
+    document: &str,
+    offset: usize,
+    position: &Position,
+    state: &State,
+) -> Option<CompletionList> {
+    let before = &document[..offset];
+    let start = before.trim_end_matches(is_name_char).strip_suffix('$')?;
+    // `Foo::$bar` is a static property and `$$foo` a variable variable
+    if start.ends_with([':', '$']) {
+        return None;
+    }
+
+    let tree = state.ast_map.get(uri)?;
+    let node = get_node_for_point(&tree, get_point_from_offset(document, start.len()))?;
+    let query = variable_declaration_query().expect("to create variable declaration query");
+
+    let mut names: Vec<String> = vec![];
+    let mut scope = get_function_like_parent(&node);
+    loop {
+        let root = scope.unwrap_or_else(|| tree.root_node());
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&query, root, document.as_bytes());
+        while let Some(match_) = matches.next() {
+            for capture in match_.captures {
+                let name = &document[capture.node.byte_range()];
+                if capture.node.end_byte() <= start.len()
+                    && get_function_like_parent(&capture.node) == scope
+                    && !names.iter().any(|known| known == name)
+                {
+                    names.push(name.to_string());
+                }
+            }
+        }
+
+        match scope {
+            Some(node) if node.kind() == "arrow_function" => {
+                scope = get_function_like_parent(&node)
+            }
+            _ => break,
+        }
+    }
+    if has_this(node) {
+        names.push(String::from("$this"));
+    }
+
+    let prefix = before[start.len()..].to_ascii_lowercase();
+    names.retain(|name| name.to_ascii_lowercase().starts_with(&prefix));
+    let types = infer_variable_types(document, offset, position, &names, state);
+
+    let range = Range::new(get_position_from_offset(document, start.len()), *position);
+    let item = |name: &str, detail: Option<String>| CompletionItem {
+        label: name.to_string(),
+        kind: Some(CompletionItemKind::VARIABLE),
+        detail,
+        filter_text: Some(name.to_string()),
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+            range,
+            name.to_string(),
+        ))),
+        ..Default::default()
+    };
+
+    let mut items: Vec<CompletionItem> = names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let types = types.get(index).filter(|types| !types.is_empty());
+            item(name, types.map(|types| format_types(types)))
+        })
+        .collect();
+    items.extend(
+        SUPERGLOBALS
+            .iter()
+            .filter(|name| name.to_ascii_lowercase().starts_with(&prefix))
+            .map(|name| item(name, Some(String::from("array")))),
+    );
+
+    Some(CompletionList {
+        is_incomplete: false,
+        items,
+    })
+}
+
+/// Whether `$this` is bound at the node, which it is in methods and the
+/// closures in them unless they are static.
+fn has_this(node: tree_sitter::Node) -> bool {
+    let mut function = get_function_like_parent(&node);
+    while let Some(node) = function {
+        match node.kind() {
+            "method_declaration" => {
+                let mut cursor = node.walk();
+                let is_static = node
+                    .children(&mut cursor)
+                    .any(|child| child.kind() == "static_modifier");
+                return !is_static;
+            }
+            "function_definition" => return false,
+            _ if node.child_by_field_name("static_modifier").is_some() => return false,
+            _ => function = get_function_like_parent(&node),
+        }
+    }
+
+    false
+}
+
+/// Infers the types the variables have at the offset, once the name being
+/// typed is completed well enough for the document to parse. Nothing is
+/// returned when it does not.
+fn infer_variable_types(
+    document: &str,
+    offset: usize,
+    position: &Position,
+    names: &[String],
+    state: &State,
+) -> Vec<Vec<Type>> {
+    for placeholder in [PLACEHOLDER.to_string(), format!("{};", PLACEHOLDER)] {
+        let content = format!(
+            "{}{}{}",
+            &document[..offset],
+            placeholder,
+            &document[offset..]
+        );
+        let interner = ThreadedInterner::new();
+        let (program, _) = parse(
+            &interner,
+            Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+        );
+        let nodes = get_nodes_for_position(&Node::Program(&program), &content, position);
+        if !matches!(nodes.last(), Some(Node::DirectVariable(_))) {
+            continue;
+        }
+
+        let context = get_name_context(&program, &content, offset);
+        let scope = Scope {
+            document: &content,
+            nodes: &nodes,
+            names: &context,
+        };
+
+        return names
+            .iter()
+            .map(|name| resolve_variable_type(name, offset, &scope, state))
+            .collect();
+    }
+
+    vec![]
+}
+
+/// Completes the next segment of a namespace in `use` statements, namespace
+/// declarations and fully qualified names, together with the classes of the
+/// namespace typed so far. Namespace declarations are offered the namespace
+/// the PSR-4 prefixes expect for the file as well.
+fn get_namespace_list(
+    uri: &Url,
+    document: &str,
+    offset: usize,
+    state: &State,
+) -> Option<CompletionList> {
+    let before = &document[..offset];
+    let start = before.trim_end_matches(|char| is_name_char(char) || char == '\\');
+    let name = &before[start.len()..];
+
+    let is_declaration = ends_with_word(start.trim_end(), "namespace");
+    let is_import = ends_with_word(start.trim_end(), "use");
+    if !is_declaration && !is_import && !name.starts_with('\\') {
+        return None;
+    }
+
+    let (namespace, prefix) = name
+        .trim_start_matches('\\')
+        .rsplit_once('\\')
+        .unwrap_or(("", name.trim_start_matches('\\')));
+    let prefix = prefix.to_ascii_lowercase();
+    let matches = |name: &str| name.to_ascii_lowercase().starts_with(&prefix);
+
+    let mut items = vec![];
+    if is_declaration {
+        let expected = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| resolve_file_namespace(&path, state));
+
+        if let Some(expected) = expected {
+            let range = Range::new(
+                get_position_from_offset(document, start.len()),
+                get_position_from_offset(document, offset),
+            );
+            items.push(CompletionItem {
+                label: expected.clone(),
+                kind: Some(CompletionItemKind::MODULE),
+                detail: Some(String::from("PSR-4 namespace of the file")),
+                preselect: Some(true),
+                filter_text: Some(expected.clone()),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(range, expected))),
+                ..Default::default()
+            });
+        }
+    }
+
+    let namespaces = state.namespaces.read().unwrap();
+    let Some(node) = namespaces.find(namespace) else {
+        return Some(CompletionList {
+            is_incomplete: false,
+            items,
+        });
+    };
+
+    for child in node.namespaces().filter(|child| matches(&child.name)) {
+        items.push(CompletionItem {
+            label: child.name.clone(),
+            kind: Some(CompletionItemKind::MODULE),
+            detail: Some(qualify_name(namespace, &child.name)),
+            ..Default::default()
+        });
+    }
+
+    // a namespace is declared, not a class
+    if !is_declaration {
+        for fqn in node.classes() {
+            let name = fqn.rsplit('\\').next().unwrap_or(fqn);
+            if !matches(name) {
+                continue;
+            }
+
+            let path = state.class_map.get(fqn).map(|path| path.clone());
+            items.push(CompletionItem {
+                label: name.to_string(),
+                kind: Some(get_class_kind(fqn, &path.unwrap_or_default(), state)),
+                detail: Some(fqn.clone()),
+                ..Default::default()
+            });
+        }
+    }
+
+    Some(CompletionList {
+        is_incomplete: false,
+        items,
+    })
+}
+
+/// Completes class names where a class is expected, importing the class when
+/// it is not available under its short name yet.
+fn get_class_name_list(
+    uri: &Url,
+    document: &str,
+    offset: usize,
+    state: &State,
+) -> Option<CompletionList> {
+    let before = &document[..offset];
+    let start = before.trim_end_matches(is_name_char);
+    // qualified names are completed segment by segment
+    if start.ends_with(['\\', '$']) || !expects_class_name(start) {
+        return None;
+    }
+
+    let prefix = before[start.len()..].to_ascii_lowercase();
+    let tree = state.ast_map.get(uri)?;
+    let imports = Imports::from_tree(&tree, document, get_point_from_offset(document, offset));
+
+    let mut items = vec![];
+    let mut is_incomplete = false;
+    for entry in state.class_map.iter() {
+        let fqn = entry.key().trim_start_matches('\\');
+        let name = fqn.rsplit('\\').next().unwrap_or(fqn);
+        let matches = |name: &str| name.to_ascii_lowercase().starts_with(&prefix);
+        // working out the import is only worth it for the classes that can match
+        if !matches(name) && !imports.may_rename(fqn) {
+            continue;
+        }
+        let import = imports.import_class(fqn);
+        if !matches(name) && !matches(&import.name) {
+            continue;
+        }
+        if items.len() == MAX_CLASS_NAMES {
+            is_incomplete = true;
+            break;
+        }
+
+        items.push(CompletionItem {
+            label: import.name,
+            kind: Some(get_class_kind(fqn, entry.value(), state)),
+            detail: Some(fqn.to_string()),
+            additional_text_edits: import.edit.map(|edit| vec![edit]),
+            ..Default::default()
+        });
+    }
+
+    Some(CompletionList {
+        is_incomplete,
+        items,
+    })
+}
+
+/// Tells the classes, interfaces and enums apart for the files that are
+/// indexed already, indexing every class just for its icon would be too slow.
+fn get_class_kind(fqn: &str, path: &str, state: &State) -> CompletionItemKind {
+    let kind = state.symbol_index.get(path).and_then(|symbols| {
+        symbols
+            .iter()
+            .find(|symbol| symbol.fqn.eq_ignore_ascii_case(fqn))
+            .map(|symbol| symbol.kind)
+    });
+
+    match kind {
+        Some(SymbolKind::Interface) => CompletionItemKind::INTERFACE,
+        Some(SymbolKind::Enum) => CompletionItemKind::ENUM,
+        _ => CompletionItemKind::CLASS,
+    }
+}
+
+/// Whether a class name is expected after the text: after `new`, in `extends`,
+/// `implements` and `catch` clauses and where parameter, return and property
+/// types are declared.
+fn expects_class_name(before: &str) -> bool {
+    // `implements Foo, Bar`
+    let mut list = before.trim_end();
+    while let Some(rest) = list.strip_suffix(',') {
+        list = strip_name(rest);
+    }
+    if ["new", "extends", "implements"]
+        .iter()
+        .any(|keyword| ends_with_word(list, keyword))
+    {
+        return true;
+    }
+
+    // `?Foo`, `Foo|Bar` and `Foo&Bar` are types as well
+    let mut hint = before.trim_end();
+    loop {
+        if let Some(rest) = hint.strip_suffix('?') {
+            hint = rest.trim_end();
+        } else if let Some(rest) = hint.strip_suffix(['|', '&']) {
+            hint = strip_name(rest);
+        } else {
+            break;
+        }
+    }
+
+    // `public static Foo $foo`
+    let is_after_modifier = |text: &str| {
+        ["public", "protected", "private", "readonly"]
+            .iter()
+            .any(|modifier| ends_with_word(text, modifier))
+    };
+    if is_after_modifier(hint)
+        || (ends_with_word(hint, "static") && is_after_modifier(hint[..hint.len() - 6].trim_end()))
+    {
+        return true;
+    }
+    if let Some(rest) = hint.strip_suffix(':') {
+        // `function foo(): Foo`
+        return rest
+            .trim_end()
+            .strip_suffix(')')
+            .and_then(find_open_parenthesis)
+            .is_some_and(is_function_signature);
+    }
+    // `function foo(Foo $foo, Bar`
+    let parameters = match hint.strip_suffix('(') {
+        Some(rest) if ends_with_word(rest.trim_end(), "catch") => return true,
+        Some(rest) => Some(rest),
+        None => hint.strip_suffix(',').and_then(find_open_parenthesis),
+    };
+
+    parameters.is_some_and(is_function_signature)
+}
+
+/// Returns the text in front of the parenthesis the end of the text is in.
+fn find_open_parenthesis(text: &str) -> Option<&str> {
+    let mut depth = 0;
+    for (index, char) in text.char_indices().rev() {
+        match char {
+            ')' => depth += 1,
+            '(' if depth == 0 => return Some(&text[..index]),
+            '(' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Whether the text ends where the parameters of a function-like start, like
+/// `function foo`, `function` or `fn`.
+fn is_function_signature(text: &str) -> bool {
+    let text = text.trim_end();
+    let name = strip_name(text);
+    let name = name.strip_suffix('&').unwrap_or(name).trim_end();
+
+    ends_with_word(text, "function")
+        || ends_with_word(text, "fn")
+        || ends_with_word(name, "function")
+}
+
+fn strip_name(text: &str) -> &str {
+    text.trim_end()
+        .trim_end_matches(|char| is_name_char(char) || char == '\\')
+        .trim_end()
+}
+
+/// Whether the text ends with the keyword, the keyword not being part of a
+/// longer name, a variable or a member.
+fn ends_with_word(text: &str, word: &str) -> bool {
+    let Some(rest) = text
+        .len()
+        .checked_sub(word.len())
+        .map(|start| &text[..start])
+    else {
+        return false;
+    };
+
+    text.is_char_boundary(rest.len())
+        && text[rest.len()..].eq_ignore_ascii_case(word)
+        && !rest.ends_with(|char| is_name_char(char) || matches!(char, '$' | '\\' | '>' | ':'))
+}
+
+/// Whether the class the receiver is written in may see the member: private
+/// members only from their own class, protected ones from the classes that
+/// share the hierarchy they are declared in.
+fn is_visible(found: &ClassMember, current_class: Option<&str>, state: &State) -> bool {
+    let declaring_class = found.class.fqn.as_str();
+    let Some(current_class) = current_class else {
+        return found.member.visibility == Visibility::Public;
+    };
+
+    match found.member.visibility {
+        Visibility::Public => true,
+        // members of a trait become members of the class using it
+        Visibility::Private => {
+            declaring_class.eq_ignore_ascii_case(current_class)
+                || find_class_symbol(current_class, state)
+                    .and_then(|symbol| symbol.class)
+                    .is_some_and(|info| {
+                        info.traits
+                            .iter()
+                            .any(|r#trait| r#trait.eq_ignore_ascii_case(declaring_class))
+                    })
+        }
+        Visibility::Protected => {
+            is_subclass_of(current_class, declaring_class, state)
+                || is_subclass_of(declaring_class, current_class, state)
+        }
+    }
+}
+
+fn is_relative_class(receiver: &Expression, document: &str) -> bool {
+    match receiver {
+        Expression::Self_(_) | Expression::Static(_) | Expression::Parent(_) => true,
+        Expression::Identifier(identifier) => {
+            is_relative_class_name(&get_node_name(document, identifier))
+        }
+        _ => false,
+    }
+}
+
+fn get_label(found: &ClassMember) -> String {
+    match found.member.kind {
+        MemberKind::Property if found.member.is_static => format!("${}", found.member.name),
+        _ => found.member.name.clone(),
+    }
+}
+
+/// Renders the declaration of a member the way it is written in its class,
+/// with the class names shortened, e.g. `public function find(int $id): ?User`.
+fn get_signature(found: &ClassMember) -> String {
+    let member = &found.member;
+    let r#type = member
+        .type_hint
+        .as_ref()
+        .or(member.doc_type.as_ref())
+        .map(|r#type| shorten_class_names(r#type));
+
+    let visibility = match member.visibility {
+        Visibility::Public => "public",
+        Visibility::Protected => "protected",
+        Visibility::Private => "private",
+    };
+    let mut modifiers = vec![visibility];
+    if member.is_abstract {
+        modifiers.insert(0, "abstract");
+    }
+    if member.is_static && member.kind != MemberKind::Constant {
+        modifiers.push("static");
+    }
+    let modifiers = modifiers.join(" ");
+
+    match member.kind {
+        MemberKind::Method => {
+            let parameters = member
+                .parameters
+                .iter()
+                .map(|parameter| parameter.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let return_type = r#type
+                .map(|r#type| format!(": {}", r#type))
+                .unwrap_or_default();
+
+            format!(
+                "{} function {}({}){}",
+                modifiers, member.name, parameters, return_type
+            )
+        }
+        MemberKind::Property => match r#type {
+            Some(r#type) => format!("{} {} ${}", modifiers, r#type, member.name),
+            None => format!("{} ${}", modifiers, member.name),
+        },
+        MemberKind::Constant => format!("{} const {}", modifiers, member.name),
+        MemberKind::EnumCase => format!("case {}", member.name),
+    }
+}
+
+fn is_name_char(char: char) -> bool {
+    char.is_alphanumeric() || char == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expects_class_name;
+
+    #[test]
+    fn expects_class_names() {
+        for before in [
+            "$user = new ",
+            "class Admin extends ",
+            "class Admin extends User implements Countable, ",
+            "} catch (",
+            "} catch (InvalidArgumentException | ",
+            "public function show(",
+            "public function show(int $id, ?",
+            "function (Request|",
+            "fn(User $user): ",
+            "public function __construct(private readonly ",
+            "    protected ?",
+            "    public static ",
+        ] {
+            assert!(expects_class_name(before), "{}", before);
+        }
+
+        for before in [
+            "$new",
+            "$user->new ",
+            "echo ",
+            "show(",
+            "$a ? show() : ",
+            "$a || ",
+            "public function ",
+        ] {
+            assert!(!expects_class_name(before), "{}", before);
+        }
+    }
+}
@@ -1,31 +1,153 @@
-use std::sync::RwLock;
+use std::{cmp::Reverse, path::Path, sync::RwLock};
 
+use dashmap::DashMap;
+use serde_json::Value;
 use streaming_iterator::StreamingIterator;
 use tree_sitter::{Query, QueryCursor};
 
 use crate::lsp::state::State;
 
-use super::parser::Parser;
+use super::{
+    cache::load_autoload_map,
+    indexer::{index_paths, index_workspace},
+    namespaces::NamespaceTrie,
+    parser::Parser,
+};
 
-//TODO: if the autoload map is not present we should probably
-//index the project ourselfs
 //TODO: Use mago parser instead of tree sitter
 pub fn load_autoload_class_map(parser: &RwLock<Parser>, state: &State) {
-    let root_path = state.root_path.read().unwrap();
-    let autoload_classmap_path = format!("{}/vendor/composer/autoload_classmap.php", *root_path);
-    let vendor_path = format!("{}/vendor", *root_path);
+    let Some(entries) = read_autoload_class_map(parser, state) else {
+        // no optimized autoloader was dumped so we index the project ourselves
+        index_root(state);
+        return;
+    };
+
+    for (namespace, full_path) in entries {
+        state.namespaces.write().unwrap().insert(&namespace);
+        state.class_map.insert(namespace, full_path);
+    }
+}
 
-    let contents = std::fs::read(autoload_classmap_path);
+fn read_autoload_class_map(
+    parser: &RwLock<Parser>,
+    state: &State,
+) -> Option<Vec<(String, String)>> {
+    let root_path = state.root_path.read().unwrap().clone();
+    let autoload_classmap_path = format!("{}/vendor/composer/autoload_classmap.php", root_path);
 
-    if contents.is_err() {
-        return;
+    load_autoload_map(&autoload_classmap_path, state, |contents| {
+        parse_autoload_map(contents, &root_path, parser)
+    })
+}
+
+fn index_root(state: &State) {
+    let root_path = state.root_path.read().unwrap().clone();
+    if !root_path.is_empty() {
+        index_workspace(Path::new(root_path.as_str()), state);
+    }
+}
+
+/// Reloads everything read from composer after its autoload files or the root
+/// `composer.json` changed. The maps are built aside and swapped in afterwards
+/// so lookups in the meantime keep seeing the previous ones.
+pub fn reload_autoload(parser: &RwLock<Parser>, state: &State) {
+    let root_path = state.root_path.read().unwrap().clone();
+    let class_map: DashMap<String, String> = match read_autoload_class_map(parser, state) {
+        Some(entries) => entries.into_iter().collect(),
+        None => {
+            index_root(state);
+            DashMap::new()
+        }
+    };
+
+    // the files we indexed ourselves are more up to date than the generated map
+    for symbols in state.symbol_index.iter() {
+        for symbol in symbols.iter().filter(|s| s.kind.is_class_like()) {
+            class_map.insert(symbol.fqn.clone(), symbols.key().clone());
+        }
+    }
+
+    let psr4_map = DashMap::new();
+    let psr0_map = DashMap::new();
+    read_autoload_prefixes(&root_path, &psr4_map, &psr0_map, parser);
+
+    replace_map(&state.class_map, class_map);
+    *state.namespaces.write().unwrap() = NamespaceTrie::from_class_map(&state.class_map);
+    replace_map(&state.psr4_map, psr4_map);
+    replace_map(&state.psr0_map, psr0_map);
+
+    load_autoload_files(parser, state);
+}
+
+/// Replaces the entries of the map, never removing the keys that are kept.
+fn replace_map<V>(map: &DashMap<String, V>, entries: DashMap<String, V>) {
+    map.retain(|key, _| entries.contains_key(key));
+    for (key, value) in entries {
+        map.insert(key, value);
+    }
+}
+
+/// Returns whether the path is one of the composer files the autoload maps are
+/// read from.
+pub fn is_autoload_file(path: &Path, root_path: &str) -> bool {
+    let root = Path::new(root_path);
+    if path == root.join("composer.json") || path == root.join("composer.lock") {
+        return true;
     }
-    let contents = contents.unwrap();
+
+    path.parent() == Some(root.join("vendor/composer").as_path())
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("autoload_") && name.ends_with(".php"))
+}
+
+/// Loads the files composer includes on every request (`autoload_files.php` and
+/// the `files` sections of `composer.json`) and indexes the functions they declare.
+pub fn load_autoload_files(parser: &RwLock<Parser>, state: &State) {
+    let root_path = state.root_path.read().unwrap().clone();
+    let mut files: Vec<String> = vec![];
+
+    let autoload_files_path = format!("{}/vendor/composer/autoload_files.php", root_path);
+    if let Some(entries) = load_autoload_map(&autoload_files_path, state, |contents| {
+        parse_autoload_map(contents, &root_path, parser)
+    }) {
+        files.extend(entries.into_iter().map(|(_, path)| path));
+    }
+
+    if let Some(json) = read_composer_json(&root_path) {
+        for section in ["autoload", "autoload-dev"] {
+            let Some(entries) = json[section]["files"].as_array() else {
+                continue;
+            };
+
+            for file in entries.iter().filter_map(Value::as_str) {
+                let path = format!("{}/{}", root_path, file.trim_start_matches("./"));
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+        }
+    }
+
+    files.retain(|file| !state.symbol_index.contains_key(file));
+    index_paths(&files, state);
+}
+
+/// Parses the `'key' => $vendorDir . '/path'` entries of a generated composer
+/// autoload file into keys and absolute paths.
+fn parse_autoload_map(
+    contents: &[u8],
+    root_path: &str,
+    parser: &RwLock<Parser>,
+) -> Vec<(String, String)> {
+    let vendor_path = format!("{}/vendor", root_path);
+    let mut out = vec![];
 
     let tree = parser
         .write()
         .unwrap()
-        .parse(&contents)
+        .parse(contents)
         .expect("to parse file");
     let query = Query::new(
         &tree_sitter_php::LANGUAGE_PHP.into(),
@@ -41,7 +163,7 @@ pub fn load_autoload_class_map(parser: &RwLock<Parser>, state: &State) {
     )
     .expect("to create query");
     let mut cursor = QueryCursor::new();
-    let mut matches = cursor.matches(&query, tree.root_node(), contents.as_slice());
+    let mut matches = cursor.matches(&query, tree.root_node(), contents);
 
     while let Some(match_) = matches.next() {
         let mut namespace = None;
@@ -69,7 +191,7 @@ pub fn load_autoload_class_map(parser: &RwLock<Parser>, state: &State) {
                 let path_string = path_string.trim_matches('\'').to_string();
                 let full_path = match base_dir_string.as_str() {
                     "$vendorDir" => format!("{}{}", vendor_path, path_string),
-                    "$baseDir" => format!("{}{}", *root_path, path_string),
+                    "$baseDir" => format!("{}{}", root_path, path_string),
                     _ => path_string,
                 };
 
@@ -77,17 +199,262 @@ pub fn load_autoload_class_map(parser: &RwLock<Parser>, state: &State) {
                     .trim_matches('\'')
                     .replace("\\\\", "\\")
                     .to_string();
-                let full_path = full_path.to_string();
-                state.class_map.insert(namespace, full_path);
+                out.push((namespace, full_path));
             }
         }
     }
+
+    out
+}
+
+/// Loads the PSR-4 and PSR-0 prefix to directory maps composer generates into
+/// `autoload_psr4.php` and `autoload_namespaces.php`, along with the `autoload`
+/// and `autoload-dev` sections of the root `composer.json`.
+pub fn load_autoload_prefixes(parser: &RwLock<Parser>, state: &State) {
+    let root_path = state.root_path.read().unwrap().clone();
+    read_autoload_prefixes(&root_path, &state.psr4_map, &state.psr0_map, parser);
+}
+
+fn read_autoload_prefixes(
+    root_path: &str,
+    psr4_map: &DashMap<String, Vec<String>>,
+    psr0_map: &DashMap<String, Vec<String>>,
+    parser: &RwLock<Parser>,
+) {
+    let composer_dir = format!("{}/vendor/composer", root_path);
+
+    load_autoload_prefix_file(
+        &format!("{}/autoload_psr4.php", composer_dir),
+        root_path,
+        psr4_map,
+        parser,
+    );
+    load_autoload_prefix_file(
+        &format!("{}/autoload_namespaces.php", composer_dir),
+        root_path,
+        psr0_map,
+        parser,
+    );
+    load_composer_json(root_path, psr4_map, psr0_map);
+}
+
+fn load_autoload_prefix_file(
+    file_path: &str,
+    root_path: &str,
+    map: &DashMap<String, Vec<String>>,
+    parser: &RwLock<Parser>,
+) {
+    let Ok(contents) = std::fs::read(file_path) else {
+        return;
+    };
+    let vendor_path = format!("{}/vendor", root_path);
+
+    let tree = parser
+        .write()
+        .unwrap()
+        .parse(&contents)
+        .expect("to parse file");
+    let query = Query::new(
+        &tree_sitter_php::LANGUAGE_PHP.into(),
+        r#"
+        (array_creation_expression
+            (array_element_initializer
+                (string) @prefix
+                (array_creation_expression
+                    (array_element_initializer
+                        (binary_expression
+                            (variable_name) @dir
+                            "."
+                            (string) @path)))))
+    "#,
+    )
+    .expect("to create query");
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(&query, tree.root_node(), contents.as_slice());
+
+    while let Some(match_) = matches.next() {
+        let mut prefix = None;
+        let mut path = None;
+        let mut dir = None;
+
+        for capture in match_.captures {
+            let text = std::str::from_utf8(&contents[capture.node.byte_range()]).ok();
+
+            match query.capture_names()[capture.index as usize] {
+                "prefix" => prefix = text,
+                "dir" => dir = text,
+                "path" => path = text,
+                _ => {}
+            }
+        }
+
+        if let (Some(prefix), Some(dir), Some(path)) = (prefix, dir, path) {
+            let path = path.trim_matches('\'');
+            let full_path = match dir {
+                "$vendorDir" => format!("{}{}", vendor_path, path),
+                "$baseDir" => format!("{}{}", root_path, path),
+                _ => path.to_string(),
+            };
+
+            let prefix = prefix.trim_matches('\'').replace("\\\\", "\\");
+            add_prefix_directory(map, prefix, full_path);
+        }
+    }
+}
+
+fn read_composer_json(root_path: &str) -> Option<Value> {
+    let contents = std::fs::read_to_string(format!("{}/composer.json", root_path)).ok()?;
+
+    serde_json::from_str(&contents).ok()
+}
+
+fn load_composer_json(
+    root_path: &str,
+    psr4_map: &DashMap<String, Vec<String>>,
+    psr0_map: &DashMap<String, Vec<String>>,
+) {
+    let Some(json) = read_composer_json(root_path) else {
+        return;
+    };
+
+    for section in ["autoload", "autoload-dev"] {
+        for (key, map) in [("psr-4", psr4_map), ("psr-0", psr0_map)] {
+            let Some(prefixes) = json[section][key].as_object() else {
+                continue;
+            };
+
+            for (prefix, dirs) in prefixes {
+                let dirs = match dirs {
+                    Value::String(dir) => vec![dir.as_str()],
+                    Value::Array(dirs) => dirs.iter().filter_map(Value::as_str).collect(),
+                    _ => continue,
+                };
+
+                for dir in dirs {
+                    let dir = dir.trim_end_matches('/');
+                    let full_path = if dir.is_empty() || dir == "." {
+                        root_path.to_string()
+                    } else {
+                        format!("{}/{}", root_path, dir.trim_start_matches("./"))
+                    };
+
+                    add_prefix_directory(map, prefix.clone(), full_path);
+                }
+            }
+        }
+    }
+}
+
+fn add_prefix_directory(map: &DashMap<String, Vec<String>>, prefix: String, dir: String) {
+    let mut dirs = map.entry(prefix).or_default();
+    if !dirs.contains(&dir) {
+        dirs.push(dir);
+    }
+}
+
+/// Finds the file a class should live in, first looking at the class map and
+/// then computing the expected path from the PSR-4 and PSR-0 prefixes.
+pub fn resolve_class_path(fqn: &str, state: &State) -> Option<String> {
+    let fqn = fqn.trim_start_matches('\\');
+    if let Some(path) = state.class_map.get(fqn) {
+        return Some(path.clone());
+    }
+
+    psr4_candidates(fqn, state)
+        .into_iter()
+        .chain(psr0_candidates(fqn, state))
+        .find(|path| Path::new(path).is_file())
+}
+
+/// Returns the namespace the PSR-4 prefixes expect the classes of a file to
+/// be in, going by the directory the file is in.
+pub fn resolve_file_namespace(path: &Path, state: &State) -> Option<String> {
+    let directory = path.parent()?;
+
+    // the most specific directory wins
+    let (prefix, relative) = state
+        .psr4_map
+        .iter()
+        .flat_map(|entry| {
+            let prefix = entry.key().clone();
+            entry
+                .value()
+                .iter()
+                .filter_map(|dir| directory.strip_prefix(dir).ok())
+                .map(|relative| (prefix.clone(), relative.to_path_buf()))
+                .collect::<Vec<_>>()
+        })
+        .min_by_key(|(_, relative)| relative.components().count())?;
+
+    let segments = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string());
+
+    Some(
+        std::iter::once(prefix.trim_end_matches('\\').to_string())
+            .chain(segments)
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>()
+            .join("\\"),
+    )
+}
+
+fn psr4_candidates(fqn: &str, state: &State) -> Vec<String> {
+    let mut prefixes: Vec<(String, Vec<String>)> = state
+        .psr4_map
+        .iter()
+        .filter(|entry| fqn.starts_with(entry.key().as_str()))
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    // the most specific prefix wins
+    prefixes.sort_by_key(|(prefix, _)| Reverse(prefix.len()));
+
+    prefixes
+        .into_iter()
+        .flat_map(|(prefix, dirs)| {
+            let relative = fqn[prefix.len()..].replace('\\', "/");
+            dirs.into_iter()
+                .map(move |dir| format!("{}/{}.php", dir, relative))
+        })
+        .collect()
+}
+
+fn psr0_candidates(fqn: &str, state: &State) -> Vec<String> {
+    // namespace separators map to directories, and so do underscores in the class name
+    let relative = match fqn.rfind('\\') {
+        Some(idx) => format!(
+            "{}/{}",
+            fqn[..idx].replace('\\', "/"),
+            fqn[idx + 1..].replace('_', "/")
+        ),
+        None => fqn.replace('_', "/"),
+    };
+
+    let mut prefixes: Vec<(String, Vec<String>)> = state
+        .psr0_map
+        .iter()
+        .filter(|entry| fqn.starts_with(entry.key().as_str()))
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    prefixes.sort_by_key(|(prefix, _)| Reverse(prefix.len()));
+
+    prefixes
+        .into_iter()
+        .flat_map(|(_, dirs)| dirs)
+        .map(|dir| format!("{}/{}.php", dir, relative))
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        analyzer::{composer::load_autoload_class_map, parser::Parser},
+        analyzer::{
+            composer::{
+                load_autoload_class_map, load_autoload_files, load_autoload_prefixes,
+                resolve_class_path, resolve_file_namespace,
+            },
+            parser::Parser,
+        },
         lsp::state::State,
     };
     use dashmap::DashMap;
@@ -145,6 +512,235 @@ mod tests {
         }
     }
 
+    #[test]
+    fn index_workspace_without_class_map() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let temp_dir_path = temp_dir.path();
+        std::fs::create_dir_all(temp_dir_path.join("src")).expect("to create directory");
+        std::fs::write(
+            temp_dir_path.join("src/Class.php"),
+            "<?php\nnamespace MyApplicationNamespace\\Testing;\n\nclass MyClass {}\n",
+        )
+        .expect("to write file");
+
+        let state = State::new(
+            DashMap::default(),
+            DashMap::default(),
+            RwLock::new(String::from(temp_dir_path.to_str().unwrap())),
+            DashMap::default(),
+            DashMap::default(),
+        );
+
+        load_autoload_class_map(&RwLock::new(Parser::new().unwrap()), &state);
+
+        assert_eq!(state.class_map.iter().count(), 1);
+        assert_eq!(
+            *state
+                .class_map
+                .get("MyApplicationNamespace\\Testing\\MyClass")
+                .unwrap(),
+            format!("{}/src/Class.php", temp_dir_path.to_str().unwrap())
+        );
+    }
+
+    #[test]
+    fn load_psr_prefixes() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let root = temp_dir.path().to_str().unwrap();
+        let composer_dir = temp_dir.path().join("vendor/composer");
+        std::fs::create_dir_all(&composer_dir).expect("to create /vendor/composer directory");
+
+        std::fs::write(
+            composer_dir.join("autoload_psr4.php"),
+            r#"<?php
+
+            // autoload_psr4.php @generated by Composer
+
+            $vendorDir = dirname(__DIR__);
+            $baseDir = dirname($vendorDir);
+
+            return array(
+                'Symfony\\Component\\Console\\' => array($vendorDir . '/symfony/console'),
+                'App\\' => array($baseDir . '/src', $baseDir . '/lib'),
+            );
+            "#,
+        )
+        .expect("to write file");
+        std::fs::write(
+            composer_dir.join("autoload_namespaces.php"),
+            r#"<?php
+
+            // autoload_namespaces.php @generated by Composer
+
+            $vendorDir = dirname(__DIR__);
+            $baseDir = dirname($vendorDir);
+
+            return array(
+                'Twig_' => array($vendorDir . '/twig/twig/lib'),
+            );
+            "#,
+        )
+        .expect("to write file");
+        std::fs::write(
+            temp_dir.path().join("composer.json"),
+            r#"{
+                "autoload": {"psr-4": {"App\\": "src/"}},
+                "autoload-dev": {"psr-4": {"App\\Tests\\": ["tests/"]}}
+            }"#,
+        )
+        .expect("to write file");
+
+        let state = State::default();
+        *state.root_path.write().unwrap() = String::from(root);
+        load_autoload_prefixes(&RwLock::new(Parser::new().unwrap()), &state);
+
+        assert_eq!(
+            *state.psr4_map.get("Symfony\\Component\\Console\\").unwrap(),
+            vec![format!("{}/vendor/symfony/console", root)]
+        );
+        assert_eq!(
+            *state.psr4_map.get("App\\").unwrap(),
+            vec![format!("{}/src", root), format!("{}/lib", root)]
+        );
+        assert_eq!(
+            *state.psr4_map.get("App\\Tests\\").unwrap(),
+            vec![format!("{}/tests", root)]
+        );
+        assert_eq!(
+            *state.psr0_map.get("Twig_").unwrap(),
+            vec![format!("{}/vendor/twig/twig/lib", root)]
+        );
+    }
+
+    #[test]
+    fn resolve_class_path_from_prefixes() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let root = temp_dir.path().to_str().unwrap();
+        for file in [
+            "lib/Models/User.php",
+            "tests/UserTest.php",
+            "legacy/Twig/Extension/Core.php",
+        ] {
+            let path = temp_dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).expect("to create directory");
+            std::fs::write(path, "<?php").expect("to write file");
+        }
+
+        let state = State::default();
+        state.psr4_map.insert(
+            String::from("App\\"),
+            vec![format!("{}/src", root), format!("{}/lib", root)],
+        );
+        state.psr4_map.insert(
+            String::from("App\\Tests\\"),
+            vec![format!("{}/tests", root)],
+        );
+        state
+            .psr0_map
+            .insert(String::from("Twig_"), vec![format!("{}/legacy", root)]);
+
+        assert_eq!(
+            resolve_class_path("App\\Models\\User", &state),
+            Some(format!("{}/lib/Models/User.php", root))
+        );
+        assert_eq!(
+            resolve_class_path("\\App\\Tests\\UserTest", &state),
+            Some(format!("{}/tests/UserTest.php", root))
+        );
+        assert_eq!(
+            resolve_class_path("Twig_Extension_Core", &state),
+            Some(format!("{}/legacy/Twig/Extension/Core.php", root))
+        );
+        assert_eq!(resolve_class_path("App\\Missing", &state), None);
+    }
+
+    #[test]
+    fn resolve_file_namespace_from_prefixes() {
+        let state = State::default();
+        state
+            .psr4_map
+            .insert(String::from("App\\"), vec![String::from("/project/src")]);
+        state.psr4_map.insert(
+            String::from("App\\Tests\\"),
+            vec![String::from("/project/src/tests")],
+        );
+
+        assert_eq!(
+            resolve_file_namespace(Path::new("/project/src/Http/Kernel.php"), &state),
+            Some(String::from("App\\Http"))
+        );
+        assert_eq!(
+            resolve_file_namespace(Path::new("/project/src/Kernel.php"), &state),
+            Some(String::from("App"))
+        );
+        assert_eq!(
+            resolve_file_namespace(Path::new("/project/src/tests/Unit/UserTest.php"), &state),
+            Some(String::from("App\\Tests\\Unit"))
+        );
+        assert_eq!(
+            resolve_file_namespace(Path::new("/elsewhere/Kernel.php"), &state),
+            None
+        );
+    }
+
+    #[test]
+    fn load_functions_from_autoload_files() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let root = temp_dir.path();
+        let composer_dir = root.join("vendor/composer");
+        std::fs::create_dir_all(&composer_dir).expect("to create /vendor/composer directory");
+        std::fs::create_dir_all(root.join("vendor/acme/helpers")).expect("to create directory");
+        std::fs::create_dir_all(root.join("src")).expect("to create directory");
+
+        std::fs::write(
+            composer_dir.join("autoload_files.php"),
+            r#"<?php
+
+            // autoload_files.php @generated by Composer
+
+            $vendorDir = dirname(__DIR__);
+            $baseDir = dirname($vendorDir);
+
+            return array(
+                '0e6d7bf4a5811bfa5cf40c5ccd6fae6a' => $vendorDir . '/acme/helpers/functions.php',
+            );
+            "#,
+        )
+        .expect("to write file");
+        std::fs::write(
+            root.join("vendor/acme/helpers/functions.php"),
+            "<?php\nnamespace Acme;\n\nfunction helper() {}\n",
+        )
+        .expect("to write file");
+        std::fs::write(
+            root.join("composer.json"),
+            r#"{"autoload": {"files": ["src/helpers.php"]}}"#,
+        )
+        .expect("to write file");
+        std::fs::write(
+            root.join("src/helpers.php"),
+            "<?php\nif (!function_exists('array_first')) {\n    function array_first($array) {}\n}\n",
+        )
+        .expect("to write file");
+
+        let state = State::default();
+        *state.root_path.write().unwrap() = String::from(root.to_str().unwrap());
+        load_autoload_files(&RwLock::new(Parser::new().unwrap()), &state);
+
+        assert_eq!(
+            *state.function_map.get("acme\\helper").unwrap(),
+            format!(
+                "{}/vendor/acme/helpers/functions.php",
+                root.to_str().unwrap()
+            )
+        );
+        assert_eq!(
+            *state.function_map.get("array_first").unwrap(),
+            format!("{}/src/helpers.php", root.to_str().unwrap())
+        );
+        assert!(state.class_map.is_empty());
+    }
+
     fn prepare_autload_file(root: &Path) {
         let file_contents = r#"
             <?php
@@ -0,0 +1,475 @@
+use mago_ast::{Program, TriviaKind};
+
+use super::{
+    indexer::MemberKind, phpdoc::get_templates, tree::get_node_name, types::is_doc_builtin_type,
+};
+
+/// A docblock comment split into its free text and its tags.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocBlock {
+    pub description: String,
+    pub tags: Vec<DocTag>,
+}
+
+/// A tag like `@param int $id The id`, stored as `param` and `int $id The id`.
+/// Tags spanning multiple lines are joined with line breaks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocTag {
+    pub name: String,
+    pub body: String,
+}
+
+impl DocBlock {
+    pub fn get_tags<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a DocTag> {
+        self.tags.iter().filter(move |tag| tag.name == name)
+    }
+
+    /// Returns the tags with the name, those prefixed with `phpstan-` or
+    /// `psalm-` first since they are meant to win over the plain ones.
+    pub fn get_type_tags<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a DocTag> {
+        self.tags
+            .iter()
+            .filter(move |tag| strip_tool_prefix(&tag.name) == Some(name))
+            .chain(self.get_tags(name))
+    }
+
+    /// Returns the description the `@param` tag of a parameter gives it, e.g.
+    /// `The new name` for `@param string $name The new name`.
+    pub fn get_param_description(&self, variable: &str) -> Option<String> {
+        self.get_tags("param").find_map(|tag| {
+            // the type is optional
+            let rest = match tag.body.starts_with(['$', '&', '.']) {
+                true => tag.body.as_str(),
+                false => split_type(&tag.body).1,
+            };
+            let (name, description) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let name = name.trim_start_matches(['&', '.']);
+
+            (name == variable && !description.trim().is_empty())
+                .then(|| description.trim().to_string())
+        })
+    }
+
+    /// Renders the description followed by the tags, with the types and
+    /// variable names of the tags that have them in code spans.
+    pub fn to_markdown(&self) -> String {
+        let mut sections = vec![];
+        if !self.description.is_empty() {
+            sections.push(self.description.clone());
+        }
+
+        for tag in self.tags.iter() {
+            let mut rendered = format!("*@{}*", tag.name);
+            let name = strip_tool_prefix(&tag.name).unwrap_or(&tag.name);
+            let (type_name, rest) = match name {
+                "param" | "return" | "var" | "throws" | "property" | "property-read"
+                | "property-write" | "method" | "mixin" | "extends" | "implements" | "see" => {
+                    split_type(&tag.body)
+                }
+                _ => ("", tag.body.as_str()),
+            };
+            // `@param $id` without a type
+            let (type_name, rest) = match type_name.starts_with('$') {
+                true => ("", tag.body.as_str()),
+                false => (type_name, rest),
+            };
+
+            if !type_name.is_empty() {
+                rendered.push_str(&format!(" `{}`", type_name));
+            }
+            let rest = match rest.split_once(char::is_whitespace) {
+                Some((variable, description)) if variable.starts_with('$') => {
+                    rendered.push_str(&format!(" `{}`", variable));
+                    description.trim_start()
+                }
+                None if rest.starts_with('$') => {
+                    rendered.push_str(&format!(" `{}`", rest));
+                    ""
+                }
+                _ => rest,
+            };
+            if !rest.is_empty() {
+                let separator = if type_name.is_empty() { " " } else { " — " };
+                rendered.push_str(separator);
+                rendered.push_str(rest);
+            }
+
+            sections.push(rendered);
+        }
+
+        sections.join("\n\n")
+    }
+}
+
+/// Returns the name of a tag like `@phpstan-param` without the prefix of the
+/// tool it is meant for.
+fn strip_tool_prefix(name: &str) -> Option<&str> {
+    name.strip_prefix("phpstan-")
+        .or_else(|| name.strip_prefix("psalm-"))
+}
+
+/// Returns the docblock right in front of the byte offset, with nothing but
+/// whitespace in between.
+pub fn find_docblock(program: &Program, document: &str, offset: usize) -> Option<DocBlock> {
+    let trivia = program
+        .trivia
+        .iter()
+        .take_while(|trivia| trivia.span.end.offset <= offset)
+        .filter(|trivia| trivia.kind.is_comment())
+        .last()?;
+
+    let between = &document[trivia.span.end.offset..offset];
+    if trivia.kind != TriviaKind::DocBlockComment || !between.trim().is_empty() {
+        return None;
+    }
+
+    Some(parse_docblock(&get_node_name(document, trivia.span)))
+}
+
+pub fn parse_docblock(text: &str) -> DocBlock {
+    let text = text.trim().trim_start_matches("/**").trim_end_matches("*/");
+
+    let mut description: Vec<&str> = vec![];
+    let mut tags: Vec<DocTag> = vec![];
+    for line in text.lines() {
+        let line = line.trim();
+        let line = line.strip_prefix('*').unwrap_or(line);
+        let line = line.strip_prefix(' ').unwrap_or(line).trim_end();
+
+        if let Some(tag) = line.strip_prefix('@') {
+            let (name, body) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+            tags.push(DocTag {
+                name: name.to_string(),
+                body: body.trim().to_string(),
+            });
+        } else if let Some(tag) = tags.last_mut() {
+            if !line.is_empty() {
+                if !tag.body.is_empty() {
+                    tag.body.push('\n');
+                }
+                tag.body.push_str(line.trim_start());
+            }
+        } else {
+            description.push(line);
+        }
+    }
+
+    DocBlock {
+        description: description.join("\n").trim().to_string(),
+        tags,
+    }
+}
+
+/// What a name written in a docblock tag refers to. Names are given as they
+/// are written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocReference {
+    /// `User` in `@param User $user`, `@throws NotFound` or `@mixin Builder`.
+    ClassLike(String),
+    /// `@see User::find()`, `@see User::$name` or `@see User::ROLE`. Members
+    /// without parentheses or `$` are taken for constants.
+    Member {
+        class: String,
+        name: String,
+        kind: MemberKind,
+    },
+    /// `@see format_name()`
+    Function(String),
+}
+
+/// Returns what the name at the byte offset of a docblock refers to. Only the
+/// types of tags count, not their variables or descriptions.
+pub fn get_docblock_reference(
+    program: &Program,
+    document: &str,
+    offset: usize,
+) -> Option<DocReference> {
+    let trivia = program.trivia.iter().find(|trivia| {
+        trivia.kind == TriviaKind::DocBlockComment
+            && trivia.span.start.offset <= offset
+            && offset < trivia.span.end.offset
+    })?;
+
+    let line_start = document[..offset]
+        .rfind('\n')
+        .map_or(0, |index| index + 1)
+        .max(trivia.span.start.offset);
+    let line_end = document[offset..]
+        .find('\n')
+        .map_or(document.len(), |index| offset + index)
+        .min(trivia.span.end.offset);
+    let line = &document[line_start..line_end];
+
+    let at = line.find('@')?;
+    if !line[..at]
+        .chars()
+        .all(|char| char.is_whitespace() || char == '/' || char == '*')
+    {
+        return None;
+    }
+    let tag_start = line_start + at + 1;
+    let tag_end = document[tag_start..line_end]
+        .find(char::is_whitespace)
+        .map_or(line_end, |index| tag_start + index);
+    let tag = &document[tag_start..tag_end];
+    let body = document[tag_end..line_end].trim_start();
+    let body = body.strip_suffix("*/").unwrap_or(body).trim_end();
+    let body_start = line_end - document[tag_end..line_end].trim_start().len();
+
+    // the part of the body names are looked up in
+    let (start, end) = match strip_tool_prefix(tag).unwrap_or(tag) {
+        "param"
+        | "return"
+        | "var"
+        | "throws"
+        | "mixin"
+        | "see"
+        | "property"
+        | "property-read"
+        | "property-write"
+        | "extends"
+        | "implements"
+        | "use"
+        | "template-extends"
+        | "template-implements"
+        | "template-use" => (body_start, body_start + split_type(body).0.len()),
+        "method" => (body_start, body_start + body.len()),
+        // `@template T of Bound`
+        "template" | "template-covariant" | "template-contravariant" => {
+            let (_, bound) = body
+                .split_once(" of ")
+                .or_else(|| body.split_once(" as "))?;
+            let bound = bound.trim_start();
+            let bound_start = body_start + body.len() - bound.len();
+            (bound_start, bound_start + split_type(bound).0.len())
+        }
+        _ => return None,
+    };
+    if offset < start || offset > end {
+        return None;
+    }
+
+    let is_name_char = |char: char| char.is_alphanumeric() || matches!(char, '_' | '\\' | '-');
+    let name_start = document[start..offset]
+        .char_indices()
+        .rev()
+        .find(|(_, char)| !is_name_char(*char))
+        .map_or(start, |(index, char)| start + index + char.len_utf8());
+    let name_end = document[offset..end]
+        .find(|char: char| !is_name_char(char))
+        .map_or(end, |index| offset + index);
+    let name = &document[name_start..name_end];
+    let before = &document[start..name_start];
+    let after = &document[name_end..end];
+    if name.is_empty() || name.starts_with(|char: char| char.is_ascii_digit() || char == '-') {
+        return None;
+    }
+
+    if let Some(class) = before
+        .strip_suffix("::")
+        .or_else(|| before.strip_suffix("::$"))
+    {
+        let class_start = class
+            .char_indices()
+            .rev()
+            .find(|(_, char)| !is_name_char(*char))
+            .map_or(0, |(index, char)| index + char.len_utf8());
+        let kind = match (before.ends_with('$'), after.starts_with('(')) {
+            (true, _) => MemberKind::Property,
+            (false, true) => MemberKind::Method,
+            (false, false) => MemberKind::Constant,
+        };
+
+        return Some(DocReference::Member {
+            class: class[class_start..].to_string(),
+            name: name.to_string(),
+            kind,
+        });
+    }
+
+    let is_method_name = tag.ends_with("method") && after.starts_with('(');
+    let is_shape_key = (after.trim_start().starts_with(':') && !after.starts_with("::"))
+        || after.starts_with("?:");
+    if before.ends_with('$') || is_method_name || is_shape_key {
+        return None;
+    }
+    if tag.ends_with("see") && after.starts_with('(') {
+        return Some(DocReference::Function(name.to_string()));
+    }
+    if is_doc_builtin_type(name) && !after.starts_with("::") {
+        return None;
+    }
+
+    let docblock = parse_docblock(&get_node_name(document, trivia.span));
+    if get_templates(&docblock)
+        .iter()
+        .any(|template| template.name == name)
+    {
+        return None;
+    }
+
+    Some(DocReference::ClassLike(name.to_string()))
+}
+
+/// Splits the type off the start of a tag body, e.g. `array<int, User> $users`
+/// into `array<int, User>` and `$users`. Spaces inside brackets and braces are
+/// part of the type.
+pub fn split_type(body: &str) -> (&str, &str) {
+    let mut depth = 0usize;
+    for (index, char) in body.char_indices() {
+        match char {
+            '<' | '(' | '{' | '[' => depth += 1,
+            '>' | ')' | '}' | ']' => depth = depth.saturating_sub(1),
+            char if char.is_whitespace() && depth == 0 => {
+                return (&body[..index], body[index..].trim_start());
+            }
+            _ => {}
+        }
+    }
+
+    (body, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use mago_interner::ThreadedInterner;
+    use mago_lexer::input::Input;
+    use mago_parser::parse;
+    use mago_source::SourceIdentifier;
+
+    use crate::analyzer::{
+        docblock::{get_docblock_reference, parse_docblock, split_type, DocReference, DocTag},
+        indexer::MemberKind,
+    };
+
+    #[test]
+    fn parse_description_and_tags() {
+        let docblock = parse_docblock(
+            r#"/**
+             * Finds a user.
+             *
+             * Throws when there is none.
+             *
+             * @param array<int, string> $ids The ids
+             *                                to look for
+             * @return User|null
+             * @deprecated
+             */"#,
+        );
+
+        assert_eq!(
+            docblock.description,
+            "Finds a user.\n\nThrows when there is none."
+        );
+        assert_eq!(
+            docblock.tags,
+            vec![
+                DocTag {
+                    name: String::from("param"),
+                    body: String::from("array<int, string> $ids The ids\nto look for"),
+                },
+                DocTag {
+                    name: String::from("return"),
+                    body: String::from("User|null"),
+                },
+                DocTag {
+                    name: String::from("deprecated"),
+                    body: String::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn render_docblock_as_markdown() {
+        let docblock = parse_docblock(
+            "/** Finds a user.\n * @param int $id The id\n * @param $name\n * @return User\n * @since 1.0\n */",
+        );
+
+        assert_eq!(
+            docblock.to_markdown(),
+            "Finds a user.\n\n*@param* `int` `$id` — The id\n\n*@param* `$name`\n\n*@return* `User`\n\n*@since* 1.0"
+        );
+    }
+
+    #[test]
+    fn find_param_descriptions() {
+        let docblock = parse_docblock(
+            "/**\n * @param array{id: int} $row The row\n * @param string ...$names Every name\n * @param &$out\n * @param $id The id\n */",
+        );
+
+        assert_eq!(
+            docblock.get_param_description("$row").as_deref(),
+            Some("The row")
+        );
+        assert_eq!(
+            docblock.get_param_description("$names").as_deref(),
+            Some("Every name")
+        );
+        assert_eq!(docblock.get_param_description("$out"), None);
+        assert_eq!(
+            docblock.get_param_description("$id").as_deref(),
+            Some("The id")
+        );
+        assert_eq!(docblock.get_param_description("$missing"), None);
+    }
+
+    #[test]
+    fn split_types_with_spaces() {
+        assert_eq!(
+            split_type("array{id: int, name: string} $row"),
+            ("array{id: int, name: string}", "$row")
+        );
+        assert_eq!(split_type("int"), ("int", ""));
+    }
+
+    #[test]
+    fn find_references_in_tags() {
+        let content = r#"<?php
+            /**
+             * @template T of Model
+             * @param array{user: User, id: int}|T $row The User row
+             * @see Repository::find()
+             * @phpstan-return Collection<int, T>
+             */
+            function load($row) {}
+        "#;
+
+        let interner = ThreadedInterner::new();
+        let (program, _) = parse(
+            &interner,
+            Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+        );
+        let reference = |text: &str, nth: usize| {
+            let offset = content.match_indices(text).nth(nth).unwrap().0;
+            get_docblock_reference(&program, content, offset + 1)
+        };
+
+        assert_eq!(
+            reference("Model", 0),
+            Some(DocReference::ClassLike(String::from("Model")))
+        );
+        assert_eq!(
+            reference("User", 0),
+            Some(DocReference::ClassLike(String::from("User")))
+        );
+        assert_eq!(
+            reference("find", 0),
+            Some(DocReference::Member {
+                class: String::from("Repository"),
+                name: String::from("find"),
+                kind: MemberKind::Method,
+            })
+        );
+        assert_eq!(
+            reference("Collection", 0),
+            Some(DocReference::ClassLike(String::from("Collection")))
+        );
+        // shape keys, builtins, templates, variables and descriptions
+        assert_eq!(reference("user", 0), None);
+        assert_eq!(reference("int", 0), None);
+        assert_eq!(reference("|T", 0), None);
+        assert_eq!(reference("$row", 0), None);
+        assert_eq!(reference("User", 1), None);
+    }
+}
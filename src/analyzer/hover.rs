@@ -0,0 +1,246 @@
+use mago_ast::{
+    AttributeList, FunctionLikeParameter, FunctionLikeParameterList, FunctionLikeReturnTypeHint,
+    Modifier, Node, Sequence,
+};
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use mago_span::{HasSpan, Position as SpanPosition, Span};
+use tower_lsp::lsp_types::{Hover, HoverContents, Location, MarkupContent, MarkupKind};
+
+use crate::lsp::state::State;
+
+use super::{
+    docblock::find_docblock,
+    indexer::{find_constant_location, find_function_location},
+    inference::{resolve_variable_type, Scope},
+    members::{find_class_symbol, find_member},
+    names::get_name_context,
+    references::{get_reference, ReferenceTarget},
+    tree::{get_node_name, get_nodes_for_position, get_range},
+    types::format_types,
+};
+
+/// Describes what the innermost node of the scope refers to: a PHP code block
+/// with the signature of its declaration followed by the rendered docblock.
+pub fn get_hover(scope: &Scope, state: &State) -> Option<Hover> {
+    let reference = get_reference(scope, state)?;
+
+    let value = match &reference.target {
+        ReferenceTarget::Variable { name, .. } => {
+            let types = resolve_variable_type(name, reference.span.start.offset, scope, state);
+            let signature = match types.is_empty() {
+                true => name.clone(),
+                false => format!("{} {}", format_types(&types), name),
+            };
+
+            render_code_block(&signature)
+        }
+        target => describe_declaration(&find_declaration(target, state)?, state)?,
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(get_range(reference.span, scope.document)),
+    })
+}
+
+fn find_declaration(target: &ReferenceTarget, state: &State) -> Option<Location> {
+    match target {
+        ReferenceTarget::ClassLike(fqn) => Some(find_class_symbol(fqn, state)?.location),
+        ReferenceTarget::Member { class, kind, name } => {
+            Some(find_member(class, name, *kind, state)?.location())
+        }
+        ReferenceTarget::Function(fqn) => find_function_location(fqn, state),
+        ReferenceTarget::Constant(fqn) => find_constant_location(fqn, state),
+        ReferenceTarget::Variable { .. } => None,
+    }
+}
+
+/// Renders the declaration whose name is at the location, reading the file
+/// from the open documents first since those may not be saved yet.
+fn describe_declaration(location: &Location, state: &State) -> Option<String> {
+    let content = match state.document_map.get(&location.uri) {
+        Some(document) => document.clone(),
+        None => std::fs::read_to_string(location.uri.to_file_path().ok()?).ok()?,
+    };
+
+    let interner = ThreadedInterner::new();
+    let (program, _) = parse(
+        &interner,
+        Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+    );
+    let nodes = get_nodes_for_position(&Node::Program(&program), &content, &location.range.start);
+
+    let (signature, declaration, is_member) =
+        nodes.iter().enumerate().rev().find_map(|(depth, node)| {
+            let ancestors = &nodes[..depth];
+            let (signature, span) = get_signature(ancestors, node, &content)?;
+            let is_member = ancestors.iter().any(|ancestor| {
+                matches!(
+                    ancestor,
+                    Node::Class(_) | Node::Interface(_) | Node::Trait(_) | Node::Enum(_)
+                )
+            });
+
+            Some((signature, span, is_member))
+        })?;
+
+    // members are shown without the namespace, their class-like already says where they are
+    let namespace = get_name_context(&program, &content, declaration.start.offset).namespace;
+    let signature = match is_member || namespace.is_empty() {
+        true => signature,
+        false => format!("namespace {};\n\n{}", namespace, signature),
+    };
+
+    let mut sections = vec![render_code_block(&signature)];
+    if let Some(docblock) = find_docblock(&program, &content, declaration.start.offset) {
+        let markdown = docblock.to_markdown();
+        if !markdown.is_empty() {
+            sections.push(markdown);
+        }
+    }
+
+    Some(sections.join("\n\n"))
+}
+
+/// Returns the signature of a declaration together with the span its docblock
+/// is written in front of. `ancestors` are the nodes around the declaration.
+fn get_signature(ancestors: &[Node], node: &Node, document: &str) -> Option<(String, Span)> {
+    let text =
+        |start: SpanPosition, end: SpanPosition| collapse(&document[start.offset..end.offset]);
+
+    let signature = match node {
+        Node::Class(class) => {
+            let start = first_modifier(&class.modifiers).unwrap_or(class.class.span().start);
+            text(start, class.left_brace.start)
+        }
+        Node::Interface(interface) => {
+            text(interface.interface.span().start, interface.left_brace.start)
+        }
+        Node::Trait(r#trait) => text(r#trait.r#trait.span().start, r#trait.left_brace.start),
+        Node::Enum(r#enum) => text(r#enum.r#enum.span().start, r#enum.left_brace.start),
+        Node::Function(function) => render_function_like(
+            document,
+            function.function.span().start,
+            &function.parameter_list,
+            &function.return_type_hint,
+        ),
+        Node::Method(method) => render_function_like(
+            document,
+            first_modifier(&method.modifiers).unwrap_or(method.function.span().start),
+            &method.parameter_list,
+            &method.return_type_hint,
+        ),
+        // `public function __construct(private string $foo)`
+        Node::FunctionLikeParameter(parameter) if parameter.is_promoted_property() => {
+            render_parameter(parameter, document)
+        }
+        Node::PropertyItem(item) => {
+            let (start, span) = match ancestors.last()? {
+                Node::PlainProperty(property) => (
+                    get_start(&property.attribute_lists, property.span()),
+                    property.span(),
+                ),
+                Node::HookedProperty(property) => (
+                    get_start(&property.attribute_lists, property.span()),
+                    property.span(),
+                ),
+                _ => return None,
+            };
+
+            let signature = format!(
+                "{} {}",
+                text(start, item.span().start),
+                text(item.span().start, item.span().end)
+            );
+            return Some((signature.trim().to_string(), span));
+        }
+        Node::ClassLikeConstantItem(item) => {
+            let Node::ClassLikeConstant(constant) = ancestors.last()? else {
+                return None;
+            };
+            let start = get_start(&constant.attribute_lists, constant.span());
+            let signature = format!(
+                "{} {}",
+                text(start, item.span().start),
+                text(item.span().start, item.span().end)
+            );
+            return Some((signature, constant.span()));
+        }
+        Node::EnumCase(case) => text(case.case.span().start, case.item.span().end),
+        Node::ConstantItem(item) => {
+            let Node::Constant(constant) = ancestors.last()? else {
+                return None;
+            };
+            let signature = format!("const {}", text(item.span().start, item.span().end));
+            return Some((signature, constant.span()));
+        }
+        // `define('FOO', 1)`
+        Node::FunctionCall(call) => text(call.span().start, call.span().end),
+        _ => return None,
+    };
+
+    Some((signature, node.span()))
+}
+
+fn render_function_like(
+    document: &str,
+    start: SpanPosition,
+    parameters: &FunctionLikeParameterList,
+    return_type_hint: &Option<FunctionLikeReturnTypeHint>,
+) -> String {
+    let head = collapse(&document[start.offset..parameters.left_parenthesis.start.offset]);
+    let parameters = parameters
+        .parameters
+        .iter()
+        .map(|parameter| render_parameter(parameter, document))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let return_type = match return_type_hint {
+        Some(hint) => format!(": {}", collapse(&get_node_name(document, &hint.hint))),
+        None => String::new(),
+    };
+
+    format!("{}({}){}", head, parameters, return_type)
+}
+
+/// Renders a parameter without its attributes and property hooks.
+fn render_parameter(parameter: &FunctionLikeParameter, document: &str) -> String {
+    let start = get_start(&parameter.attribute_lists, parameter.span());
+    let end = match &parameter.default_value {
+        Some(default_value) => default_value.span().end,
+        None => parameter.variable.span().end,
+    };
+
+    collapse(&document[start.offset..end.offset])
+}
+
+fn first_modifier(modifiers: &Sequence<Modifier>) -> Option<SpanPosition> {
+    modifiers
+        .iter()
+        .next()
+        .map(|modifier| modifier.span().start)
+}
+
+/// Returns where a declaration starts once its attributes are skipped.
+fn get_start(attribute_lists: &Sequence<AttributeList>, span: Span) -> SpanPosition {
+    attribute_lists
+        .iter()
+        .last()
+        .map(|attributes| attributes.span().end)
+        .unwrap_or(span.start)
+}
+
+/// Joins a declaration spread over multiple lines into a single one.
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn render_code_block(signature: &str) -> String {
+    format!("```php\n<?php\n{}\n```", signature)
+}
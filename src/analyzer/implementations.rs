@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::Location;
+
+use crate::lsp::state::State;
+
+use super::{
+    indexer::{MemberKind, Symbol, SymbolKind},
+    members::find_class_symbol,
+    references::ReferenceTarget,
+};
+
+/// Finds the classes implementing or extending a class-like, or the concrete
+/// methods overriding a method, going by the inheritance edges of the indexed
+/// files. Dependencies are not indexed up front, they do not implement
+/// anything declared in the workspace.
+pub fn find_implementations(target: &ReferenceTarget, state: &State) -> Vec<Location> {
+    match target {
+        ReferenceTarget::ClassLike(fqn) => get_subtypes(fqn, state)
+            .into_iter()
+            .filter(|symbol| matches!(symbol.kind, SymbolKind::Class | SymbolKind::Enum))
+            .map(|symbol| symbol.location)
+            .collect(),
+        ReferenceTarget::Member {
+            class,
+            kind: MemberKind::Method,
+            name,
+        } => get_subtypes(class, state)
+            .into_iter()
+            .filter_map(|symbol| {
+                let method = symbol.class.as_ref()?.members.iter().find(|member| {
+                    member.kind == MemberKind::Method
+                        && !member.is_abstract
+                        && member.name.eq_ignore_ascii_case(name)
+                })?;
+
+                Some(Location::new(symbol.location.uri.clone(), method.range))
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Returns every class-like extending or implementing the class-like, directly
+/// or through other class-likes.
+pub fn get_subtypes(fqn: &str, state: &State) -> Vec<Symbol> {
+    let mut visited = HashSet::new();
+    let mut pending = vec![fqn.trim_start_matches('\\').to_ascii_lowercase()];
+    let mut subtypes = vec![];
+
+    while let Some(parent) = pending.pop() {
+        let children = match state.subtypes.get(&parent) {
+            Some(children) => children.clone(),
+            None => continue,
+        };
+
+        for child in children {
+            if !visited.insert(child.to_ascii_lowercase()) {
+                continue;
+            }
+            pending.push(child.to_ascii_lowercase());
+
+            if let Some(symbol) = find_class_symbol(&child, state) {
+                subtypes.push(symbol);
+            }
+        }
+    }
+
+    subtypes
+}
@@ -0,0 +1,339 @@
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+use tree_sitter::{Node, Point, Tree};
+
+use super::{indexer::qualify_name, utils::get_position_from_offset};
+
+/// The class imports in effect at some point of a document. They are read from
+/// the tree sitter tree since that one survives the incomplete code around the
+/// cursor while completing.
+#[derive(Debug, Default)]
+pub struct Imports {
+    pub namespace: String,
+    /// the alias and the fully qualified name of every imported class
+    classes: Vec<(String, String)>,
+    /// the names of the classes, interfaces, traits and enums declared in the
+    /// namespace, they cannot be imported under the same name
+    declared: Vec<String>,
+    /// what every `use` statement imports first, where it starts and ends
+    statements: Vec<(String, Position, Position)>,
+    /// where the first `use` statement goes when there is none yet
+    header_end: Position,
+}
+
+/// How a class is written at some point of a document, together with the
+/// `use` statement that makes it available there, if one is needed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassImport {
+    pub name: String,
+    pub edit: Option<TextEdit>,
+}
+
+impl Imports {
+    /// Returns the imports of the namespace the point is in.
+    pub fn from_tree(tree: &Tree, document: &str, point: Point) -> Self {
+        let mut imports = Imports::default();
+        imports.collect(tree.root_node(), document, point);
+
+        imports
+    }
+
+    /// Returns how the class can be referred to: by its short name when it is
+    /// in the current namespace or already imported, otherwise through a new
+    /// `use` statement, aliased when the short name is taken.
+    pub fn import_class(&self, fqn: &str) -> ClassImport {
+        let fqn = fqn.trim_start_matches('\\');
+        let name = fqn.rsplit('\\').next().unwrap_or(fqn);
+        let known = |name: String| ClassImport { name, edit: None };
+
+        if let Some((alias, _)) = self
+            .classes
+            .iter()
+            .find(|(_, imported)| imported.eq_ignore_ascii_case(fqn))
+        {
+            return known(alias.clone());
+        }
+        if qualify_name(&self.namespace, name).eq_ignore_ascii_case(fqn) {
+            return known(name.to_string());
+        }
+
+        // `Http\Request` becomes `HttpRequest` when `Request` is taken
+        let segments: Vec<&str> = fqn.split('\\').collect();
+        let Some(alias) = (1..=segments.len())
+            .map(|count| segments[segments.len() - count..].concat())
+            .find(|alias| !self.is_taken(alias))
+        else {
+            return known(format!("\\{}", fqn));
+        };
+
+        let statement = match alias == name {
+            true => format!("use {};", fqn),
+            false => format!("use {} as {};", fqn, alias),
+        };
+
+        ClassImport {
+            name: alias,
+            edit: Some(self.insert_statement(fqn, &statement)),
+        }
+    }
+
+    /// Whether the class might be written otherwise than by its short name,
+    /// without working out the `use` statement for it.
+    pub fn may_rename(&self, fqn: &str) -> bool {
+        let fqn = fqn.trim_start_matches('\\');
+        let name = fqn.rsplit('\\').next().unwrap_or(fqn);
+
+        self.is_taken(name)
+            || self
+                .classes
+                .iter()
+                .any(|(_, imported)| imported.eq_ignore_ascii_case(fqn))
+    }
+
+    fn is_taken(&self, alias: &str) -> bool {
+        self.classes
+            .iter()
+            .map(|(imported, _)| imported)
+            .chain(&self.declared)
+            .any(|taken| taken.eq_ignore_ascii_case(alias))
+    }
+
+    /// Adds the statement where it keeps the `use` statements sorted.
+    fn insert_statement(&self, fqn: &str, statement: &str) -> TextEdit {
+        let key = fqn.to_ascii_lowercase();
+        let next = self
+            .statements
+            .iter()
+            .find(|(imported, ..)| imported.to_ascii_lowercase() > key);
+
+        let (position, new_text) = match (next, self.statements.last()) {
+            (Some((_, start, _)), _) => (*start, format!("{}\n{}", statement, indentation(start))),
+            (None, Some((_, start, end))) => {
+                (*end, format!("\n{}{}", indentation(start), statement))
+            }
+            (None, None) => (self.header_end, format!("\n\n{}", statement)),
+        };
+
+        TextEdit {
+            range: Range::new(position, position),
+            new_text,
+        }
+    }
+
+    fn collect(&mut self, node: Node, document: &str, point: Point) {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.start_position() > point {
+                // the classes declared further down still take their names
+                match child.kind() {
+                    "namespace_definition" => return,
+                    _ => {
+                        self.add_declaration(child, document);
+                        continue;
+                    }
+                }
+            }
+
+            match child.kind() {
+                "php_tag" => self.header_end = get_position_from_offset(document, child.end_byte()),
+                // `use` statements cannot go before `declare(strict_types=1);`
+                "declare_statement" => {
+                    self.header_end = get_position_from_offset(document, child.end_byte())
+                }
+                "namespace_definition" => {
+                    // every namespace starts over with its own imports
+                    *self = Imports {
+                        namespace: child
+                            .child_by_field_name("name")
+                            .map(|name| text(name, document))
+                            .unwrap_or_default(),
+                        header_end: get_position_from_offset(document, child.end_byte()),
+                        ..Default::default()
+                    };
+
+                    if let Some(body) = child.child_by_field_name("body") {
+                        if body.start_position() < point && point < body.end_position() {
+                            // right after the opening brace
+                            self.header_end =
+                                get_position_from_offset(document, body.start_byte() + 1);
+                            self.collect(body, document, point);
+                            return;
+                        }
+                    }
+                }
+                "namespace_use_declaration" if child.child_by_field_name("type").is_none() => {
+                    self.add_statement(child, document);
+                }
+                _ => self.add_declaration(child, document),
+            }
+        }
+    }
+
+    fn add_declaration(&mut self, declaration: Node, document: &str) {
+        if !matches!(
+            declaration.kind(),
+            "class_declaration"
+                | "interface_declaration"
+                | "trait_declaration"
+                | "enum_declaration"
+        ) {
+            return;
+        }
+        if let Some(name) = declaration.child_by_field_name("name") {
+            self.declared.push(text(name, document));
+        }
+    }
+
+    fn add_statement(&mut self, statement: Node, document: &str) {
+        let (prefix, clauses) = match statement.child_by_field_name("body") {
+            // `use App\Models\{User, Post};`
+            Some(group) => {
+                let mut cursor = statement.walk();
+                let prefix = statement
+                    .children(&mut cursor)
+                    .find(|child| child.kind() == "namespace_name")
+                    .map(|prefix| text(prefix, document));
+
+                (prefix, group)
+            }
+            None => (None, statement),
+        };
+
+        let mut cursor = clauses.walk();
+        for clause in clauses.children(&mut cursor) {
+            if clause.kind() != "namespace_use_clause"
+                || clause.child_by_field_name("type").is_some()
+            {
+                continue;
+            }
+            let Some(name) = clause.named_child(0) else {
+                continue;
+            };
+
+            let name = text(name, document);
+            let fqn = match &prefix {
+                Some(prefix) => format!("{}\\{}", prefix, name),
+                None => name.trim_start_matches('\\').to_string(),
+            };
+            let alias = match clause.child_by_field_name("alias") {
+                Some(alias) => text(alias, document),
+                None => fqn.rsplit('\\').next().unwrap_or_default().to_string(),
+            };
+
+            let start = get_position_from_offset(document, statement.start_byte());
+            if self.statements.last().map(|(_, start, _)| *start) != Some(start) {
+                self.statements.push((
+                    fqn.clone(),
+                    start,
+                    get_position_from_offset(document, statement.end_byte()),
+                ));
+            }
+            self.classes.push((alias, fqn));
+        }
+    }
+}
+
+fn indentation(start: &Position) -> String {
+    " ".repeat(start.character as usize)
+}
+
+fn text(node: Node, document: &str) -> String {
+    document[node.byte_range()].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range, TextEdit};
+    use tree_sitter::Point;
+
+    use crate::analyzer::parser::Parser;
+
+    use super::{ClassImport, Imports};
+
+    fn import(document: &str, fqn: &str) -> ClassImport {
+        let tree = Parser::new().unwrap().parse(document).unwrap();
+        Imports::from_tree(&tree, document, Point::new(usize::MAX, 0)).import_class(fqn)
+    }
+
+    fn insert(line: u32, character: u32, new_text: &str) -> Option<TextEdit> {
+        let position = Position::new(line, character);
+        Some(TextEdit {
+            range: Range::new(position, position),
+            new_text: new_text.to_string(),
+        })
+    }
+
+    #[test]
+    fn imports_classes_in_order() {
+        let document = "<?php\nnamespace App\\Http;\n\nuse App\\Models\\Post;\nuse App\\Models\\User as Author;\nuse function App\\helper;\n\nclass Controller {}\n";
+
+        assert_eq!(
+            import(document, "App\\Models\\Comment"),
+            ClassImport {
+                name: String::from("Comment"),
+                edit: insert(3, 0, "use App\\Models\\Comment;\n"),
+            }
+        );
+        assert_eq!(
+            import(document, "Symfony\\Request"),
+            ClassImport {
+                name: String::from("Request"),
+                edit: insert(4, 30, "\nuse Symfony\\Request;"),
+            }
+        );
+        assert_eq!(
+            import(document, "App\\Models\\User"),
+            ClassImport {
+                name: String::from("Author"),
+                edit: None,
+            }
+        );
+        assert_eq!(
+            import(document, "App\\Http\\Kernel"),
+            ClassImport {
+                name: String::from("Kernel"),
+                edit: None,
+            }
+        );
+    }
+
+    #[test]
+    fn aliases_taken_names() {
+        let document =
+            "<?php\nnamespace App;\n\nuse App\\Models\\{Post, User};\n\nclass Controller {}\n";
+
+        assert_eq!(
+            import(document, "Auth\\Foundation\\User"),
+            ClassImport {
+                name: String::from("FoundationUser"),
+                edit: insert(3, 28, "\nuse Auth\\Foundation\\User as FoundationUser;"),
+            }
+        );
+        assert_eq!(
+            import(
+                "<?php\nnamespace App;\n\nclass Controller {}\n",
+                "Exception"
+            ),
+            ClassImport {
+                name: String::from("Exception"),
+                edit: insert(1, 14, "\n\nuse Exception;"),
+            }
+        );
+        assert_eq!(
+            import(
+                "<?php\nnamespace App\\Http;\n\nclass Controller {}\n\nclass Request {}\n",
+                "Symfony\\Request"
+            ),
+            ClassImport {
+                name: String::from("SymfonyRequest"),
+                edit: insert(1, 19, "\n\nuse Symfony\\Request as SymfonyRequest;"),
+            }
+        );
+        assert_eq!(
+            import("<?php declare(strict_types=1);\n\nnew Foo();\n", "App\\Foo"),
+            ClassImport {
+                name: String::from("Foo"),
+                edit: insert(0, 30, "\n\nuse App\\Foo;"),
+            }
+        );
+    }
+}
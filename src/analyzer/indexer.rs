@@ -0,0 +1,1353 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use dashmap::DashMap;
+use mago_ast::{
+    Argument, Call, ClassLikeMember, EnumCaseItem, Expression, FunctionLikeParameterList,
+    Identifier, IfBody, Literal, MethodBody, Modifier, NamespaceBody, Program, Property,
+    PropertyItem, Sequence, Statement,
+};
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use mago_span::{HasSpan, Span};
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Location, Range, Url};
+
+use crate::lsp::state::State;
+
+use super::{
+    cache::{take_cached_symbols, FileStamp},
+    docblock::{find_docblock, split_type},
+    names::NameContext,
+    phpdoc::{get_generic_supertypes, get_templates, Template},
+    tree::{get_node_name, get_range},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymbolKind {
+    Class,
+    Interface,
+    Trait,
+    Enum,
+    Function,
+    Constant,
+}
+
+impl SymbolKind {
+    pub fn is_class_like(&self) -> bool {
+        !matches!(self, SymbolKind::Function | SymbolKind::Constant)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Symbol {
+    pub fqn: String,
+    pub kind: SymbolKind,
+    pub location: Location,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<ClassInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function: Option<FunctionInfo>,
+}
+
+/// What a class-like declares and where it inherits from. Every name is fully
+/// qualified. `generics` are the supertypes given template arguments with
+/// `@extends`, `@implements` or `@use`, like `App\Collection<int, App\User>`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClassInfo {
+    pub extends: Vec<String>,
+    pub implements: Vec<String>,
+    pub traits: Vec<String>,
+    pub members: Vec<Member>,
+    pub templates: Vec<Template>,
+    pub generics: Vec<String>,
+}
+
+/// The declared and the documented return type of a function, fully qualified,
+/// its parameters and the template parameters it declares.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunctionInfo {
+    pub type_hint: Option<String>,
+    pub doc_type: Option<String>,
+    pub parameters: Vec<Parameter>,
+    pub templates: Vec<Template>,
+}
+
+/// A parameter of a function or method. The type and the default value are
+/// kept as they are written in the declaration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    pub type_hint: Option<String>,
+    pub default_value: Option<String>,
+    pub is_variadic: bool,
+    pub is_by_reference: bool,
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(type_hint) = &self.type_hint {
+            write!(f, "{} ", type_hint)?;
+        }
+        if self.is_by_reference {
+            write!(f, "&")?;
+        }
+        if self.is_variadic {
+            write!(f, "...")?;
+        }
+        write!(f, "{}", self.name)?;
+        match &self.default_value {
+            Some(default_value) => write!(f, " = {}", default_value),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemberKind {
+    Method,
+    Property,
+    Constant,
+    EnumCase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visibility {
+    Public,
+    Protected,
+    Private,
+}
+
+/// A member of a class-like. Properties are stored without the leading `$` and
+/// the range points at the name of the member in the file of its class. The
+/// type is the return type of methods, `doc_type` the one from the `@return`
+/// or `@var` tag of the docblock. Methods keep their parameters and may declare
+/// template parameters of their own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Member {
+    pub name: String,
+    pub kind: MemberKind,
+    pub visibility: Visibility,
+    pub is_static: bool,
+    pub is_abstract: bool,
+    pub type_hint: Option<String>,
+    pub doc_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<Parameter>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<Template>,
+    pub range: Range,
+}
+
+/// How many of the files queued for indexing have been indexed so far.
+#[derive(Debug, Default)]
+pub struct IndexProgress {
+    pub indexed: AtomicUsize,
+    pub total: AtomicUsize,
+}
+
+impl IndexProgress {
+    pub fn get(&self) -> (usize, usize) {
+        (
+            self.indexed.load(Ordering::Relaxed),
+            self.total.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Collects the named types, functions and constants declared in a program
+/// together with their fully qualified names and the location of their names.
+pub fn collect_symbols(program: &Program, content: &str, uri: &Url) -> Vec<Symbol> {
+    let mut symbols = vec![];
+
+    collect_statement_symbols(
+        program.statements.iter(),
+        &mut NameContext::default(),
+        &FileContext {
+            program,
+            content,
+            uri,
+        },
+        &mut symbols,
+    );
+
+    symbols
+}
+
+/// The file symbols are collected from.
+struct FileContext<'a> {
+    program: &'a Program,
+    content: &'a str,
+    uri: &'a Url,
+}
+
+fn collect_statement_symbols<'a>(
+    statements: impl Iterator<Item = &'a Statement>,
+    names: &mut NameContext,
+    file: &FileContext,
+    out: &mut Vec<Symbol>,
+) {
+    let FileContext { content, uri, .. } = *file;
+
+    for statement in statements {
+        let (name, kind, class) = match statement {
+            Statement::Namespace(ns) => {
+                let name = ns
+                    .name
+                    .as_ref()
+                    .map(|name| get_node_name(content, name))
+                    .unwrap_or_default();
+                let statements = match &ns.body {
+                    NamespaceBody::Implicit(body) => body.statements.iter(),
+                    NamespaceBody::BraceDelimited(block) => block.statements.iter(),
+                };
+
+                collect_statement_symbols(statements, &mut NameContext::new(&name), file, out);
+                continue;
+            }
+            Statement::Use(r#use) => {
+                names.add_use(r#use, content);
+                continue;
+            }
+            Statement::Class(class) => {
+                let info = ClassInfo {
+                    extends: resolve_names(
+                        class.extends.iter().flat_map(|e| e.types.iter()),
+                        names,
+                        content,
+                    ),
+                    implements: resolve_names(
+                        class.implements.iter().flat_map(|i| i.types.iter()),
+                        names,
+                        content,
+                    ),
+                    ..Default::default()
+                };
+                (&class.name, SymbolKind::Class, Some((info, &class.members)))
+            }
+            Statement::Interface(interface) => {
+                let info = ClassInfo {
+                    extends: resolve_names(
+                        interface.extends.iter().flat_map(|e| e.types.iter()),
+                        names,
+                        content,
+                    ),
+                    ..Default::default()
+                };
+                (
+                    &interface.name,
+                    SymbolKind::Interface,
+                    Some((info, &interface.members)),
+                )
+            }
+            Statement::Trait(r#trait) => (
+                &r#trait.name,
+                SymbolKind::Trait,
+                Some((ClassInfo::default(), &r#trait.members)),
+            ),
+            Statement::Enum(r#enum) => {
+                let info = ClassInfo {
+                    implements: resolve_names(
+                        r#enum.implements.iter().flat_map(|i| i.types.iter()),
+                        names,
+                        content,
+                    ),
+                    ..Default::default()
+                };
+                (
+                    &r#enum.name,
+                    SymbolKind::Enum,
+                    Some((info, &r#enum.members)),
+                )
+            }
+            Statement::Function(function) => (&function.name, SymbolKind::Function, None),
+            Statement::Constant(constant) => {
+                for item in constant.items.iter() {
+                    out.push(Symbol {
+                        fqn: qualify_name(&names.namespace, &get_node_name(content, &item.name)),
+                        kind: SymbolKind::Constant,
+                        location: Location::new(uri.clone(), get_range(&item.name, content)),
+                        class: None,
+                        function: None,
+                    });
+                }
+                continue;
+            }
+            Statement::Expression(statement) => {
+                out.extend(get_defined_constant(&statement.expression, content, uri));
+                continue;
+            }
+            // conditionally declared functions e.g. `if (!function_exists('foo')) { function foo() {} }`
+            Statement::Block(block) => {
+                collect_statement_symbols(block.statements.iter(), names, file, out);
+                continue;
+            }
+            Statement::If(r#if) => {
+                // the fallback is often declared in the `else` branch
+                match &r#if.body {
+                    IfBody::Statement(body) => {
+                        let branches = std::iter::once(&body.statement)
+                            .chain(body.else_if_clauses.iter().map(|clause| &clause.statement))
+                            .chain(body.else_clause.iter().map(|clause| &clause.statement));
+                        for branch in branches {
+                            collect_statement_symbols(
+                                std::iter::once(branch.as_ref()),
+                                names,
+                                file,
+                                out,
+                            );
+                        }
+                    }
+                    IfBody::ColonDelimited(body) => {
+                        let branches = std::iter::once(&body.statements)
+                            .chain(body.else_if_clauses.iter().map(|clause| &clause.statements))
+                            .chain(body.else_clause.iter().map(|clause| &clause.statements));
+                        for branch in branches {
+                            collect_statement_symbols(branch.iter(), names, file, out);
+                        }
+                    }
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        let function = match statement {
+            Statement::Function(function) => {
+                let templates = get_declared_templates(function.span(), names, file);
+                Some(FunctionInfo {
+                    type_hint: function
+                        .return_type_hint
+                        .as_ref()
+                        .map(|hint| names.resolve_hint(&hint.hint, content)),
+                    doc_type: get_doc_type(
+                        function.span(),
+                        "return",
+                        None,
+                        names,
+                        &templates,
+                        file,
+                    ),
+                    parameters: get_parameters(&function.parameter_list, content),
+                    templates,
+                })
+            }
+            _ => None,
+        };
+
+        out.push(Symbol {
+            fqn: qualify_name(&names.namespace, &get_node_name(content, name)),
+            kind,
+            location: Location::new(uri.clone(), get_range(name.span(), content)),
+            class: class.map(|(mut info, members)| {
+                info.templates = get_declared_templates(statement.span(), names, file);
+                info.generics = get_generic_types(statement.span(), names, &info.templates, file);
+                collect_members(members.iter(), &mut info, names, file);
+                info
+            }),
+            function,
+        });
+    }
+}
+
+fn collect_members<'a>(
+    members: impl Iterator<Item = &'a ClassLikeMember>,
+    info: &mut ClassInfo,
+    names: &NameContext,
+    file: &FileContext,
+) {
+    let FileContext { content, .. } = *file;
+
+    for member in members {
+        match member {
+            ClassLikeMember::TraitUse(trait_use) => {
+                info.traits
+                    .extend(resolve_names(trait_use.trait_names.iter(), names, content));
+            }
+            ClassLikeMember::Method(method) => {
+                let name = get_node_name(content, &method.name);
+                let method_templates = get_declared_templates(method.span(), names, file);
+                let templates = [info.templates.as_slice(), &method_templates].concat();
+                info.members.push(Member {
+                    name: name.clone(),
+                    kind: MemberKind::Method,
+                    visibility: get_visibility(&method.modifiers),
+                    is_static: method.modifiers.contains_static(),
+                    // interface methods have no body either
+                    is_abstract: matches!(method.body, MethodBody::Abstract(_)),
+                    type_hint: method
+                        .return_type_hint
+                        .as_ref()
+                        .map(|hint| names.resolve_hint(&hint.hint, content)),
+                    doc_type: get_doc_type(method.span(), "return", None, names, &templates, file),
+                    parameters: get_parameters(&method.parameter_list, content),
+                    templates: method_templates,
+                    range: get_range(&method.name, content),
+                });
+
+                if !name.eq_ignore_ascii_case("__construct") {
+                    continue;
+                }
+
+                // promoted constructor parameters declare properties as well
+                for parameter in method.parameter_list.parameters.iter() {
+                    if !parameter.is_promoted_property() {
+                        continue;
+                    }
+
+                    let variable = get_node_name(content, &parameter.variable);
+                    info.members.push(Member {
+                        name: variable.trim_start_matches('$').to_string(),
+                        kind: MemberKind::Property,
+                        visibility: get_visibility(&parameter.modifiers),
+                        is_static: false,
+                        is_abstract: false,
+                        type_hint: parameter
+                            .hint
+                            .as_ref()
+                            .map(|hint| names.resolve_hint(hint, content)),
+                        doc_type: get_doc_type(
+                            method.span(),
+                            "param",
+                            Some(&variable),
+                            names,
+                            &templates,
+                            file,
+                        ),
+                        parameters: vec![],
+                        templates: vec![],
+                        range: get_range(&parameter.variable, content),
+                    });
+                }
+            }
+            ClassLikeMember::Property(property) => {
+                let (modifiers, hint, items) = match property {
+                    Property::Plain(plain) => (
+                        &plain.modifiers,
+                        &plain.hint,
+                        plain.items.iter().collect::<Vec<_>>(),
+                    ),
+                    Property::Hooked(hooked) => {
+                        (&hooked.modifiers, &hooked.hint, vec![&hooked.item])
+                    }
+                };
+
+                for item in items {
+                    let variable = match item {
+                        PropertyItem::Abstract(item) => &item.variable,
+                        PropertyItem::Concrete(item) => &item.variable,
+                    };
+
+                    let variable_name = get_node_name(content, variable);
+                    info.members.push(Member {
+                        name: variable_name.trim_start_matches('$').to_string(),
+                        kind: MemberKind::Property,
+                        visibility: get_visibility(modifiers),
+                        is_static: modifiers.contains_static(),
+                        is_abstract: false,
+                        type_hint: hint.as_ref().map(|hint| names.resolve_hint(hint, content)),
+                        doc_type: get_doc_type(
+                            property.span(),
+                            "var",
+                            Some(&variable_name),
+                            names,
+                            &info.templates,
+                            file,
+                        ),
+                        parameters: vec![],
+                        templates: vec![],
+                        range: get_range(variable, content),
+                    });
+                }
+            }
+            ClassLikeMember::Constant(constant) => {
+                for item in constant.items.iter() {
+                    info.members.push(Member {
+                        name: get_node_name(content, &item.name),
+                        kind: MemberKind::Constant,
+                        visibility: get_visibility(&constant.modifiers),
+                        is_static: true,
+                        is_abstract: false,
+                        type_hint: constant
+                            .hint
+                            .as_ref()
+                            .map(|hint| names.resolve_hint(hint, content)),
+                        doc_type: get_doc_type(
+                            constant.span(),
+                            "var",
+                            None,
+                            names,
+                            &info.templates,
+                            file,
+                        ),
+                        parameters: vec![],
+                        templates: vec![],
+                        range: get_range(&item.name, content),
+                    });
+                }
+            }
+            ClassLikeMember::EnumCase(case) => {
+                let name = match &case.item {
+                    EnumCaseItem::Unit(item) => &item.name,
+                    EnumCaseItem::Backed(item) => &item.name,
+                };
+
+                info.members.push(Member {
+                    name: get_node_name(content, name),
+                    kind: MemberKind::EnumCase,
+                    visibility: Visibility::Public,
+                    is_static: true,
+                    is_abstract: false,
+                    type_hint: Some(String::from("static")),
+                    doc_type: None,
+                    parameters: vec![],
+                    templates: vec![],
+                    range: get_range(name, content),
+                });
+            }
+        }
+    }
+}
+
+/// Returns the constant declared by a `define('NAME', $value)` call. Defined
+/// constants always live in the namespace spelled out in their name.
+fn get_defined_constant(expression: &Expression, content: &str, uri: &Url) -> Option<Symbol> {
+    let Expression::Call(Call::Function(call)) = expression else {
+        return None;
+    };
+    let Expression::Identifier(function) = call.function.as_ref() else {
+        return None;
+    };
+    if !get_node_name(content, function)
+        .trim_start_matches('\\')
+        .eq_ignore_ascii_case("define")
+    {
+        return None;
+    }
+
+    let Some(Argument::Positional(argument)) = call.argument_list.arguments.first() else {
+        return None;
+    };
+    let Expression::Literal(Literal::String(literal)) = &argument.value else {
+        return None;
+    };
+
+    let quoted = get_node_name(content, literal);
+    let name = quoted.get(1..quoted.len().checked_sub(1)?)?;
+    if name.is_empty() {
+        return None;
+    }
+
+    // point at the name without the quotes around it
+    let mut range = get_range(literal, content);
+    range.start.character += 1;
+    range.end.character = range.end.character.saturating_sub(1);
+
+    Some(Symbol {
+        // both `'App\\FOO'` and `'App\FOO'` spell out the same name
+        fqn: name
+            .replace("\\\\", "\\")
+            .trim_start_matches('\\')
+            .to_string(),
+        kind: SymbolKind::Constant,
+        location: Location::new(uri.clone(), range),
+        class: None,
+        function: None,
+    })
+}
+
+/// Returns the fully qualified type of the tag in the docblock in front of a
+/// declaration. Tags naming a variable only count for that variable.
+fn get_doc_type(
+    declaration: Span,
+    tag: &str,
+    variable: Option<&str>,
+    names: &NameContext,
+    templates: &[Template],
+    file: &FileContext,
+) -> Option<String> {
+    let docblock = find_docblock(file.program, file.content, declaration.start.offset)?;
+
+    let doc_type = docblock.get_type_tags(tag).find_map(|tag| {
+        let (type_name, rest) = split_type(&tag.body);
+        let named = rest
+            .split_whitespace()
+            .next()
+            .filter(|rest| rest.starts_with('$'));
+        if type_name.is_empty() || type_name.starts_with('$') {
+            return None;
+        }
+
+        match (variable, named) {
+            (Some(variable), Some(named)) if variable != named => None,
+            _ => Some(names.resolve_doc_type(type_name, templates)),
+        }
+    });
+
+    doc_type
+}
+
+/// Returns the template parameters declared in the docblock in front of a
+/// declaration, with their bounds fully qualified.
+fn get_declared_templates(
+    declaration: Span,
+    names: &NameContext,
+    file: &FileContext,
+) -> Vec<Template> {
+    let Some(docblock) = find_docblock(file.program, file.content, declaration.start.offset) else {
+        return vec![];
+    };
+
+    get_templates(&docblock)
+        .into_iter()
+        .map(|template| Template {
+            bound: template
+                .bound
+                .map(|bound| names.resolve_doc_type(&bound, &[])),
+            ..template
+        })
+        .collect()
+}
+
+/// Returns the fully qualified supertypes the docblock in front of a class-like
+/// passes template arguments to.
+fn get_generic_types(
+    declaration: Span,
+    names: &NameContext,
+    templates: &[Template],
+    file: &FileContext,
+) -> Vec<String> {
+    let Some(docblock) = find_docblock(file.program, file.content, declaration.start.offset) else {
+        return vec![];
+    };
+
+    get_generic_supertypes(&docblock)
+        .iter()
+        .map(|generic| names.resolve_doc_type(generic, templates))
+        .collect()
+}
+
+fn get_parameters(parameters: &FunctionLikeParameterList, content: &str) -> Vec<Parameter> {
+    let collapse = |text: String| text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    parameters
+        .parameters
+        .iter()
+        .map(|parameter| Parameter {
+            name: get_node_name(content, &parameter.variable),
+            type_hint: parameter
+                .hint
+                .as_ref()
+                .map(|hint| collapse(get_node_name(content, hint))),
+            default_value: parameter
+                .default_value
+                .as_ref()
+                .map(|default_value| collapse(get_node_name(content, &default_value.value))),
+            is_variadic: parameter.ellipsis.is_some(),
+            is_by_reference: parameter.ampersand.is_some(),
+        })
+        .collect()
+}
+
+fn resolve_names<'a>(
+    identifiers: impl Iterator<Item = &'a Identifier>,
+    names: &NameContext,
+    content: &str,
+) -> Vec<String> {
+    identifiers
+        .map(|identifier| names.resolve_class(&get_node_name(content, identifier)))
+        .collect()
+}
+
+fn get_visibility(modifiers: &Sequence<Modifier>) -> Visibility {
+    if modifiers.contains_private() {
+        Visibility::Private
+    } else if modifiers.contains_protected() {
+        Visibility::Protected
+    } else {
+        Visibility::Public
+    }
+}
+
+pub fn qualify_name(namespace: &str, name: &str) -> String {
+    if namespace.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}\\{}", namespace, name)
+    }
+}
+
+/// Parses the file and replaces every class map entry pointing at it with the
+/// symbols it currently declares.
+pub fn index_file(path: &Path, content: &str, state: &State) {
+    let Some(path_str) = path.to_str() else {
+        return;
+    };
+
+    remove_file(path_str, state);
+
+    if let Ok(metadata) = std::fs::metadata(path) {
+        let stamp = FileStamp::new(&metadata, content.as_bytes());
+        state.file_stamps.insert(path_str.to_string(), stamp);
+    }
+
+    store_symbols(path_str, parse_symbols(path, content), state);
+}
+
+/// Removes every class map, function map and constant map entry pointing at
+/// the file together with the inheritance edges of the class-likes in it.
+pub fn remove_file(path: &str, state: &State) {
+    state.file_stamps.remove(path);
+    state.index_cache.write().unwrap().files.remove(path);
+
+    let previous = state.symbol_index.remove(path);
+    // the file could have been mapped by composer without ever being indexed by
+    // us, sweeping the class map for every other file would be too slow
+    if previous.is_none() && state.class_map.iter().any(|entry| entry.value() == path) {
+        let mut removed = vec![];
+        state.class_map.retain(|fqn, class_path| {
+            if class_path == path {
+                removed.push(fqn.clone());
+            }
+            class_path != path
+        });
+
+        let mut namespaces = state.namespaces.write().unwrap();
+        for fqn in removed {
+            namespaces.remove(&fqn);
+        }
+    }
+
+    for symbol in previous.map(|(_, symbols)| symbols).unwrap_or_default() {
+        let removed = get_symbol_map(symbol.kind, state).remove_if(
+            &get_symbol_key(symbol.kind, &symbol.fqn),
+            |_, symbol_path| symbol_path == path,
+        );
+        if removed.is_some() && symbol.kind.is_class_like() {
+            state.namespaces.write().unwrap().remove(&symbol.fqn);
+        }
+
+        for parent in get_supertypes(&symbol) {
+            if let Some(mut subtypes) = state.subtypes.get_mut(&parent.to_ascii_lowercase()) {
+                subtypes.retain(|subtype| subtype != &symbol.fqn);
+            }
+        }
+    }
+}
+
+/// Indexes a file that has not been indexed yet, reusing the symbols from the
+/// index cache when the file did not change since they were stored. Files that
+/// are already indexed at their current revision are skipped.
+pub fn index_path(path: &Path, state: &State) {
+    let Some(path_str) = path.to_str() else {
+        return;
+    };
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return;
+    };
+
+    let is_current = state
+        .file_stamps
+        .get(path_str)
+        .is_some_and(|stamp| stamp.matches_metadata(&metadata));
+    if is_current && state.symbol_index.contains_key(path_str) {
+        return;
+    }
+
+    let (stamp, symbols) = match take_cached_symbols(path_str, &metadata, state) {
+        Ok(cached) => cached,
+        Err(content) => (
+            FileStamp::new(&metadata, content.as_bytes()),
+            parse_symbols(path, &content),
+        ),
+    };
+
+    // the file changed since it was indexed, its previous symbols are stale
+    if state.symbol_index.contains_key(path_str) {
+        remove_file(path_str, state);
+    }
+
+    state.file_stamps.insert(path_str.to_string(), stamp);
+    store_symbols(path_str, symbols, state);
+}
+
+/// Walks the workspace and indexes every php file in it. Used when composer
+/// did not generate a class map we could load.
+pub fn index_workspace(root: &Path, state: &State) {
+    let mut files = vec![];
+    collect_php_files(root, &mut files);
+    index_paths(&files, state);
+}
+
+/// Indexes the files of the workspace apart from the dependencies, which the
+/// class map does not list unless composer optimized it. Implementations are
+/// looked up in the inheritance edges of the indexed files, so those have to be
+/// known up front.
+pub fn index_project_files(state: &State) {
+    let files: Vec<PathBuf> = get_workspace_files(state)
+        .into_iter()
+        .filter(|path| {
+            path.to_str()
+                .is_some_and(|path| !state.symbol_index.contains_key(path))
+        })
+        .collect();
+
+    index_paths(&files, state);
+}
+
+/// Indexes the files while reporting the progress through the state.
+pub fn index_paths<P: AsRef<Path>>(files: &[P], state: &State) {
+    let progress = &state.index_progress;
+    progress.total.fetch_add(files.len(), Ordering::Relaxed);
+
+    for file in files {
+        index_path(file.as_ref(), state);
+        progress.indexed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns the location of a symbol if the file it lives in has been indexed.
+pub fn get_symbol_location(path: &str, fqn: &str, state: &State) -> Option<Location> {
+    state.symbol_index.get(path).and_then(|symbols| {
+        symbols
+            .iter()
+            .find(|symbol| symbol.fqn == fqn)
+            .map(|symbol| symbol.location.clone())
+    })
+}
+
+/// Returns the location of a function, indexing the file it was mapped to if
+/// that has not happened yet.
+pub fn find_function_location(fqn: &str, state: &State) -> Option<Location> {
+    find_function_symbol(fqn, state).map(|symbol| symbol.location)
+}
+
+/// Returns the indexed symbol of a function, indexing the file it was mapped to
+/// if that has not happened yet.
+pub fn find_function_symbol(fqn: &str, state: &State) -> Option<Symbol> {
+    find_mapped_symbol(fqn, SymbolKind::Function, state)
+}
+
+/// Returns the location of a constant declared with `const` or `define()`,
+/// indexing the file it was mapped to if that has not happened yet.
+pub fn find_constant_location(fqn: &str, state: &State) -> Option<Location> {
+    find_mapped_symbol(fqn, SymbolKind::Constant, state).map(|symbol| symbol.location)
+}
+
+fn find_mapped_symbol(fqn: &str, kind: SymbolKind, state: &State) -> Option<Symbol> {
+    let key = get_symbol_key(kind, fqn);
+    let path = get_symbol_map(kind, state).get(&key)?.clone();
+
+    if !state.symbol_index.contains_key(&path) {
+        index_path(Path::new(&path), state);
+    }
+
+    let symbols = state.symbol_index.get(&path)?;
+    symbols
+        .iter()
+        .find(|symbol| symbol.kind == kind && get_symbol_key(kind, &symbol.fqn) == key)
+        .cloned()
+}
+
+pub fn collect_php_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') || name == "node_modules" {
+                continue;
+            }
+
+            collect_php_files(&path, out);
+        } else if path.extension().is_some_and(|ext| ext == "php") {
+            out.push(path);
+        }
+    }
+}
+
+/// Returns the php files of the workspace apart from the dependencies.
+pub fn get_workspace_files(state: &State) -> Vec<PathBuf> {
+    let root_path = state.root_path.read().unwrap().clone();
+    let root = Path::new(&root_path);
+    let vendor = root.join("vendor");
+
+    let mut files = vec![];
+    collect_php_files(root, &mut files);
+    files.retain(|path| !path.starts_with(&vendor));
+
+    files
+}
+
+fn parse_symbols(path: &Path, content: &str) -> Vec<Symbol> {
+    let Ok(uri) = Url::from_file_path(path) else {
+        return vec![];
+    };
+
+    let interner = ThreadedInterner::new();
+    let input = Input::new(SourceIdentifier::dummy(), content.as_bytes());
+    let (program, _) = parse(&interner, input);
+
+    collect_symbols(&program, content, &uri)
+}
+
+/// Returns the key of a symbol in its symbol map. Function names are case
+/// insensitive in PHP and stored lowercased, constants are case sensitive.
+pub fn get_symbol_key(kind: SymbolKind, fqn: &str) -> String {
+    match kind {
+        SymbolKind::Function => fqn.to_ascii_lowercase(),
+        _ => fqn.to_string(),
+    }
+}
+
+fn get_symbol_map(kind: SymbolKind, state: &State) -> &DashMap<String, String> {
+    match kind {
+        SymbolKind::Function => &state.function_map,
+        SymbolKind::Constant => &state.constant_map,
+        _ => &state.class_map,
+    }
+}
+
+/// The class-likes a symbol directly extends or implements.
+fn get_supertypes(symbol: &Symbol) -> impl Iterator<Item = &String> {
+    symbol
+        .class
+        .iter()
+        .flat_map(|info| info.extends.iter().chain(info.implements.iter()))
+}
+
+fn store_symbols(path: &str, symbols: Vec<Symbol>, state: &State) {
+    for symbol in symbols.iter() {
+        get_symbol_map(symbol.kind, state)
+            .insert(get_symbol_key(symbol.kind, &symbol.fqn), path.to_string());
+        if symbol.kind.is_class_like() {
+            state.namespaces.write().unwrap().insert(&symbol.fqn);
+        }
+
+        for parent in get_supertypes(symbol) {
+            let mut subtypes = state
+                .subtypes
+                .entry(parent.to_ascii_lowercase())
+                .or_default();
+            if !subtypes.contains(&symbol.fqn) {
+                subtypes.push(symbol.fqn.clone());
+            }
+        }
+    }
+
+    state.symbol_index.insert(path.to_string(), symbols);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analyzer::indexer::{
+            collect_symbols, index_file, index_path, index_project_files, index_workspace,
+            MemberKind, SymbolKind, Visibility,
+        },
+        lsp::state::State,
+    };
+    use mago_interner::ThreadedInterner;
+    use mago_lexer::input::Input;
+    use mago_parser::parse;
+    use mago_source::SourceIdentifier;
+    use std::path::Path;
+    use tempfile::TempDir;
+    use tower_lsp::lsp_types::Url;
+
+    #[test]
+    fn collect_named_type_symbols() {
+        let content = r#"<?php
+            namespace App\Models;
+
+            class User {}
+            interface HasName {}
+            trait Greets {}
+            enum Status {}
+            function helper() {}
+        "#;
+        let uri = Url::from_file_path(Path::new("/tmp/User.php")).unwrap();
+        let interner = ThreadedInterner::new();
+        let (program, _) = parse(
+            &interner,
+            Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+        );
+
+        let symbols = collect_symbols(&program, content, &uri);
+
+        let expected = [
+            ("App\\Models\\User", SymbolKind::Class, 3, 18),
+            ("App\\Models\\HasName", SymbolKind::Interface, 4, 22),
+            ("App\\Models\\Greets", SymbolKind::Trait, 5, 18),
+            ("App\\Models\\Status", SymbolKind::Enum, 6, 17),
+            ("App\\Models\\helper", SymbolKind::Function, 7, 21),
+        ];
+        assert_eq!(symbols.len(), expected.len());
+        for (symbol, (fqn, kind, line, character)) in symbols.iter().zip(expected) {
+            assert_eq!(symbol.fqn, fqn);
+            assert_eq!(symbol.kind, kind);
+            assert_eq!(symbol.location.range.start.line, line);
+            assert_eq!(symbol.location.range.start.character, character);
+        }
+    }
+
+    #[test]
+    fn collect_class_members() {
+        let content = r#"<?php
+            namespace App\Models;
+
+            use Illuminate\Database\Eloquent\Model;
+            use App\Concerns\HasFactory;
+
+            final class User extends Model implements \JsonSerializable
+            {
+                use HasFactory;
+
+                public const TABLE = 'users';
+                protected ?Post $latestPost = null;
+
+                public static function find(int $id): ?static {}
+                private function secret() {}
+            }
+        "#;
+        let uri = Url::from_file_path(Path::new("/tmp/User.php")).unwrap();
+        let interner = ThreadedInterner::new();
+        let (program, _) = parse(
+            &interner,
+            Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+        );
+
+        let symbols = collect_symbols(&program, content, &uri);
+        let info = symbols[0].class.as_ref().unwrap();
+
+        assert_eq!(info.extends, ["Illuminate\\Database\\Eloquent\\Model"]);
+        assert_eq!(info.implements, ["JsonSerializable"]);
+        assert_eq!(info.traits, ["App\\Concerns\\HasFactory"]);
+
+        let members = info
+            .members
+            .iter()
+            .map(|member| {
+                (
+                    member.name.as_str(),
+                    member.kind,
+                    member.visibility,
+                    member.is_static,
+                    member.type_hint.as_deref(),
+                    member.range.start.line,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            members,
+            [
+                (
+                    "TABLE",
+                    MemberKind::Constant,
+                    Visibility::Public,
+                    true,
+                    None,
+                    10
+                ),
+                (
+                    "latestPost",
+                    MemberKind::Property,
+                    Visibility::Protected,
+                    false,
+                    Some("App\\Models\\Post|null"),
+                    11
+                ),
+                (
+                    "find",
+                    MemberKind::Method,
+                    Visibility::Public,
+                    true,
+                    Some("static|null"),
+                    13
+                ),
+                (
+                    "secret",
+                    MemberKind::Method,
+                    Visibility::Private,
+                    false,
+                    None,
+                    14
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn collect_symbols_from_braced_namespaces() {
+        let content = r#"<?php
+            namespace First {
+                class A {}
+            }
+            namespace Second {
+                class B {}
+            }
+        "#;
+        let uri = Url::from_file_path(Path::new("/tmp/multiple.php")).unwrap();
+        let interner = ThreadedInterner::new();
+        let (program, _) = parse(
+            &interner,
+            Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+        );
+
+        let fqns: Vec<String> = collect_symbols(&program, content, &uri)
+            .into_iter()
+            .map(|symbol| symbol.fqn)
+            .collect();
+
+        assert_eq!(fqns, vec!["First\\A", "Second\\B"]);
+    }
+
+    #[test]
+    fn index_file_replaces_stale_entries() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let path = temp_dir.path().join("Renamed.php");
+        let path_str = path.to_str().unwrap().to_string();
+
+        let state = State::default();
+        state
+            .class_map
+            .insert(String::from("App\\OldName"), path_str.clone());
+        state
+            .class_map
+            .insert(String::from("App\\Other"), String::from("/other.php"));
+
+        index_file(&path, "<?php namespace App; class NewName {}", &state);
+
+        assert!(state.class_map.get("App\\OldName").is_none());
+        assert_eq!(*state.class_map.get("App\\NewName").unwrap(), path_str);
+        assert_eq!(*state.class_map.get("App\\Other").unwrap(), "/other.php");
+    }
+
+    #[test]
+    fn index_path_replaces_stale_entries() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let path = temp_dir.path().join("Renamed.php");
+
+        let state = State::default();
+        std::fs::write(&path, "<?php namespace App; class OldName extends Model {}")
+            .expect("to write file");
+        index_path(&path, &state);
+        std::fs::write(&path, "<?php namespace App; class NewName {}").expect("to write file");
+        index_path(&path, &state);
+
+        assert!(state.class_map.get("App\\OldName").is_none());
+        assert!(state.class_map.get("App\\NewName").is_some());
+        assert!(state.subtypes.get("app\\model").unwrap().is_empty());
+    }
+
+    #[test]
+    fn index_file_tracks_subtypes() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let path = temp_dir.path().join("User.php");
+
+        let state = State::default();
+        index_file(
+            &path,
+            "<?php namespace App; class User extends Model implements \\JsonSerializable {}",
+            &state,
+        );
+
+        assert_eq!(
+            *state.subtypes.get("app\\model").unwrap(),
+            vec!["App\\User"]
+        );
+        assert_eq!(
+            *state.subtypes.get("jsonserializable").unwrap(),
+            vec!["App\\User"]
+        );
+
+        index_file(
+            &path,
+            "<?php namespace App; class User extends Model {}",
+            &state,
+        );
+
+        assert_eq!(
+            *state.subtypes.get("app\\model").unwrap(),
+            vec!["App\\User"]
+        );
+        assert!(state.subtypes.get("jsonserializable").unwrap().is_empty());
+    }
+
+    #[test]
+    fn collect_constant_symbols() {
+        let content = r#"<?php
+            namespace App;
+
+            const FIRST = 1, SECOND = 2;
+            define('App\\THIRD', 3);
+            \define('FOURTH', 4);
+            define($dynamic, 5);
+        "#;
+        let uri = Url::from_file_path(Path::new("/tmp/constants.php")).unwrap();
+        let interner = ThreadedInterner::new();
+        let (program, _) = parse(
+            &interner,
+            Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+        );
+
+        let symbols = collect_symbols(&program, content, &uri);
+
+        let constants = symbols
+            .iter()
+            .map(|symbol| {
+                (
+                    symbol.fqn.as_str(),
+                    symbol.kind,
+                    symbol.location.range.start.character,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            constants,
+            [
+                ("App\\FIRST", SymbolKind::Constant, 18),
+                ("App\\SECOND", SymbolKind::Constant, 29),
+                ("App\\THIRD", SymbolKind::Constant, 20),
+                ("FOURTH", SymbolKind::Constant, 21),
+            ]
+        );
+    }
+
+    #[test]
+    fn collect_conditionally_declared_functions() {
+        let content = r#"<?php
+            if (!function_exists('array_first')) {
+                function array_first($array) {}
+            }
+
+            if (PHP_VERSION_ID >= 80400) {
+                require __DIR__ . '/native.php';
+            } elseif (function_exists('mb_substr')) {
+                function str_head($string) {}
+            } else {
+                function str_tail($string) {}
+            }
+
+            if (!class_exists('Polyfill')):
+                class Polyfill {}
+            elseif (true):
+                interface Contract {}
+            else:
+                trait Fallback {}
+            endif;
+        "#;
+        let uri = Url::from_file_path(Path::new("/tmp/helpers.php")).unwrap();
+        let interner = ThreadedInterner::new();
+        let (program, _) = parse(
+            &interner,
+            Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+        );
+
+        let symbols = collect_symbols(&program, content, &uri);
+
+        let fqns: Vec<&str> = symbols.iter().map(|symbol| symbol.fqn.as_str()).collect();
+        assert_eq!(
+            fqns,
+            vec![
+                "array_first",
+                "str_head",
+                "str_tail",
+                "Polyfill",
+                "Contract",
+                "Fallback"
+            ]
+        );
+        assert_eq!(symbols[0].kind, SymbolKind::Function);
+    }
+
+    #[test]
+    fn index_workspace_files() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("src/Models")).expect("to create directory");
+        std::fs::create_dir_all(root.join(".git")).expect("to create directory");
+        std::fs::write(
+            root.join("src/Models/User.php"),
+            "<?php\nnamespace App\\Models;\n\nclass User {}\n",
+        )
+        .expect("to write file");
+        std::fs::write(
+            root.join("src/helpers.php"),
+            "<?php\nnamespace App;\n\nfunction helper() {}\n",
+        )
+        .expect("to write file");
+        std::fs::write(root.join(".git/Ignored.php"), "<?php class Ignored {}")
+            .expect("to write file");
+        std::fs::write(root.join("src/notes.txt"), "<?php class Text {}").expect("to write file");
+
+        let state = State::default();
+        index_workspace(root, &state);
+
+        let user_path = root.join("src/Models/User.php");
+        assert_eq!(
+            *state.class_map.get("App\\Models\\User").unwrap(),
+            user_path.to_str().unwrap()
+        );
+        assert_eq!(
+            *state.function_map.get("app\\helper").unwrap(),
+            root.join("src/helpers.php").to_str().unwrap()
+        );
+        assert_eq!(state.class_map.len(), 1);
+        assert_eq!(state.index_progress.get(), (2, 2));
+
+        let symbols = state.symbol_index.get(user_path.to_str().unwrap()).unwrap();
+        assert_eq!(symbols[0].location.range.start.line, 3);
+        assert_eq!(symbols[0].location.range.start.character, 6);
+    }
+
+    #[test]
+    fn index_project_files_missing_from_the_class_map() {
+        let temp_dir = TempDir::new().expect("to initialize temp dir");
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("src")).expect("to create directory");
+        std::fs::create_dir_all(root.join("vendor/acme")).expect("to create directory");
+        let admin_path = root.join("src/Admin.php");
+        std::fs::write(
+            &admin_path,
+            "<?php\nnamespace App;\n\nclass Admin extends User {}\n",
+        )
+        .expect("to write file");
+        let client_path = root.join("vendor/acme/Client.php");
+        std::fs::write(&client_path, "<?php\nnamespace Acme;\n\nclass Client {}\n")
+            .expect("to write file");
+
+        let state = State::default();
+        *state.root_path.write().unwrap() = root.to_str().unwrap().to_string();
+        // composer only lists the classes of the project when it is optimized
+        state.class_map.insert(
+            String::from("Acme\\Client"),
+            client_path.to_str().unwrap().to_string(),
+        );
+        index_project_files(&state);
+
+        assert!(state
+            .symbol_index
+            .contains_key(admin_path.to_str().unwrap()));
+        assert!(!state
+            .symbol_index
+            .contains_key(client_path.to_str().unwrap()));
+        assert_eq!(
+            *state.subtypes.get("app\\user").unwrap(),
+            vec!["App\\Admin"]
+        );
+    }
+}
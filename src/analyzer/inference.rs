@@ -0,0 +1,693 @@
+use mago_ast::{
+    Access, BinaryOperator, Call, Construct, DirectVariable, Expression, Foreach, ForeachTarget,
+    FunctionLikeParameter, Hint, Literal, MagicConstant, MatchArm, Node, Program, TriviaKind,
+    UnaryPrefixOperator, Variable,
+};
+use mago_span::{HasSpan, Span};
+use tower_lsp::lsp_types::{Position, Url};
+
+use crate::lsp::state::State;
+
+use super::{
+    docblock::{find_docblock, parse_docblock, split_type, DocBlock},
+    indexer::{find_function_symbol, qualify_name, MemberKind},
+    members::{find_member, get_parent_class, ClassMember},
+    names::{get_name_context, NameContext},
+    narrowing::narrow_variable_type,
+    phpdoc::{get_templates, Template},
+    tree::{get_node_name, get_nodes_for_position},
+    types::{
+        get_bound_types, get_classes, get_iterable_types, get_template_types, parse_type,
+        resolve_type, union, Type, TypeContext,
+    },
+    utils::get_offset_from_position,
+    variables::{find_variable_definition, VariableDefinition},
+};
+
+/// Everything needed to make sense of an expression: the document, the path of
+/// nodes from the program down to the expression and the names in effect.
+pub struct Scope<'a, 'b> {
+    pub document: &'b str,
+    pub nodes: &'b [Node<'a>],
+    pub names: &'b NameContext,
+}
+
+/// Builds the scope of the innermost node at the position of an open document
+/// and hands it to `f`.
+pub fn with_scope_at<T>(
+    uri: &Url,
+    position: &Position,
+    state: &State,
+    f: impl FnOnce(&Scope) -> Option<T>,
+) -> Option<T> {
+    let program = state.document_program.get(uri)?;
+    let document = state.document_map.get(uri)?;
+
+    let nodes = get_nodes_for_position(&Node::Program(&program), &document, position);
+    let names = get_name_context(
+        &program,
+        &document,
+        get_offset_from_position(&document, position),
+    );
+    let scope = Scope {
+        document: &document,
+        nodes: &nodes,
+        names: &names,
+    };
+
+    f(&scope)
+}
+
+/// Returns the type of the innermost expression at the position of a document.
+pub fn type_at(uri: &Url, position: &Position, state: &State) -> Vec<Type> {
+    with_scope_at(uri, position, state, |scope| {
+        let depth = scope
+            .nodes
+            .iter()
+            .rposition(|node| matches!(node, Node::Expression(_)))?;
+        let Node::Expression(expression) = scope.nodes[depth] else {
+            return None;
+        };
+
+        let scope = Scope {
+            nodes: &scope.nodes[..=depth],
+            ..*scope
+        };
+        Some(infer_expression_type(expression, &scope, state))
+    })
+    .unwrap_or_default()
+}
+
+/// Returns the types of the values an expression may evaluate to.
+pub fn infer_expression_type(expression: &Expression, scope: &Scope, state: &State) -> Vec<Type> {
+    match expression {
+        Expression::Parenthesized(parenthesized) => {
+            infer_expression_type(&parenthesized.expression, scope, state)
+        }
+        Expression::Self_(_) | Expression::Static(_) => class_types(get_current_class(scope)),
+        Expression::Parent(_) => {
+            class_types(get_current_class(scope).and_then(|class| get_parent_class(&class, state)))
+        }
+        Expression::Identifier(identifier) => {
+            resolve_class_name(&get_node_name(scope.document, identifier), scope, state)
+                .into_iter()
+                .map(Type::Class)
+                .collect()
+        }
+        Expression::Instantiation(instantiation) => match instantiation.class.as_ref() {
+            Expression::Identifier(_)
+            | Expression::Self_(_)
+            | Expression::Static(_)
+            | Expression::Parent(_) => infer_expression_type(&instantiation.class, scope, state),
+            _ => vec![Type::builtin("object")],
+        },
+        Expression::AnonymousClass(_) => vec![Type::builtin("object")],
+        Expression::Clone(clone) => infer_expression_type(&clone.object, scope, state),
+        Expression::Variable(Variable::Direct(variable)) => resolve_variable_type(
+            &get_node_name(scope.document, variable),
+            variable.span().start.offset,
+            scope,
+            state,
+        ),
+        Expression::Assignment(assignment) => infer_expression_type(&assignment.rhs, scope, state),
+        Expression::Call(Call::Function(call)) => match call.function.as_ref() {
+            Expression::Identifier(identifier) => {
+                resolve_function_type(&get_node_name(scope.document, identifier), scope, state)
+            }
+            _ => vec![],
+        },
+        Expression::Call(Call::Method(call)) => resolve_member_type(
+            &call.object,
+            &get_node_name(scope.document, &call.method),
+            MemberKind::Method,
+            scope,
+            state,
+        ),
+        Expression::Call(Call::NullSafeMethod(call)) => union(
+            resolve_member_type(
+                &call.object,
+                &get_node_name(scope.document, &call.method),
+                MemberKind::Method,
+                scope,
+                state,
+            ),
+            vec![Type::builtin("null")],
+        ),
+        Expression::Call(Call::StaticMethod(call)) => resolve_member_type(
+            &call.class,
+            &get_node_name(scope.document, &call.method),
+            MemberKind::Method,
+            scope,
+            state,
+        ),
+        Expression::Access(Access::Property(access)) => resolve_member_type(
+            &access.object,
+            &get_node_name(scope.document, &access.property),
+            MemberKind::Property,
+            scope,
+            state,
+        ),
+        Expression::Access(Access::NullSafeProperty(access)) => union(
+            resolve_member_type(
+                &access.object,
+                &get_node_name(scope.document, &access.property),
+                MemberKind::Property,
+                scope,
+                state,
+            ),
+            vec![Type::builtin("null")],
+        ),
+        Expression::Access(Access::StaticProperty(access)) => resolve_member_type(
+            &access.class,
+            get_node_name(scope.document, &access.property).trim_start_matches('$'),
+            MemberKind::Property,
+            scope,
+            state,
+        ),
+        Expression::Access(Access::ClassConstant(access)) => {
+            let name = get_node_name(scope.document, &access.constant);
+            match name.eq_ignore_ascii_case("class") {
+                true => vec![Type::builtin("string")],
+                false => {
+                    resolve_member_type(&access.class, &name, MemberKind::Constant, scope, state)
+                }
+            }
+        }
+        Expression::Literal(literal) => vec![Type::builtin(match literal {
+            Literal::String(_) => "string",
+            Literal::Integer(_) => "int",
+            Literal::Float(_) => "float",
+            Literal::True(_) => "true",
+            Literal::False(_) => "false",
+            Literal::Null(_) => "null",
+        })],
+        Expression::CompositeString(_) => vec![Type::builtin("string")],
+        Expression::MagicConstant(MagicConstant::Line(_)) => vec![Type::builtin("int")],
+        Expression::MagicConstant(_) => vec![Type::builtin("string")],
+        Expression::Array(_) | Expression::LegacyArray(_) => vec![Type::builtin("array")],
+        Expression::ArrayAccess(access) => {
+            get_iterable_types(&infer_expression_type(&access.array, scope, state), state).1
+        }
+        Expression::Closure(_) | Expression::ArrowFunction(_) | Expression::ClosureCreation(_) => {
+            vec![Type::Class(String::from("Closure"))]
+        }
+        Expression::Binary(binary) => match binary.operator {
+            // `$foo ?? $bar` is only null when `$bar` can be
+            BinaryOperator::NullCoalesce(_) => union(
+                without_null(infer_expression_type(&binary.lhs, scope, state)),
+                infer_expression_type(&binary.rhs, scope, state),
+            ),
+            BinaryOperator::Elvis(_) => union(
+                infer_expression_type(&binary.lhs, scope, state)
+                    .into_iter()
+                    .filter(|r#type| !r#type.is_null() && *r#type != Type::builtin("false"))
+                    .collect(),
+                infer_expression_type(&binary.rhs, scope, state),
+            ),
+            BinaryOperator::StringConcat(_) => vec![Type::builtin("string")],
+            BinaryOperator::Addition(_)
+            | BinaryOperator::Subtraction(_)
+            | BinaryOperator::Multiplication(_)
+            | BinaryOperator::Division(_)
+            | BinaryOperator::Exponentiation(_) => {
+                let operands = union(
+                    infer_expression_type(&binary.lhs, scope, state),
+                    infer_expression_type(&binary.rhs, scope, state),
+                );
+                match operands
+                    .iter()
+                    .all(|r#type| *r#type == Type::builtin("int"))
+                {
+                    true if !matches!(binary.operator, BinaryOperator::Division(_)) => {
+                        vec![Type::builtin("int")]
+                    }
+                    _ => vec![Type::builtin("int"), Type::builtin("float")],
+                }
+            }
+            BinaryOperator::Modulo(_)
+            | BinaryOperator::BitwiseAnd(_)
+            | BinaryOperator::BitwiseOr(_)
+            | BinaryOperator::BitwiseXor(_)
+            | BinaryOperator::LeftShift(_)
+            | BinaryOperator::RightShift(_)
+            | BinaryOperator::Spaceship(_) => vec![Type::builtin("int")],
+            _ => vec![Type::builtin("bool")],
+        },
+        Expression::Conditional(conditional) => union(
+            match &conditional.then {
+                Some(then) => infer_expression_type(then, scope, state),
+                // `$foo ?: $bar`
+                None => infer_expression_type(&conditional.condition, scope, state),
+            },
+            infer_expression_type(&conditional.r#else, scope, state),
+        ),
+        Expression::Match(r#match) => r#match.arms.iter().fold(vec![], |types, arm| {
+            let expression = match arm {
+                MatchArm::Expression(arm) => &arm.expression,
+                MatchArm::Default(arm) => &arm.expression,
+            };
+
+            union(types, infer_expression_type(expression, scope, state))
+        }),
+        Expression::UnaryPrefix(unary) => match &unary.operator {
+            UnaryPrefixOperator::Not(_) => vec![Type::builtin("bool")],
+            UnaryPrefixOperator::ArrayCast(..) => vec![Type::builtin("array")],
+            UnaryPrefixOperator::BoolCast(..) | UnaryPrefixOperator::BooleanCast(..) => {
+                vec![Type::builtin("bool")]
+            }
+            UnaryPrefixOperator::DoubleCast(..)
+            | UnaryPrefixOperator::RealCast(..)
+            | UnaryPrefixOperator::FloatCast(..) => vec![Type::builtin("float")],
+            UnaryPrefixOperator::IntCast(..) | UnaryPrefixOperator::IntegerCast(..) => {
+                vec![Type::builtin("int")]
+            }
+            UnaryPrefixOperator::ObjectCast(..) => vec![Type::builtin("object")],
+            UnaryPrefixOperator::UnsetCast(..) => vec![Type::builtin("null")],
+            UnaryPrefixOperator::StringCast(..) | UnaryPrefixOperator::BinaryCast(..) => {
+                vec![Type::builtin("string")]
+            }
+            _ => infer_expression_type(&unary.operand, scope, state),
+        },
+        Expression::Construct(Construct::Isset(_) | Construct::Empty(_)) => {
+            vec![Type::builtin("bool")]
+        }
+        Expression::Throw(_) => vec![Type::builtin("never")],
+        _ => vec![],
+    }
+}
+
+/// Returns the fully qualified names of the classes an expression may evaluate to.
+pub fn resolve_expression_classes(
+    expression: &Expression,
+    scope: &Scope,
+    state: &State,
+) -> Vec<String> {
+    get_classes(&infer_expression_type(expression, scope, state))
+}
+
+/// Resolves a class name written in the document, including `self`, `static`
+/// and `parent`.
+pub fn resolve_class_name(name: &str, scope: &Scope, state: &State) -> Vec<String> {
+    let resolved = scope.names.resolve_class(name);
+    match resolved.as_str() {
+        "self" | "static" => get_current_class(scope).into_iter().collect(),
+        "parent" => get_current_class(scope)
+            .and_then(|class| get_parent_class(&class, state))
+            .into_iter()
+            .collect(),
+        _ => vec![resolved],
+    }
+}
+
+/// Returns the fully qualified name of the innermost class-like around the
+/// expression.
+pub fn get_current_class(scope: &Scope) -> Option<String> {
+    scope.nodes.iter().rev().find_map(|node| {
+        let name = match node {
+            Node::Class(class) => &class.name,
+            Node::Interface(interface) => &interface.name,
+            Node::Trait(r#trait) => &r#trait.name,
+            Node::Enum(r#enum) => &r#enum.name,
+            _ => return None,
+        };
+
+        Some(qualify_name(
+            &scope.names.namespace,
+            &get_node_name(scope.document, name),
+        ))
+    })
+}
+
+/// Returns the type of a member as seen from the class it was accessed on,
+/// with the template parameters standing for the arguments that class was
+/// given. The documented type wins over the declared one since it is usually
+/// the more precise of the two.
+pub fn get_member_type(
+    found: &ClassMember,
+    called_class: &str,
+    arguments: &[Vec<Type>],
+    state: &State,
+) -> Vec<Type> {
+    let declaring_class = &found.class.fqn;
+    let mut templates = get_template_types(called_class, arguments, declaring_class, state);
+    templates.extend(get_bound_types(
+        &found.member.templates,
+        declaring_class,
+        state,
+    ));
+    let context = TypeContext {
+        declaring_class,
+        called_class,
+        called_arguments: arguments,
+        templates: &templates,
+    };
+
+    resolve_declared_type(
+        &found.member.doc_type,
+        &found.member.type_hint,
+        &context,
+        state,
+    )
+}
+
+/// Returns the type of the value a variable holds at the offset: the type of its
+/// last definition, overridden by `@var` annotations and narrowed down by the
+/// conditions the offset is guarded by.
+pub fn resolve_variable_type(name: &str, offset: usize, scope: &Scope, state: &State) -> Vec<Type> {
+    let Some(resolved) = find_variable_definition(name, offset, scope) else {
+        return vec![];
+    };
+
+    // the definition is resolved in the scope it was written in
+    let definition_scope = Scope {
+        document: scope.document,
+        nodes: &scope.nodes[..=resolved.depth],
+        names: scope.names,
+    };
+    let definition = resolved.definition.span();
+    let annotated = || find_attached_annotation(name, definition.start.offset, scope, state);
+
+    let types = match resolved.definition {
+        VariableDefinition::This(_) => class_types(get_current_class(&definition_scope)),
+        VariableDefinition::Parameter(parameter) => {
+            get_parameter_type(parameter, resolved.depth, &definition_scope, state)
+        }
+        VariableDefinition::Assignment(_, value) | VariableDefinition::Static(_, Some(value)) => {
+            annotated().unwrap_or_else(|| infer_expression_type(value, &definition_scope, state))
+        }
+        VariableDefinition::Catch(_, clause) => {
+            resolve_hint_type(&clause.hint, &definition_scope, state)
+        }
+        VariableDefinition::Foreach(variable, foreach) => annotated()
+            .unwrap_or_else(|| get_foreach_type(variable, foreach, &definition_scope, state)),
+        _ => annotated().unwrap_or_default(),
+    };
+    // `/** @var Foo $foo */` somewhere after the definition
+    let types = find_later_annotation(
+        name,
+        &scope.nodes[resolved.depth],
+        definition.end.offset,
+        offset,
+        scope,
+        state,
+    )
+    .unwrap_or(types);
+
+    narrow_variable_type(types, name, definition.start.offset, offset, scope, state)
+}
+
+/// Resolves a type written in a docblock of the document. The template
+/// parameters of the declarations around it stand for their bounds.
+pub fn resolve_doc_type(text: &str, scope: &Scope, state: &State) -> Vec<Type> {
+    let current_class = get_current_class(scope).unwrap_or_default();
+    let templates = get_scope_templates(scope);
+
+    resolve_type(
+        &scope.names.resolve_doc_type(text, &templates),
+        &TypeContext {
+            declaring_class: &current_class,
+            called_class: &current_class,
+            called_arguments: &[],
+            templates: &get_bound_types(&templates, &current_class, state),
+        },
+        state,
+    )
+}
+
+pub fn resolve_hint_type(hint: &Hint, scope: &Scope, state: &State) -> Vec<Type> {
+    let current_class = get_current_class(scope).unwrap_or_default();
+
+    parse_type(
+        &scope.names.resolve_hint(hint, scope.document),
+        &current_class,
+        &current_class,
+        state,
+    )
+}
+
+fn class_types(class: Option<String>) -> Vec<Type> {
+    class.into_iter().map(Type::Class).collect()
+}
+
+fn without_null(types: Vec<Type>) -> Vec<Type> {
+    types
+        .into_iter()
+        .filter(|r#type| !r#type.is_null())
+        .collect()
+}
+
+fn resolve_member_type(
+    object: &Expression,
+    name: &str,
+    kind: MemberKind,
+    scope: &Scope,
+    state: &State,
+) -> Vec<Type> {
+    let mut types = vec![];
+
+    for r#type in infer_expression_type(object, scope, state) {
+        let (class, arguments) = match &r#type {
+            Type::Class(class) => (class, [].as_slice()),
+            Type::Generic(class, arguments) => (class, arguments.as_slice()),
+            _ => continue,
+        };
+
+        if let Some(found) = find_member(class, name, kind, state) {
+            types = union(types, get_member_type(&found, class, arguments, state));
+        }
+    }
+
+    types
+}
+
+/// Returns the type of the key or value variable of a `foreach`. Variables
+/// destructured from the values are not known.
+fn get_foreach_type(
+    variable: &DirectVariable,
+    foreach: &Foreach,
+    scope: &Scope,
+    state: &State,
+) -> Vec<Type> {
+    let is_variable = |expression: &Expression| matches!(expression, Expression::Variable(Variable::Direct(direct)) if direct.span() == variable.span());
+    let (keys, values) = get_iterable_types(
+        &infer_expression_type(&foreach.expression, scope, state),
+        state,
+    );
+
+    match &foreach.target {
+        ForeachTarget::KeyValue(target) if is_variable(&target.key) => keys,
+        ForeachTarget::KeyValue(target) if is_variable(&target.value) => values,
+        ForeachTarget::Value(target) if is_variable(&target.value) => values,
+        _ => vec![],
+    }
+}
+
+fn resolve_function_type(name: &str, scope: &Scope, state: &State) -> Vec<Type> {
+    let Some(symbol) = scope
+        .names
+        .resolve_function(name)
+        .iter()
+        .find_map(|fqn| find_function_symbol(fqn, state))
+    else {
+        return vec![];
+    };
+
+    let Some(function) = symbol.function else {
+        return vec![];
+    };
+    let templates = get_bound_types(&function.templates, "", state);
+    let context = TypeContext {
+        declaring_class: "",
+        called_class: "",
+        called_arguments: &[],
+        templates: &templates,
+    };
+
+    resolve_declared_type(&function.doc_type, &function.type_hint, &context, state)
+}
+
+/// Resolves the documented type of a declaration, falling back to its declared
+/// type when nothing is known about the documented one, e.g. an unbound
+/// template.
+fn resolve_declared_type(
+    doc_type: &Option<String>,
+    type_hint: &Option<String>,
+    context: &TypeContext,
+    state: &State,
+) -> Vec<Type> {
+    doc_type
+        .iter()
+        .chain(type_hint.iter())
+        .map(|type_hint| resolve_type(type_hint, context, state))
+        .find(|types| !types.is_empty())
+        .unwrap_or_default()
+}
+
+/// The `@param` tag of the function-like declaring the parameter wins over its
+/// type hint. Variadic parameters are arrays of whatever they are hinted with.
+fn get_parameter_type(
+    parameter: &FunctionLikeParameter,
+    depth: usize,
+    scope: &Scope,
+    state: &State,
+) -> Vec<Type> {
+    if parameter.ellipsis.is_some() {
+        return vec![Type::builtin("array")];
+    }
+
+    let name = get_node_name(scope.document, &parameter.variable);
+    let documented = get_program(scope)
+        .and_then(|program| {
+            let function_like = scope.nodes[depth].span();
+            find_docblock(program, scope.document, function_like.start.offset)
+        })
+        .and_then(|docblock| get_tag_type(&docblock, "param", &name, false))
+        .map(|text| resolve_doc_type(&text, scope, state));
+
+    match (documented, &parameter.hint) {
+        (Some(types), _) => types,
+        (None, Some(hint)) => resolve_hint_type(hint, scope, state),
+        (None, None) => vec![],
+    }
+}
+
+/// Returns the type of a `@var` annotation written right in front of the
+/// statement defining the variable, e.g. `/** @var User $user */` above
+/// `$user = $repository->find(1);`.
+fn find_attached_annotation(
+    name: &str,
+    offset: usize,
+    scope: &Scope,
+    state: &State,
+) -> Option<Vec<Type>> {
+    let program = get_program(scope)?;
+    let trivia = program
+        .trivia
+        .iter()
+        .take_while(|trivia| trivia.span.end.offset <= offset)
+        .filter(|trivia| trivia.kind == TriviaKind::DocBlockComment)
+        .last()?;
+
+    // nothing but the start of the same statement may come in between
+    let between = &scope.document[trivia.span.end.offset..offset];
+    if between.contains([';', '{', '}']) {
+        return None;
+    }
+
+    let docblock = parse_docblock(&get_node_name(scope.document, trivia.span));
+    get_tag_type(&docblock, "var", name, false).map(|text| resolve_doc_type(&text, scope, state))
+}
+
+/// Returns the type of the last `@var` annotation naming the variable between
+/// the two offsets in the function-like the variable is defined in. Nested
+/// function-likes the second offset is not in have variables of their own.
+fn find_later_annotation(
+    name: &str,
+    variable_scope: &Node,
+    from: usize,
+    to: usize,
+    scope: &Scope,
+    state: &State,
+) -> Option<Vec<Type>> {
+    let program = get_program(scope)?;
+    let mut nested = vec![];
+    collect_nested_function_likes(variable_scope, from, to, &mut nested);
+
+    program
+        .trivia
+        .iter()
+        .rev()
+        .filter(|trivia| {
+            trivia.kind == TriviaKind::DocBlockComment
+                && trivia.span.start.offset >= from
+                && trivia.span.end.offset <= to
+                && !nested.iter().any(|span| {
+                    span.start.offset <= trivia.span.start.offset
+                        && trivia.span.end.offset <= span.end.offset
+                })
+        })
+        .find_map(|trivia| {
+            let docblock = parse_docblock(&get_node_name(scope.document, trivia.span));
+            get_tag_type(&docblock, "var", name, true)
+        })
+        .map(|text| resolve_doc_type(&text, scope, state))
+}
+
+/// Collects the spans of the function-likes below the node that overlap the
+/// two offsets without containing the second one.
+fn collect_nested_function_likes(node: &Node, from: usize, to: usize, spans: &mut Vec<Span>) {
+    for child in node.children() {
+        let span = child.span();
+        if span.end.offset < from || span.start.offset > to {
+            continue;
+        }
+
+        let is_function_like = matches!(
+            child,
+            Node::Function(_) | Node::Method(_) | Node::Closure(_) | Node::ArrowFunction(_)
+        );
+        if is_function_like && span.end.offset < to {
+            spans.push(span);
+            continue;
+        }
+
+        collect_nested_function_likes(&child, from, to, spans);
+    }
+}
+
+/// Returns the type of a tag naming the variable. Tags without a variable
+/// count as well unless `is_named` is set.
+fn get_tag_type(docblock: &DocBlock, tag: &str, name: &str, is_named: bool) -> Option<String> {
+    docblock.get_type_tags(tag).find_map(|tag| {
+        let (type_name, rest) = split_type(&tag.body);
+        if type_name.is_empty() || type_name.starts_with('$') {
+            return None;
+        }
+
+        match rest.split_whitespace().next() {
+            Some(variable) if variable.starts_with('$') => {
+                (variable == name).then(|| type_name.to_string())
+            }
+            _ => (!is_named).then(|| type_name.to_string()),
+        }
+    })
+}
+
+/// Returns the template parameters declared by the class-like and the
+/// function-likes around the expression, with their bounds fully qualified.
+fn get_scope_templates(scope: &Scope) -> Vec<Template> {
+    let Some(program) = get_program(scope) else {
+        return vec![];
+    };
+
+    scope
+        .nodes
+        .iter()
+        .filter(|node| {
+            matches!(
+                node,
+                Node::Class(_)
+                    | Node::Interface(_)
+                    | Node::Trait(_)
+                    | Node::Enum(_)
+                    | Node::Method(_)
+                    | Node::Function(_)
+            )
+        })
+        .filter_map(|node| find_docblock(program, scope.document, node.span().start.offset))
+        .flat_map(|docblock| get_templates(&docblock))
+        .map(|template| Template {
+            bound: template
+                .bound
+                .map(|bound| scope.names.resolve_doc_type(&bound, &[])),
+            ..template
+        })
+        .collect()
+}
+
+fn get_program<'a>(scope: &Scope<'a, '_>) -> Option<&'a Program> {
+    match scope.nodes.first() {
+        Some(Node::Program(program)) => Some(program),
+        _ => None,
+    }
+}
@@ -0,0 +1,186 @@
+use std::{collections::HashSet, path::Path};
+
+use tower_lsp::lsp_types::Location;
+
+use crate::lsp::state::State;
+
+use super::{
+    composer::resolve_class_path,
+    indexer::{index_path, Member, MemberKind, Symbol, SymbolKind},
+};
+
+/// A member together with the class-like that declares it.
+#[derive(Debug, Clone)]
+pub struct ClassMember {
+    pub class: Symbol,
+    pub member: Member,
+}
+
+impl ClassMember {
+    pub fn location(&self) -> Location {
+        Location::new(self.class.location.uri.clone(), self.member.range)
+    }
+}
+
+/// Returns the indexed symbol of a class-like, indexing the file it lives in
+/// when that has not happened yet.
+pub fn find_class_symbol(fqn: &str, state: &State) -> Option<Symbol> {
+    let fqn = fqn.trim_start_matches('\\');
+    let path = resolve_class_path(fqn, state)?;
+    if !state.symbol_index.contains_key(&path) {
+        index_path(Path::new(&path), state);
+    }
+
+    let symbols = state.symbol_index.get(&path)?;
+    symbols
+        .iter()
+        .find(|symbol| symbol.kind.is_class_like() && symbol.fqn.eq_ignore_ascii_case(fqn))
+        .cloned()
+}
+
+/// Finds a member of a class-like, looking at the class itself, the traits it
+/// uses, its parents and finally its interfaces.
+pub fn find_member(
+    class: &str,
+    name: &str,
+    kind: MemberKind,
+    state: &State,
+) -> Option<ClassMember> {
+    let mut found = None;
+    walk_class_hierarchy(class, state, &mut |symbol| {
+        let member = symbol.class.as_ref().and_then(|info| {
+            info.members
+                .iter()
+                .find(|member| is_same_member(member, kind, name))
+        });
+
+        match member {
+            Some(member) => {
+                found = Some(ClassMember {
+                    class: symbol.clone(),
+                    member: member.clone(),
+                });
+                false
+            }
+            None => true,
+        }
+    });
+
+    found
+}
+
+/// Returns every member a class-like has, its own first and then the ones it
+/// inherits that it does not override.
+pub fn get_members(class: &str, state: &State) -> Vec<ClassMember> {
+    let mut members: Vec<ClassMember> = vec![];
+    walk_class_hierarchy(class, state, &mut |symbol| {
+        let Some(info) = &symbol.class else {
+            return true;
+        };
+
+        for member in info.members.iter() {
+            let is_overridden = members
+                .iter()
+                .any(|found| is_same_member(&found.member, member.kind, &member.name));
+            if !is_overridden {
+                members.push(ClassMember {
+                    class: symbol.clone(),
+                    member: member.clone(),
+                });
+            }
+        }
+
+        true
+    });
+
+    members
+}
+
+/// Whether a class-like is the other one or inherits from it.
+pub fn is_subclass_of(class: &str, parent: &str, state: &State) -> bool {
+    let mut found = false;
+    walk_class_hierarchy(class, state, &mut |symbol| {
+        found = symbol
+            .fqn
+            .eq_ignore_ascii_case(parent.trim_start_matches('\\'));
+        !found
+    });
+
+    found
+}
+
+/// Returns the parent class of a class, if it has one.
+pub fn get_parent_class(class: &str, state: &State) -> Option<String> {
+    let symbol = find_class_symbol(class, state)?;
+    match symbol.kind {
+        SymbolKind::Class => symbol.class?.extends.into_iter().next(),
+        _ => None,
+    }
+}
+
+/// Visits a class-like and everything it inherits from in member lookup order
+/// until the visitor returns false. Every class-like is visited once.
+pub fn walk_class_hierarchy(class: &str, state: &State, visit: &mut impl FnMut(&Symbol) -> bool) {
+    let mut visited = HashSet::new();
+    let mut interfaces = vec![];
+
+    if walk_classes(class, state, &mut visited, &mut interfaces, visit) {
+        while let Some(interface) = interfaces.pop() {
+            if !walk_classes(&interface, state, &mut visited, &mut interfaces, visit) {
+                return;
+            }
+        }
+    }
+}
+
+fn walk_classes(
+    class: &str,
+    state: &State,
+    visited: &mut HashSet<String>,
+    interfaces: &mut Vec<String>,
+    visit: &mut impl FnMut(&Symbol) -> bool,
+) -> bool {
+    if !visited.insert(class.to_ascii_lowercase()) {
+        return true;
+    }
+    let Some(symbol) = find_class_symbol(class, state) else {
+        return true;
+    };
+    if !visit(&symbol) {
+        return false;
+    }
+
+    let Some(info) = &symbol.class else {
+        return true;
+    };
+
+    // interfaces are searched last since they only declare what the classes implement
+    interfaces.extend(info.implements.iter().rev().cloned());
+
+    for r#trait in info.traits.iter() {
+        if !walk_classes(r#trait, state, visited, interfaces, visit) {
+            return false;
+        }
+    }
+
+    for parent in info.extends.iter() {
+        if !walk_classes(parent, state, visited, interfaces, visit) {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn is_same_member(member: &Member, kind: MemberKind, name: &str) -> bool {
+    match kind {
+        // method names are case insensitive
+        MemberKind::Method => member.kind == kind && member.name.eq_ignore_ascii_case(name),
+        // `Foo::BAR` could be either a constant or an enum case
+        MemberKind::Constant | MemberKind::EnumCase => {
+            matches!(member.kind, MemberKind::Constant | MemberKind::EnumCase)
+                && member.name == name
+        }
+        MemberKind::Property => member.kind == kind && member.name == name,
+    }
+}
@@ -1,5 +1,22 @@
+pub mod cache;
+pub mod completion;
 pub mod composer;
+pub mod docblock;
+pub mod hover;
+pub mod implementations;
+pub mod imports;
+pub mod indexer;
+pub mod inference;
+pub mod members;
+pub mod names;
+pub mod namespaces;
+pub mod narrowing;
 pub mod parser;
+pub mod phpdoc;
 pub mod query;
+pub mod references;
+pub mod signature;
 pub mod tree;
+pub mod types;
 pub mod utils;
+pub mod variables;
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+
+use mago_ast::{Hint, NamespaceBody, Program, Statement, Use, UseItem, UseItems, UseType};
+use mago_span::HasSpan;
+
+use super::{
+    indexer::qualify_name,
+    phpdoc::{parse_doc_type, Template},
+    tree::get_node_name,
+    types::is_doc_builtin_type,
+};
+
+/// What a `use` statement imports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportKind {
+    Class,
+    Function,
+    Constant,
+}
+
+/// The namespace and the imports in effect at some point of a document, used to
+/// turn the names written there into fully qualified ones.
+#[derive(Debug, Default, Clone)]
+pub struct NameContext {
+    pub namespace: String,
+    classes: HashMap<String, String>,
+    functions: HashMap<String, String>,
+    constants: HashMap<String, String>,
+}
+
+impl NameContext {
+    pub fn new(namespace: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_use(&mut self, statement: &Use, content: &str) {
+        for (kind, fqn, item) in get_imports(&statement.items, content) {
+            let alias = match &item.alias {
+                Some(alias) => get_node_name(content, &alias.identifier),
+                None => fqn.rsplit('\\').next().unwrap_or_default().to_string(),
+            };
+
+            self.import(kind, &fqn, &alias);
+        }
+    }
+
+    pub fn import(&mut self, kind: ImportKind, fqn: &str, alias: &str) {
+        let fqn = fqn.trim_start_matches('\\').to_string();
+        match kind {
+            ImportKind::Class => self.classes.insert(alias.to_ascii_lowercase(), fqn),
+            ImportKind::Function => self.functions.insert(alias.to_ascii_lowercase(), fqn),
+            // constants are case sensitive unlike classes and functions
+            ImportKind::Constant => self.constants.insert(alias.to_string(), fqn),
+        };
+    }
+
+    /// Returns the fully qualified name of a class name as written in the
+    /// document. `self`, `static` and `parent` are returned as they are.
+    pub fn resolve_class(&self, name: &str) -> String {
+        if let Some(fqn) = name.strip_prefix('\\') {
+            return fqn.to_string();
+        }
+        if is_relative_class_name(name) {
+            return name.to_ascii_lowercase();
+        }
+        if let Some(relative) = name.strip_prefix("namespace\\") {
+            return qualify_name(&self.namespace, relative);
+        }
+
+        match name.split_once('\\') {
+            Some((first, rest)) => match self.classes.get(&first.to_ascii_lowercase()) {
+                Some(fqn) => format!("{}\\{}", fqn, rest),
+                None => qualify_name(&self.namespace, name),
+            },
+            None => self
+                .classes
+                .get(&name.to_ascii_lowercase())
+                .cloned()
+                .unwrap_or_else(|| qualify_name(&self.namespace, name)),
+        }
+    }
+
+    /// Returns the fully qualified names a function call may refer to in the
+    /// order PHP tries them: unqualified names that are not imported fall back
+    /// to the global namespace.
+    pub fn resolve_function(&self, name: &str) -> Vec<String> {
+        self.resolve_with_fallback(name, self.functions.get(&name.to_ascii_lowercase()))
+    }
+
+    /// Same as `resolve_function` but for constants, whose names are case
+    /// sensitive.
+    pub fn resolve_constant(&self, name: &str) -> Vec<String> {
+        self.resolve_with_fallback(name, self.constants.get(name))
+    }
+
+    /// Renders a type hint with every class name in it fully qualified, e.g.
+    /// `?User` becomes `App\Models\User|null`.
+    pub fn resolve_hint(&self, hint: &Hint, content: &str) -> String {
+        match hint {
+            Hint::Identifier(identifier) => self.resolve_class(&get_node_name(content, identifier)),
+            Hint::Nullable(nullable) => {
+                format!("{}|null", self.resolve_hint(&nullable.hint, content))
+            }
+            Hint::Union(union) => format!(
+                "{}|{}",
+                self.resolve_hint(&union.left, content),
+                self.resolve_hint(&union.right, content)
+            ),
+            Hint::Intersection(intersection) => format!(
+                "{}&{}",
+                self.resolve_hint(&intersection.left, content),
+                self.resolve_hint(&intersection.right, content)
+            ),
+            Hint::Parenthesized(parenthesized) => {
+                format!("({})", self.resolve_hint(&parenthesized.hint, content))
+            }
+            _ => get_node_name(content, hint).to_ascii_lowercase(),
+        }
+    }
+
+    /// Same as `resolve_hint` but for a type written in a docblock, e.g.
+    /// `Collection<int, User>|null`. Builtin and pseudo types and the template
+    /// parameters in scope are left alone. Text that is not a type is returned
+    /// as it is.
+    pub fn resolve_doc_type(&self, text: &str, templates: &[Template]) -> String {
+        let Some(doc_type) = parse_doc_type(text) else {
+            return text.to_string();
+        };
+
+        doc_type
+            .map_names(&|name| {
+                if templates.iter().any(|template| template.name == name) || name.starts_with('$') {
+                    name.to_string()
+                } else if is_doc_builtin_type(name) {
+                    name.to_ascii_lowercase()
+                } else {
+                    self.resolve_class(name)
+                }
+            })
+            .to_string()
+    }
+
+    fn resolve_with_fallback(&self, name: &str, imported: Option<&String>) -> Vec<String> {
+        if let Some(fqn) = name.strip_prefix('\\') {
+            return vec![fqn.to_string()];
+        }
+        if name.contains('\\') {
+            // qualified names are resolved like class names
+            return vec![self.resolve_class(name)];
+        }
+        if let Some(fqn) = imported {
+            return vec![fqn.clone()];
+        }
+
+        let namespaced = qualify_name(&self.namespace, name);
+        if namespaced == name {
+            vec![namespaced]
+        } else {
+            vec![namespaced, name.to_string()]
+        }
+    }
+}
+
+/// Returns the namespace and imports in effect at the byte offset of a document.
+pub fn get_name_context(program: &Program, content: &str, offset: usize) -> NameContext {
+    let mut global = NameContext::default();
+
+    for statement in program.statements.iter() {
+        match statement {
+            Statement::Use(r#use) => global.add_use(r#use, content),
+            Statement::Namespace(namespace) => {
+                let span = namespace.span();
+                if offset < span.start.offset || offset > span.end.offset {
+                    continue;
+                }
+
+                let name = namespace
+                    .name
+                    .as_ref()
+                    .map(|name| get_node_name(content, name))
+                    .unwrap_or_default();
+                let statements = match &namespace.body {
+                    NamespaceBody::Implicit(body) => body.statements.iter(),
+                    NamespaceBody::BraceDelimited(block) => block.statements.iter(),
+                };
+
+                let mut context = NameContext::new(&name);
+                for statement in statements {
+                    if let Statement::Use(r#use) = statement {
+                        context.add_use(r#use, content);
+                    }
+                }
+
+                return context;
+            }
+            _ => {}
+        }
+    }
+
+    global
+}
+
+/// Returns what every item of a `use` statement imports together with the
+/// fully qualified name it imports.
+pub fn get_imports<'a>(
+    items: &'a UseItems,
+    content: &str,
+) -> Vec<(ImportKind, String, &'a UseItem)> {
+    let qualify = |prefix: &str, item: &UseItem| {
+        let name = get_node_name(content, &item.name);
+        if prefix.is_empty() {
+            name
+        } else {
+            format!("{}\\{}", prefix.trim_end_matches('\\'), name)
+        }
+    };
+
+    match items {
+        UseItems::Sequence(sequence) => sequence
+            .items
+            .iter()
+            .map(|item| (ImportKind::Class, qualify("", item), item))
+            .collect(),
+        UseItems::TypedSequence(sequence) => {
+            let kind = get_import_kind(&sequence.r#type);
+            sequence
+                .items
+                .iter()
+                .map(|item| (kind, qualify("", item), item))
+                .collect()
+        }
+        UseItems::TypedList(list) => {
+            let kind = get_import_kind(&list.r#type);
+            let prefix = get_node_name(content, &list.namespace);
+            list.items
+                .iter()
+                .map(|item| (kind, qualify(&prefix, item), item))
+                .collect()
+        }
+        UseItems::MixedList(list) => {
+            let prefix = get_node_name(content, &list.namespace);
+            list.items
+                .iter()
+                .map(|item| {
+                    let kind = item
+                        .r#type
+                        .as_ref()
+                        .map(get_import_kind)
+                        .unwrap_or(ImportKind::Class);
+                    (kind, qualify(&prefix, &item.item), &item.item)
+                })
+                .collect()
+        }
+    }
+}
+
+pub fn is_relative_class_name(name: &str) -> bool {
+    ["self", "static", "parent"]
+        .iter()
+        .any(|keyword| name.eq_ignore_ascii_case(keyword))
+}
+
+fn get_import_kind(r#type: &UseType) -> ImportKind {
+    match r#type {
+        UseType::Function(_) => ImportKind::Function,
+        UseType::Const(_) => ImportKind::Constant,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mago_interner::ThreadedInterner;
+    use mago_lexer::input::Input;
+    use mago_parser::parse;
+    use mago_source::SourceIdentifier;
+
+    use crate::analyzer::{names::get_name_context, phpdoc::Template};
+
+    #[test]
+    fn resolve_class_names() {
+        let content = r#"<?php
+            namespace App\Http;
+
+            use App\Models\User;
+            use App\Models as Models;
+            use Vendor\Package\{Client, Response as HttpResponse};
+
+            class Controller {}
+        "#;
+
+        let interner = ThreadedInterner::new();
+        let (program, _) = parse(
+            &interner,
+            Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+        );
+        let context = get_name_context(&program, content, content.find("class").unwrap());
+
+        assert_eq!(context.namespace, "App\\Http");
+        assert_eq!(context.resolve_class("User"), "App\\Models\\User");
+        assert_eq!(context.resolve_class("user"), "App\\Models\\User");
+        assert_eq!(context.resolve_class("Models\\Post"), "App\\Models\\Post");
+        assert_eq!(
+            context.resolve_class("HttpResponse"),
+            "Vendor\\Package\\Response"
+        );
+        assert_eq!(context.resolve_class("Client"), "Vendor\\Package\\Client");
+        assert_eq!(context.resolve_class("Request"), "App\\Http\\Request");
+        assert_eq!(context.resolve_class("\\DateTime"), "DateTime");
+        assert_eq!(context.resolve_class("Static"), "static");
+        assert_eq!(
+            context.resolve_doc_type("array<int, User>|Client[]|null", &[]),
+            "array<int, App\\Models\\User>|Vendor\\Package\\Client[]|null"
+        );
+        assert_eq!(
+            context.resolve_doc_type("array{id: int, user?: User}|'draft'|non-empty-string", &[]),
+            "array{id: int, user?: App\\Models\\User}|'draft'|non-empty-string"
+        );
+        assert_eq!(
+            context.resolve_doc_type(
+                "Collection<TKey, User>",
+                &[Template {
+                    name: String::from("TKey"),
+                    bound: None,
+                }]
+            ),
+            "App\\Http\\Collection<TKey, App\\Models\\User>"
+        );
+    }
+
+    #[test]
+    fn resolve_function_and_constant_names() {
+        let content = r#"<?php
+            namespace App;
+
+            use function Vendor\Helpers\format_date;
+            use function Vendor\Helpers\{slugify as slug};
+            use const Vendor\Config\DEBUG;
+            use Vendor\Support;
+
+            function marker() {}
+        "#;
+
+        let interner = ThreadedInterner::new();
+        let (program, _) = parse(
+            &interner,
+            Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+        );
+        let context = get_name_context(&program, content, content.find("marker").unwrap());
+
+        assert_eq!(
+            context.resolve_function("Format_Date"),
+            ["Vendor\\Helpers\\format_date"]
+        );
+        assert_eq!(
+            context.resolve_function("slug"),
+            ["Vendor\\Helpers\\slugify"]
+        );
+        assert_eq!(
+            context.resolve_function("strlen"),
+            ["App\\strlen", "strlen"]
+        );
+        assert_eq!(context.resolve_function("\\strlen"), ["strlen"]);
+        assert_eq!(
+            context.resolve_function("Support\\helper"),
+            ["Vendor\\Support\\helper"]
+        );
+        assert_eq!(context.resolve_constant("DEBUG"), ["Vendor\\Config\\DEBUG"]);
+        assert_eq!(context.resolve_constant("debug"), ["App\\debug", "debug"]);
+    }
+
+    #[test]
+    fn name_context_of_braced_namespaces() {
+        let content = r#"<?php
+            namespace First {
+                use Other\Thing;
+            }
+
+            namespace Second {
+                function marker() {}
+            }
+        "#;
+
+        let interner = ThreadedInterner::new();
+        let (program, _) = parse(
+            &interner,
+            Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+        );
+        let context = get_name_context(&program, content, content.find("marker").unwrap());
+
+        assert_eq!(context.namespace, "Second");
+        assert_eq!(context.resolve_class("Thing"), "Second\\Thing");
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use dashmap::DashMap;
+
+/// The namespaces of the known classes as a tree of their segments, e.g. `App`
+/// holding `Http` holding the class `App\Http\Kernel`. Segments are looked up
+/// case insensitively like PHP does.
+#[derive(Debug, Default)]
+pub struct NamespaceTrie {
+    pub name: String,
+    children: BTreeMap<String, NamespaceTrie>,
+    classes: Vec<String>,
+}
+
+impl NamespaceTrie {
+    pub fn from_class_map(class_map: &DashMap<String, String>) -> Self {
+        let mut trie = NamespaceTrie::default();
+        for entry in class_map.iter() {
+            trie.insert(entry.key());
+        }
+
+        trie
+    }
+
+    pub fn insert(&mut self, fqn: &str) {
+        let fqn = fqn.trim_start_matches('\\');
+        let mut node = self;
+        let mut segments = fqn.split('\\').peekable();
+        while let Some(segment) = segments.next() {
+            if segments.peek().is_none() {
+                if !node.classes.iter().any(|class| class == fqn) {
+                    node.classes.push(fqn.to_string());
+                }
+                return;
+            }
+
+            node = node
+                .children
+                .entry(segment.to_ascii_lowercase())
+                .or_insert_with(|| NamespaceTrie {
+                    name: segment.to_string(),
+                    ..Default::default()
+                });
+        }
+    }
+
+    /// Removes a class, dropping the namespaces left without any class.
+    pub fn remove(&mut self, fqn: &str) {
+        let fqn = fqn.trim_start_matches('\\');
+        let segments: Vec<&str> = fqn.split('\\').collect();
+        self.remove_from(fqn, &segments[..segments.len() - 1]);
+    }
+
+    fn remove_from(&mut self, fqn: &str, namespace: &[&str]) {
+        let Some((segment, rest)) = namespace.split_first() else {
+            self.classes.retain(|class| class != fqn);
+            return;
+        };
+
+        let key = segment.to_ascii_lowercase();
+        if let Some(child) = self.children.get_mut(&key) {
+            child.remove_from(fqn, rest);
+            if child.children.is_empty() && child.classes.is_empty() {
+                self.children.remove(&key);
+            }
+        }
+    }
+
+    /// Returns the node of a namespace, the root for the global one.
+    pub fn find(&self, namespace: &str) -> Option<&NamespaceTrie> {
+        namespace
+            .trim_matches('\\')
+            .split('\\')
+            .filter(|segment| !segment.is_empty())
+            .try_fold(self, |node, segment| {
+                node.children.get(&segment.to_ascii_lowercase())
+            })
+    }
+
+    /// Returns the namespaces directly below this one.
+    pub fn namespaces(&self) -> impl Iterator<Item = &NamespaceTrie> {
+        self.children.values()
+    }
+
+    /// Returns the fully qualified names of the classes in this namespace.
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NamespaceTrie;
+
+    #[test]
+    fn finds_namespaces_and_classes() {
+        let mut trie = NamespaceTrie::default();
+        for fqn in [
+            "App\\Http\\Kernel",
+            "App\\Http\\Controllers\\UserController",
+            "\\App\\Models\\User",
+            "Closure",
+        ] {
+            trie.insert(fqn);
+        }
+
+        let names = |node: &NamespaceTrie| {
+            node.namespaces()
+                .map(|namespace| namespace.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(&trie), vec!["App"]);
+        assert_eq!(trie.classes(), ["Closure"]);
+
+        let app = trie.find("\\app\\").unwrap();
+        assert_eq!(names(app), vec!["Http", "Models"]);
+
+        let http = trie.find("App\\Http").unwrap();
+        assert_eq!(names(http), vec!["Controllers"]);
+        assert_eq!(http.classes(), ["App\\Http\\Kernel"]);
+
+        assert!(trie.find("App\\Console").is_none());
+
+        trie.insert("App\\Http\\Kernel");
+        assert_eq!(
+            trie.find("App\\Http").unwrap().classes(),
+            ["App\\Http\\Kernel"]
+        );
+    }
+
+    #[test]
+    fn removes_empty_namespaces() {
+        let mut trie = NamespaceTrie::default();
+        trie.insert("App\\Http\\Kernel");
+        trie.insert("App\\Models\\User");
+
+        trie.remove("\\App\\Http\\Kernel");
+        assert!(trie.find("App\\Http").is_none());
+        assert_eq!(
+            trie.find("App\\Models").unwrap().classes(),
+            ["App\\Models\\User"]
+        );
+
+        trie.remove("App\\Models\\User");
+        assert!(trie.find("App").is_none());
+    }
+}
@@ -0,0 +1,378 @@
+use mago_ast::{
+    Argument, BinaryOperator, Call, Construct, Expression, If, IfBody, Literal, Node, Statement,
+    UnaryPrefixOperator, Variable,
+};
+use mago_span::{HasSpan, Span};
+
+use crate::lsp::state::State;
+
+use super::{
+    inference::{infer_expression_type, Scope},
+    tree::get_node_name,
+    types::{get_classes, union, Type},
+};
+
+/// Narrows the type of a variable down to what the conditions guarding the
+/// offset allow, e.g. `$foo` is a `Foo` inside `if ($foo instanceof Foo)` and
+/// after `if ($foo === null) { return; }` it is no longer null. Only conditions
+/// written after the definition of the variable count.
+pub fn narrow_variable_type(
+    types: Vec<Type>,
+    name: &str,
+    definition: usize,
+    offset: usize,
+    scope: &Scope,
+    state: &State,
+) -> Vec<Type> {
+    let narrowing = Narrowing {
+        name,
+        offset,
+        scope,
+        state,
+    };
+    let mut types = types;
+
+    for node in scope.nodes.iter() {
+        types = match node {
+            Node::Block(block) => block
+                .statements
+                .iter()
+                .filter(|statement| {
+                    statement.span().start.offset > definition
+                        && statement.span().end.offset <= offset
+                })
+                .fold(types, |types, statement| match get_guard(statement) {
+                    Some(condition) => narrowing.apply(types, condition, false),
+                    None => types,
+                }),
+            Node::If(r#if) if is_after(r#if.span(), &r#if.condition, definition) => {
+                narrowing.narrow_in_if(types, r#if)
+            }
+            Node::While(r#while) if is_after(r#while.span(), &r#while.condition, definition) => {
+                match offset > r#while.condition.span().end.offset {
+                    true => narrowing.apply(types, &r#while.condition, true),
+                    false => types,
+                }
+            }
+            Node::Conditional(conditional)
+                if is_after(conditional.span(), &conditional.condition, definition) =>
+            {
+                match &conditional.then {
+                    Some(then) if contains(then.span(), offset) => {
+                        narrowing.apply(types, &conditional.condition, true)
+                    }
+                    _ if contains(conditional.r#else.span(), offset) => {
+                        narrowing.apply(types, &conditional.condition, false)
+                    }
+                    _ => types,
+                }
+            }
+            Node::Binary(binary)
+                if is_after(binary.span(), &binary.lhs, definition)
+                    && contains(binary.rhs.span(), offset) =>
+            {
+                match binary.operator {
+                    BinaryOperator::And(_) | BinaryOperator::LowAnd(_) => {
+                        narrowing.apply(types, &binary.lhs, true)
+                    }
+                    BinaryOperator::Or(_) | BinaryOperator::LowOr(_) => {
+                        narrowing.apply(types, &binary.lhs, false)
+                    }
+                    _ => types,
+                }
+            }
+            _ => types,
+        };
+    }
+
+    types
+}
+
+struct Narrowing<'a, 'b, 'c> {
+    name: &'c str,
+    offset: usize,
+    scope: &'c Scope<'a, 'b>,
+    state: &'c State,
+}
+
+impl Narrowing<'_, '_, '_> {
+    /// Applies the conditions of the branch the offset is in together with the
+    /// negated conditions of the branches before it.
+    fn narrow_in_if(&self, types: Vec<Type>, r#if: &If) -> Vec<Type> {
+        let mut branches: Vec<(Option<&Expression>, (usize, usize))> = vec![];
+        match &r#if.body {
+            IfBody::Statement(body) => {
+                branches.push((Some(&r#if.condition), range(body.statement.span())));
+                for clause in body.else_if_clauses.iter() {
+                    branches.push((Some(&clause.condition), range(clause.statement.span())));
+                }
+                if let Some(clause) = &body.else_clause {
+                    branches.push((None, range(clause.statement.span())));
+                }
+            }
+            IfBody::ColonDelimited(body) => {
+                let mut starts = body
+                    .else_if_clauses
+                    .iter()
+                    .map(|clause| clause.span().start.offset)
+                    .chain(
+                        body.else_clause
+                            .iter()
+                            .map(|clause| clause.span().start.offset),
+                    )
+                    .chain(std::iter::once(body.endif.span().start.offset));
+
+                let then_end = starts.next().unwrap_or(body.endif.span().start.offset);
+                branches.push((Some(&r#if.condition), (body.colon.end.offset, then_end)));
+                for clause in body.else_if_clauses.iter() {
+                    branches.push((Some(&clause.condition), range(clause.span())));
+                }
+                if let Some(clause) = &body.else_clause {
+                    branches.push((None, range(clause.span())));
+                }
+            }
+        }
+
+        let mut types = types;
+        for (condition, (start, end)) in branches {
+            let in_condition =
+                condition.is_some_and(|condition| contains(condition.span(), self.offset));
+            if in_condition {
+                return types;
+            }
+            if start <= self.offset && self.offset <= end {
+                return match condition {
+                    Some(condition) => self.apply(types, condition, true),
+                    None => types,
+                };
+            }
+
+            if let Some(condition) = condition {
+                types = self.apply(types, condition, false);
+            }
+        }
+
+        types
+    }
+
+    /// Returns the types the variable can have once the condition turned out
+    /// to be truthy or falsy.
+    fn apply(&self, types: Vec<Type>, condition: &Expression, truthy: bool) -> Vec<Type> {
+        match condition {
+            Expression::Parenthesized(parenthesized) => {
+                self.apply(types, &parenthesized.expression, truthy)
+            }
+            Expression::UnaryPrefix(unary)
+                if matches!(unary.operator, UnaryPrefixOperator::Not(_)) =>
+            {
+                self.apply(types, &unary.operand, !truthy)
+            }
+            Expression::Binary(binary) => match binary.operator {
+                BinaryOperator::And(_) | BinaryOperator::LowAnd(_) if truthy => {
+                    let types = self.apply(types, &binary.lhs, true);
+                    self.apply(types, &binary.rhs, true)
+                }
+                BinaryOperator::Or(_) | BinaryOperator::LowOr(_) if !truthy => {
+                    let types = self.apply(types, &binary.lhs, false);
+                    self.apply(types, &binary.rhs, false)
+                }
+                // either side may have decided the outcome
+                BinaryOperator::And(_)
+                | BinaryOperator::LowAnd(_)
+                | BinaryOperator::Or(_)
+                | BinaryOperator::LowOr(_) => union(
+                    self.apply(types.clone(), &binary.lhs, truthy),
+                    self.apply(types, &binary.rhs, truthy),
+                ),
+                BinaryOperator::Instanceof(_) if self.is_variable(&binary.lhs) => {
+                    let classes =
+                        get_classes(&infer_expression_type(&binary.rhs, self.scope, self.state));
+
+                    match truthy {
+                        // known template arguments are kept
+                        true => classes
+                            .into_iter()
+                            .map(|class| {
+                                types
+                                    .iter()
+                                    .find(|r#type| r#type.class() == Some(&class))
+                                    .cloned()
+                                    .unwrap_or(Type::Class(class))
+                            })
+                            .collect(),
+                        false => types
+                            .into_iter()
+                            .filter(|r#type| {
+                                !r#type
+                                    .class()
+                                    .is_some_and(|class| classes.iter().any(|known| known == class))
+                            })
+                            .collect(),
+                    }
+                }
+                BinaryOperator::Identical(_)
+                | BinaryOperator::Equal(_)
+                | BinaryOperator::NotIdentical(_)
+                | BinaryOperator::NotEqual(_)
+                | BinaryOperator::AngledNotEqual(_) => {
+                    let is_null_check = (self.is_variable(&binary.lhs) && is_null(&binary.rhs))
+                        || (is_null(&binary.lhs) && self.is_variable(&binary.rhs));
+                    if !is_null_check {
+                        return types;
+                    }
+
+                    let is_equal = matches!(
+                        binary.operator,
+                        BinaryOperator::Identical(_) | BinaryOperator::Equal(_)
+                    );
+                    match truthy == is_equal {
+                        true => vec![Type::builtin("null")],
+                        false => without(types, &["null"]),
+                    }
+                }
+                _ => types,
+            },
+            Expression::Call(Call::Function(call)) => {
+                let Expression::Identifier(function) = call.function.as_ref() else {
+                    return types;
+                };
+                let function = get_node_name(self.scope.document, function);
+                let Some(checked) = get_checked_types(function.trim_start_matches('\\')) else {
+                    return types;
+                };
+                if !self.is_argument(call.argument_list.arguments.first()) {
+                    return types;
+                }
+
+                match truthy {
+                    // objects keep the classes we know about
+                    true if checked == ["object"] && !get_classes(&types).is_empty() => types
+                        .into_iter()
+                        .filter(|r#type| r#type.class().is_some())
+                        .collect(),
+                    // `array<int, User>` stays what it is after `is_array()`
+                    true => checked
+                        .iter()
+                        .map(|checked| {
+                            types
+                                .iter()
+                                .find(|r#type| r#type.is_builtin(checked))
+                                .cloned()
+                                .unwrap_or_else(|| Type::builtin(checked))
+                        })
+                        .collect(),
+                    false => without(types, checked),
+                }
+            }
+            Expression::Construct(Construct::Isset(isset))
+                if truthy && isset.values.iter().any(|value| self.is_variable(value)) =>
+            {
+                without(types, &["null"])
+            }
+            Expression::Variable(_) if truthy && self.is_variable(condition) => {
+                without(types, &["null", "false"])
+            }
+            // `if ($foo = find())`
+            Expression::Assignment(assignment) if truthy && self.is_variable(&assignment.lhs) => {
+                without(types, &["null", "false"])
+            }
+            _ => types,
+        }
+    }
+
+    fn is_variable(&self, expression: &Expression) -> bool {
+        matches!(
+            expression,
+            Expression::Variable(Variable::Direct(variable))
+                if get_node_name(self.scope.document, variable) == self.name
+        )
+    }
+
+    fn is_argument(&self, argument: Option<&Argument>) -> bool {
+        match argument {
+            Some(Argument::Positional(argument)) => self.is_variable(&argument.value),
+            _ => false,
+        }
+    }
+}
+
+/// Returns the types a `is_*` function checks for.
+fn get_checked_types(function: &str) -> Option<&'static [&'static str]> {
+    let types: &[&str] = match function.to_ascii_lowercase().as_str() {
+        "is_string" => &["string"],
+        "is_int" | "is_integer" | "is_long" => &["int"],
+        "is_float" | "is_double" => &["float"],
+        "is_numeric" => &["int", "float", "string"],
+        "is_bool" => &["bool"],
+        "is_array" => &["array"],
+        "is_null" => &["null"],
+        "is_object" => &["object"],
+        "is_callable" => &["callable"],
+        "is_iterable" => &["iterable"],
+        "is_resource" => &["resource"],
+        "is_scalar" => &["int", "float", "string", "bool"],
+        _ => return None,
+    };
+
+    Some(types)
+}
+
+/// Returns the condition of an `if` that leaves the current block when it is
+/// met, e.g. `if (!$foo instanceof Foo) { return; }`.
+fn get_guard(statement: &Statement) -> Option<&Expression> {
+    let Statement::If(r#if) = statement else {
+        return None;
+    };
+
+    let exits = match &r#if.body {
+        IfBody::Statement(body) => {
+            body.else_if_clauses.is_empty()
+                && body.else_clause.is_none()
+                && is_exit(&body.statement)
+        }
+        IfBody::ColonDelimited(body) => {
+            body.else_if_clauses.is_empty()
+                && body.else_clause.is_none()
+                && body.statements.iter().last().is_some_and(is_exit)
+        }
+    };
+
+    exits.then_some(&r#if.condition)
+}
+
+fn is_exit(statement: &Statement) -> bool {
+    match statement {
+        Statement::Return(_) | Statement::Continue(_) | Statement::Break(_) => true,
+        Statement::Expression(statement) => matches!(
+            statement.expression.as_ref(),
+            Expression::Throw(_) | Expression::Construct(Construct::Exit(_) | Construct::Die(_))
+        ),
+        Statement::Block(block) => block.statements.iter().last().is_some_and(is_exit),
+        _ => false,
+    }
+}
+
+/// Whether a condition was written after the definition of the variable or
+/// defines the variable itself, like `if ($foo = find())`.
+fn is_after(node: Span, condition: &Expression, definition: usize) -> bool {
+    node.start.offset > definition || contains(condition.span(), definition)
+}
+
+fn is_null(expression: &Expression) -> bool {
+    matches!(expression, Expression::Literal(Literal::Null(_)))
+}
+
+fn without(types: Vec<Type>, builtins: &[&str]) -> Vec<Type> {
+    types
+        .into_iter()
+        .filter(|r#type| !builtins.iter().any(|builtin| r#type.is_builtin(builtin)))
+        .collect()
+}
+
+fn contains(span: Span, offset: usize) -> bool {
+    span.start.offset <= offset && offset <= span.end.offset
+}
+
+fn range(span: Span) -> (usize, usize) {
+    (span.start.offset, span.end.offset)
+}
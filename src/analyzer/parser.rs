@@ -16,4 +16,10 @@ impl Parser {
     pub fn parse(&mut self, text: impl AsRef<[u8]>) -> Option<Tree> {
         self.inner.parse(text, None)
     }
+
+    /// Parses the text reusing the unchanged parts of a tree that has already
+    /// been adjusted with `Tree::edit`.
+    pub fn reparse(&mut self, text: impl AsRef<[u8]>, old_tree: &Tree) -> Option<Tree> {
+        self.inner.parse(text, Some(old_tree))
+    }
 }
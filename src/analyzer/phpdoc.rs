@@ -0,0 +1,687 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::docblock::{split_type, DocBlock};
+
+/// A type written in a docblock, as understood by PHPStan and Psalm.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocType {
+    /// A builtin, pseudo or class type with its template arguments, e.g. `int`,
+    /// `list<User>` or `Collection<int, User>`.
+    Named {
+        name: String,
+        arguments: Vec<DocType>,
+    },
+    /// `User[]`
+    Array(Box<DocType>),
+    /// `array{id: int, name?: string}`, `list{int, string}` or `object{id: int}`.
+    Shape {
+        name: String,
+        fields: Vec<ShapeField>,
+    },
+    /// `callable(int, string): bool` or `Closure(User): void`.
+    Callable {
+        name: String,
+        parameters: Vec<DocType>,
+        return_type: Option<Box<DocType>>,
+    },
+    Nullable(Box<DocType>),
+    Union(Vec<DocType>),
+    Intersection(Vec<DocType>),
+    /// `($id is int ? User : null)`
+    Conditional {
+        subject: String,
+        target: Box<DocType>,
+        then: Box<DocType>,
+        r#else: Box<DocType>,
+    },
+    /// `'draft'`, `42` or `User::ROLE_*`
+    Literal(String),
+}
+
+/// A field of an array shape. Fields of list shapes have no key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeField {
+    pub key: Option<String>,
+    pub is_optional: bool,
+    pub value: DocType,
+}
+
+impl DocType {
+    /// Replaces the names of the named and callable types, e.g. to qualify the
+    /// class names of a type.
+    pub fn map_names(self, map: &impl Fn(&str) -> String) -> DocType {
+        let map_all = |types: Vec<DocType>| -> Vec<DocType> {
+            types
+                .into_iter()
+                .map(|r#type| r#type.map_names(map))
+                .collect()
+        };
+
+        match self {
+            DocType::Named { name, arguments } => DocType::Named {
+                name: map(&name),
+                arguments: map_all(arguments),
+            },
+            DocType::Array(inner) => DocType::Array(Box::new(inner.map_names(map))),
+            DocType::Shape { name, fields } => DocType::Shape {
+                name,
+                fields: fields
+                    .into_iter()
+                    .map(|field| ShapeField {
+                        value: field.value.map_names(map),
+                        ..field
+                    })
+                    .collect(),
+            },
+            DocType::Callable {
+                name,
+                parameters,
+                return_type,
+            } => DocType::Callable {
+                name: map(&name),
+                parameters: map_all(parameters),
+                return_type: return_type.map(|r#type| Box::new(r#type.map_names(map))),
+            },
+            DocType::Nullable(inner) => DocType::Nullable(Box::new(inner.map_names(map))),
+            DocType::Union(types) => DocType::Union(map_all(types)),
+            DocType::Intersection(types) => DocType::Intersection(map_all(types)),
+            DocType::Conditional {
+                subject,
+                target,
+                then,
+                r#else,
+            } => DocType::Conditional {
+                subject,
+                target: Box::new(target.map_names(map)),
+                then: Box::new(then.map_names(map)),
+                r#else: Box::new(r#else.map_names(map)),
+            },
+            DocType::Literal(literal) => DocType::Literal(literal),
+        }
+    }
+
+    fn is_compound(&self) -> bool {
+        matches!(
+            self,
+            DocType::Union(_)
+                | DocType::Intersection(_)
+                | DocType::Nullable(_)
+                | DocType::Callable { .. }
+        )
+    }
+}
+
+impl Display for DocType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DocType::Named { name, arguments } if arguments.is_empty() => write!(f, "{}", name),
+            DocType::Named { name, arguments } => write!(f, "{}<{}>", name, join(arguments, ", ")),
+            DocType::Array(inner) if inner.is_compound() => write!(f, "({})[]", inner),
+            DocType::Array(inner) => write!(f, "{}[]", inner),
+            DocType::Shape { name, fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|field| match &field.key {
+                        Some(key) => format!(
+                            "{}{}: {}",
+                            key,
+                            if field.is_optional { "?" } else { "" },
+                            field.value
+                        ),
+                        None => field.value.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "{}{{{}}}", name, fields.join(", "))
+            }
+            DocType::Callable {
+                name,
+                parameters,
+                return_type,
+            } => {
+                write!(f, "{}({})", name, join(parameters, ", "))?;
+                match return_type {
+                    Some(return_type) if return_type.is_compound() => {
+                        write!(f, ": ({})", return_type)
+                    }
+                    Some(return_type) => write!(f, ": {}", return_type),
+                    None => Ok(()),
+                }
+            }
+            DocType::Nullable(inner) if inner.is_compound() => write!(f, "?({})", inner),
+            DocType::Nullable(inner) => write!(f, "?{}", inner),
+            DocType::Union(types) => write!(f, "{}", join(types, "|")),
+            DocType::Intersection(types) => {
+                let types = types
+                    .iter()
+                    .map(|r#type| match r#type.is_compound() {
+                        true => format!("({})", r#type),
+                        false => r#type.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "{}", types.join("&"))
+            }
+            DocType::Conditional {
+                subject,
+                target,
+                then,
+                r#else,
+            } => write!(f, "({} is {} ? {} : {})", subject, target, then, r#else),
+            DocType::Literal(literal) => write!(f, "{}", literal),
+        }
+    }
+}
+
+fn join(types: &[DocType], separator: &str) -> String {
+    types
+        .iter()
+        .map(|r#type| r#type.to_string())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// A template parameter declared with `@template T of Bound`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    pub bound: Option<String>,
+}
+
+/// Returns the template parameters a docblock declares, including covariant
+/// and contravariant ones.
+pub fn get_templates(docblock: &DocBlock) -> Vec<Template> {
+    let mut templates: Vec<Template> = vec![];
+
+    for name in ["template", "template-covariant", "template-contravariant"] {
+        for tag in docblock.get_type_tags(name) {
+            let mut words = tag.body.split_whitespace();
+            let Some(template) = words.next() else {
+                continue;
+            };
+            if templates.iter().any(|existing| existing.name == template) {
+                continue;
+            }
+
+            let bound = match words.next() {
+                Some("of" | "as") => {
+                    let rest = tag.body[template.len()..].trim_start()[2..].trim_start();
+                    Some(split_type(rest).0.to_string()).filter(|bound| !bound.is_empty())
+                }
+                _ => None,
+            };
+            templates.push(Template {
+                name: template.to_string(),
+                bound,
+            });
+        }
+    }
+
+    templates
+}
+
+/// Returns the supertypes a docblock passes template arguments to with
+/// `@extends`, `@implements` and `@use`, e.g. `Collection<int, User>`.
+pub fn get_generic_supertypes(docblock: &DocBlock) -> Vec<String> {
+    let mut supertypes = vec![];
+
+    for name in [
+        "extends",
+        "implements",
+        "use",
+        "template-extends",
+        "template-implements",
+        "template-use",
+    ] {
+        for tag in docblock.get_type_tags(name) {
+            let (type_name, _) = split_type(&tag.body);
+            if type_name.contains('<') && !supertypes.iter().any(|known| known == type_name) {
+                supertypes.push(type_name.to_string());
+            }
+        }
+    }
+
+    supertypes
+}
+
+/// Parses a type written in a docblock. Returns `None` when the text is not a
+/// type as a whole.
+pub fn parse_doc_type(text: &str) -> Option<DocType> {
+    let mut parser = TypeParser {
+        tokens: tokenize(text)?,
+        position: 0,
+    };
+
+    let r#type = parser.parse_union()?;
+    match parser.position == parser.tokens.len() {
+        true => Some(r#type),
+        false => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Names, variables, numbers and string literals.
+    Word(String),
+    Punctuation(char),
+    Ellipsis,
+    DoubleColon,
+}
+
+fn tokenize(text: &str) -> Option<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut rest = text.trim();
+
+    while let Some(char) = rest.chars().next() {
+        if char.is_whitespace() {
+            rest = rest.trim_start();
+            continue;
+        }
+
+        let length = if char == '\'' || char == '"' {
+            let end = rest[1..].find(char)? + 2;
+            tokens.push(Token::Word(rest[..end].to_string()));
+            end
+        } else if rest.starts_with("...") {
+            tokens.push(Token::Ellipsis);
+            3
+        } else if rest.starts_with("::") {
+            tokens.push(Token::DoubleColon);
+            2
+        } else if is_word_char(char) || char == '$' || char == '*' {
+            let end = rest
+                .char_indices()
+                .skip(1)
+                .find(|(_, char)| !is_word_char(*char) && *char != '*')
+                .map_or(rest.len(), |(index, _)| index);
+            // `-1` is a number, but the dash of `non-empty-string` is part of the name
+            tokens.push(Token::Word(rest[..end].to_string()));
+            end
+        } else if "<>{}()[],:?|&=".contains(char) {
+            tokens.push(Token::Punctuation(char));
+            1
+        } else {
+            return None;
+        };
+        rest = &rest[length..];
+    }
+
+    Some(tokens)
+}
+
+fn is_word_char(char: char) -> bool {
+    char.is_alphanumeric() || matches!(char, '_' | '\\' | '-' | '.')
+}
+
+struct TypeParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl TypeParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, punctuation: char) -> bool {
+        match self.peek() == Some(&Token::Punctuation(punctuation)) {
+            true => {
+                self.position += 1;
+                true
+            }
+            false => false,
+        }
+    }
+
+    fn expect(&mut self, punctuation: char) -> Option<()> {
+        self.eat(punctuation).then_some(())
+    }
+
+    fn parse_union(&mut self) -> Option<DocType> {
+        let mut types = vec![self.parse_intersection()?];
+        while self.eat('|') {
+            types.push(self.parse_intersection()?);
+        }
+
+        Some(match types.len() {
+            1 => types.remove(0),
+            _ => DocType::Union(types),
+        })
+    }
+
+    fn parse_intersection(&mut self) -> Option<DocType> {
+        let mut types = vec![self.parse_postfix()?];
+        // `&` in front of a variable marks a callable parameter passed by reference
+        while self.peek() == Some(&Token::Punctuation('&'))
+            && !matches!(self.tokens.get(self.position + 1), Some(Token::Word(word)) if word.starts_with('$'))
+            && !matches!(
+                self.tokens.get(self.position + 1),
+                Some(Token::Ellipsis | Token::Punctuation(',' | ')'))
+            )
+        {
+            self.position += 1;
+            types.push(self.parse_postfix()?);
+        }
+
+        Some(match types.len() {
+            1 => types.remove(0),
+            _ => DocType::Intersection(types),
+        })
+    }
+
+    fn parse_postfix(&mut self) -> Option<DocType> {
+        if self.eat('?') {
+            return Some(DocType::Nullable(Box::new(self.parse_postfix()?)));
+        }
+
+        let mut r#type = self.parse_atom()?;
+        while self.peek() == Some(&Token::Punctuation('['))
+            && self.tokens.get(self.position + 1) == Some(&Token::Punctuation(']'))
+        {
+            self.position += 2;
+            r#type = DocType::Array(Box::new(r#type));
+        }
+
+        Some(r#type)
+    }
+
+    fn parse_atom(&mut self) -> Option<DocType> {
+        if self.eat('(') {
+            let r#type = self.parse_conditional()?;
+            self.expect(')')?;
+            return Some(r#type);
+        }
+
+        let Some(Token::Word(word)) = self.next() else {
+            return None;
+        };
+        if word.starts_with(['\'', '"']) || word.starts_with(|char: char| char.is_ascii_digit()) {
+            return Some(DocType::Literal(word));
+        }
+        if word.starts_with('-') && word[1..].starts_with(|char: char| char.is_ascii_digit()) {
+            return Some(DocType::Literal(word));
+        }
+
+        match self.peek() {
+            // `User::ROLE_*`
+            Some(Token::DoubleColon) => {
+                self.position += 1;
+                let Some(Token::Word(constant)) = self.next() else {
+                    return None;
+                };
+                Some(DocType::Literal(format!("{}::{}", word, constant)))
+            }
+            Some(Token::Punctuation('<')) => {
+                self.position += 1;
+                let mut arguments = vec![self.parse_union()?];
+                while self.eat(',') {
+                    arguments.push(self.parse_union()?);
+                }
+                self.expect('>')?;
+
+                Some(DocType::Named {
+                    name: word,
+                    arguments,
+                })
+            }
+            Some(Token::Punctuation('{')) => {
+                self.position += 1;
+                let fields = self.parse_shape_fields()?;
+                Some(DocType::Shape { name: word, fields })
+            }
+            Some(Token::Punctuation('(')) if is_callable_name(&word) => {
+                self.position += 1;
+                let parameters = self.parse_callable_parameters()?;
+                let return_type = match self.eat(':') {
+                    true => Some(Box::new(self.parse_postfix()?)),
+                    false => None,
+                };
+
+                Some(DocType::Callable {
+                    name: word,
+                    parameters,
+                    return_type,
+                })
+            }
+            _ => Some(DocType::Named {
+                name: word,
+                arguments: vec![],
+            }),
+        }
+    }
+
+    /// Parses what comes inside parentheses: either a conditional return type
+    /// or a type that is grouped.
+    fn parse_conditional(&mut self) -> Option<DocType> {
+        let is_conditional = matches!(
+            (self.tokens.get(self.position), self.tokens.get(self.position + 1)),
+            (Some(Token::Word(_)), Some(Token::Word(is))) if is == "is"
+        );
+        if !is_conditional {
+            return self.parse_union();
+        }
+
+        let Some(Token::Word(subject)) = self.next() else {
+            return None;
+        };
+        self.position += 1;
+        let subject = match self.peek() {
+            Some(Token::Word(not)) if not == "not" => {
+                self.position += 1;
+                format!("{} not", subject)
+            }
+            _ => subject,
+        };
+        let target = self.parse_union()?;
+        self.expect('?')?;
+        let then = self.parse_union()?;
+        self.expect(':')?;
+        let r#else = self.parse_union()?;
+
+        Some(DocType::Conditional {
+            subject,
+            target: Box::new(target),
+            then: Box::new(then),
+            r#else: Box::new(r#else),
+        })
+    }
+
+    fn parse_shape_fields(&mut self) -> Option<Vec<ShapeField>> {
+        let mut fields = vec![];
+
+        loop {
+            if self.eat('}') {
+                return Some(fields);
+            }
+            // unsealed shapes like `array{id: int, ...}`
+            if self.peek() == Some(&Token::Ellipsis) {
+                self.position += 1;
+                self.eat(',');
+                continue;
+            }
+
+            let key = match (
+                self.tokens.get(self.position),
+                self.tokens.get(self.position + 1),
+                self.tokens.get(self.position + 2),
+            ) {
+                (Some(Token::Word(key)), Some(Token::Punctuation(':')), _) => {
+                    let key = key.clone();
+                    self.position += 2;
+                    Some((key, false))
+                }
+                (
+                    Some(Token::Word(key)),
+                    Some(Token::Punctuation('?')),
+                    Some(Token::Punctuation(':')),
+                ) => {
+                    let key = key.clone();
+                    self.position += 3;
+                    Some((key, true))
+                }
+                _ => None,
+            };
+
+            fields.push(ShapeField {
+                key: key.as_ref().map(|(key, _)| key.clone()),
+                is_optional: key.is_some_and(|(_, is_optional)| is_optional),
+                value: self.parse_union()?,
+            });
+            if !self.eat(',') {
+                self.expect('}')?;
+                return Some(fields);
+            }
+        }
+    }
+
+    /// Parses the parameters of a callable type up to the closing parenthesis,
+    /// dropping their names and default values.
+    fn parse_callable_parameters(&mut self) -> Option<Vec<DocType>> {
+        let mut parameters = vec![];
+
+        loop {
+            if self.eat(')') {
+                return Some(parameters);
+            }
+
+            parameters.push(self.parse_union()?);
+            self.eat('&');
+            if self.peek() == Some(&Token::Ellipsis) {
+                self.position += 1;
+            }
+            if matches!(self.peek(), Some(Token::Word(word)) if word.starts_with('$')) {
+                self.position += 1;
+            }
+            self.eat('=');
+
+            if !self.eat(',') {
+                self.expect(')')?;
+                return Some(parameters);
+            }
+        }
+    }
+}
+
+fn is_callable_name(name: &str) -> bool {
+    let name = name.trim_start_matches('\\');
+    ["callable", "Closure", "pure-callable", "pure-Closure"]
+        .iter()
+        .any(|callable| callable.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::{
+        docblock::parse_docblock,
+        phpdoc::{
+            get_generic_supertypes, get_templates, parse_doc_type, DocType, ShapeField, Template,
+        },
+    };
+
+    fn named(name: &str, arguments: Vec<DocType>) -> DocType {
+        DocType::Named {
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    #[test]
+    fn parse_generic_and_array_types() {
+        assert_eq!(
+            parse_doc_type("Collection<int, User>|User[]|null"),
+            Some(DocType::Union(vec![
+                named(
+                    "Collection",
+                    vec![named("int", vec![]), named("User", vec![])]
+                ),
+                DocType::Array(Box::new(named("User", vec![]))),
+                named("null", vec![]),
+            ]))
+        );
+        assert_eq!(
+            parse_doc_type("list<Foo>"),
+            Some(named("list", vec![named("Foo", vec![])]))
+        );
+        assert_eq!(
+            parse_doc_type("array{id: int, name?: string, 0: 'draft'}"),
+            Some(DocType::Shape {
+                name: String::from("array"),
+                fields: vec![
+                    ShapeField {
+                        key: Some(String::from("id")),
+                        is_optional: false,
+                        value: named("int", vec![]),
+                    },
+                    ShapeField {
+                        key: Some(String::from("name")),
+                        is_optional: true,
+                        value: named("string", vec![]),
+                    },
+                    ShapeField {
+                        key: Some(String::from("0")),
+                        is_optional: false,
+                        value: DocType::Literal(String::from("'draft'")),
+                    },
+                ],
+            })
+        );
+        assert_eq!(parse_doc_type("array<int, "), None);
+    }
+
+    #[test]
+    fn render_parsed_types() {
+        for text in [
+            "int<0, max>|non-empty-string|-1",
+            "?(Foo&Bar)",
+            "(A|B)[]",
+            "callable(int, string): bool",
+            "Closure(User): (User|null)",
+            "($id is int ? User : null)",
+            "array{id: int, tags?: list<string>}",
+            "User::STATUS_*|class-string<T>",
+        ] {
+            assert_eq!(parse_doc_type(text).unwrap().to_string(), text);
+        }
+
+        assert_eq!(
+            parse_doc_type("callable(int &$count, string ...$rest=): void")
+                .unwrap()
+                .to_string(),
+            "callable(int, string): void"
+        );
+    }
+
+    #[test]
+    fn collect_templates_and_generic_supertypes() {
+        let docblock = parse_docblock(
+            "/**\n * @template TKey of array-key\n * @psalm-template-covariant TValue\n * @extends Collection<TKey, TValue>\n * @implements \\IteratorAggregate<TKey, TValue>\n * @implements Countable\n */",
+        );
+
+        assert_eq!(
+            get_templates(&docblock),
+            vec![
+                Template {
+                    name: String::from("TKey"),
+                    bound: Some(String::from("array-key")),
+                },
+                Template {
+                    name: String::from("TValue"),
+                    bound: None,
+                },
+            ]
+        );
+        assert_eq!(
+            get_generic_supertypes(&docblock),
+            vec![
+                "Collection<TKey, TValue>",
+                "\\IteratorAggregate<TKey, TValue>"
+            ]
+        );
+    }
+}
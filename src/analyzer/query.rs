@@ -33,6 +33,12 @@ pub fn variable_declaration_query() -> Result<Query, QueryError> {
 
         (simple_parameter
             (variable_name) @parameter_declaration)
+
+        (anonymous_function_use_clause
+            (variable_name) @closure_use_declaration)
+
+        (anonymous_function_use_clause
+            (by_ref (variable_name) @closure_use_declaration))
         ",
     )
 }
@@ -40,8 +46,19 @@ pub fn variable_declaration_query() -> Result<Query, QueryError> {
 pub fn namespace_use_query() -> Result<Query, QueryError> {
     Query::new(
         &LANGUAGE_PHP.into(),
-        "(namespace_use_clause
-            (qualified_name) @namespace)",
+        // `use function` and `use const` import functions and constants, not classes
+        "(namespace_use_declaration
+            !type
+            (namespace_use_clause
+                (qualified_name) @namespace))",
+    )
+}
+
+pub fn namespace_definition_query() -> Result<Query, QueryError> {
+    Query::new(
+        &LANGUAGE_PHP.into(),
+        "(namespace_definition
+            name: (namespace_name) @namespace)",
     )
 }
 
@@ -0,0 +1,515 @@
+use mago_ast::{Node, Program};
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use mago_span::{HasSpan, Span};
+use tower_lsp::lsp_types::{Location, Url};
+
+use crate::lsp::state::State;
+
+use super::{
+    indexer::{
+        find_constant_location, get_symbol_key, get_workspace_files, qualify_name, MemberKind,
+        SymbolKind,
+    },
+    inference::{get_current_class, resolve_class_name, resolve_expression_classes, Scope},
+    members::find_member,
+    names::{get_imports, get_name_context, ImportKind},
+    tree::{get_node_name, get_range},
+    types::is_builtin_type,
+    variables::find_variable_definition,
+};
+
+/// A symbol that can be referenced from somewhere else. Class-likes and members
+/// are identified by the fully qualified name of the class-like declaring them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReferenceTarget {
+    ClassLike(String),
+    Member {
+        class: String,
+        kind: MemberKind,
+        name: String,
+    },
+    Function(String),
+    Constant(String),
+    /// A local variable, identified by the offset of the scope it is defined in.
+    Variable {
+        scope: usize,
+        name: String,
+    },
+}
+
+impl ReferenceTarget {
+    /// Whether another target refers to the same symbol, following the case
+    /// sensitivity rules of PHP.
+    pub fn matches(&self, other: &ReferenceTarget) -> bool {
+        match (self, other) {
+            (ReferenceTarget::ClassLike(a), ReferenceTarget::ClassLike(b))
+            | (ReferenceTarget::Function(a), ReferenceTarget::Function(b)) => {
+                a.eq_ignore_ascii_case(b)
+            }
+            (ReferenceTarget::Constant(a), ReferenceTarget::Constant(b)) => a == b,
+            (
+                ReferenceTarget::Member {
+                    class: a_class,
+                    kind: a_kind,
+                    name: a_name,
+                },
+                ReferenceTarget::Member {
+                    class: b_class,
+                    kind: b_kind,
+                    name: b_name,
+                },
+            ) => {
+                a_class.eq_ignore_ascii_case(b_class)
+                    && a_kind == b_kind
+                    && match a_kind {
+                        MemberKind::Method => a_name.eq_ignore_ascii_case(b_name),
+                        _ => a_name == b_name,
+                    }
+            }
+            (
+                ReferenceTarget::Variable {
+                    scope: a_scope,
+                    name: a_name,
+                },
+                ReferenceTarget::Variable {
+                    scope: b_scope,
+                    name: b_name,
+                },
+            ) => a_scope == b_scope && a_name == b_name,
+            _ => false,
+        }
+    }
+
+    /// The name every reference to the target has to contain, used to skip
+    /// files that cannot reference it without parsing them.
+    fn get_short_name(&self) -> &str {
+        match self {
+            ReferenceTarget::ClassLike(fqn)
+            | ReferenceTarget::Function(fqn)
+            | ReferenceTarget::Constant(fqn) => fqn.rsplit('\\').next().unwrap_or(fqn),
+            ReferenceTarget::Member { name, .. } => name,
+            ReferenceTarget::Variable { name, .. } => name,
+        }
+    }
+
+    fn is_case_sensitive(&self) -> bool {
+        match self {
+            ReferenceTarget::Constant(_) | ReferenceTarget::Variable { .. } => true,
+            ReferenceTarget::Member { kind, .. } => *kind != MemberKind::Method,
+            _ => false,
+        }
+    }
+}
+
+/// A name in a document together with what it refers to.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub target: ReferenceTarget,
+    pub span: Span,
+    pub is_declaration: bool,
+}
+
+/// Works out what the innermost node of the scope refers to. The innermost
+/// node has to be a name: an identifier or a variable.
+pub fn get_reference(scope: &Scope, state: &State) -> Option<Reference> {
+    let (leaf, ancestors) = scope.nodes.split_last()?;
+
+    match leaf {
+        Node::DirectVariable(variable) => get_variable_reference(variable.span(), scope, state),
+        Node::LocalIdentifier(_)
+        | Node::QualifiedIdentifier(_)
+        | Node::FullyQualifiedIdentifier(_) => {
+            let span = leaf.span();
+            // identifiers are wrapped in an `Identifier` node in most places
+            let depth = ancestors
+                .iter()
+                .rposition(|node| !matches!(node, Node::Identifier(_)))?;
+
+            get_identifier_reference(span, depth, scope, state)
+        }
+        _ => None,
+    }
+}
+
+/// Finds every reference to the target. Local variables are only looked up in
+/// the document they live in, everything else in the open documents and the
+/// whole workspace apart from the dependencies. Reads and parses the files, so
+/// it should not run on the async runtime.
+pub fn find_references(
+    target: &ReferenceTarget,
+    include_declaration: bool,
+    current_uri: &Url,
+    state: &State,
+) -> Vec<Location> {
+    let files = match target {
+        ReferenceTarget::Variable { .. } => current_uri.to_file_path().into_iter().collect(),
+        _ => {
+            let mut files = get_workspace_files(state);
+            // open documents may be outside of the workspace or there may be no workspace
+            for uri in state
+                .document_map
+                .iter()
+                .map(|document| document.key().clone())
+            {
+                if let Ok(path) = uri.to_file_path() {
+                    if !files.contains(&path) {
+                        files.push(path);
+                    }
+                }
+            }
+
+            files
+        }
+    };
+
+    let short_name = match target.is_case_sensitive() {
+        true => target.get_short_name().to_string(),
+        false => target.get_short_name().to_ascii_lowercase(),
+    };
+
+    let mut locations = vec![];
+    for path in files {
+        let Ok(uri) = Url::from_file_path(&path) else {
+            continue;
+        };
+        // open documents may have changes that are not saved yet
+        let content = match state.document_map.get(&uri) {
+            Some(document) => document.clone(),
+            None => match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(_) => continue,
+            },
+        };
+
+        let contains_name = match target.is_case_sensitive() {
+            true => content.contains(&short_name),
+            false => content.to_ascii_lowercase().contains(&short_name),
+        };
+        if !contains_name {
+            continue;
+        }
+
+        let interner = ThreadedInterner::new();
+        let (program, _) = parse(
+            &interner,
+            Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+        );
+
+        let mut nodes = vec![];
+        collect_references(
+            Node::Program(&program),
+            &mut nodes,
+            &FileContext {
+                program: &program,
+                content: &content,
+                short_name: &short_name,
+                case_sensitive: target.is_case_sensitive(),
+            },
+            state,
+            &mut |reference| {
+                if reference.is_declaration && !include_declaration {
+                    return;
+                }
+                if reference.target.matches(target) {
+                    locations.push(Location::new(
+                        uri.clone(),
+                        get_range(reference.span, &content),
+                    ));
+                }
+            },
+        );
+    }
+
+    // constants declared with `define()` are not found by looking at names
+    if include_declaration {
+        if let ReferenceTarget::Constant(fqn) = target {
+            if let Some(location) = find_constant_location(fqn, state) {
+                if !locations.contains(&location) {
+                    locations.push(location);
+                }
+            }
+        }
+    }
+
+    locations
+}
+
+struct FileContext<'a> {
+    program: &'a Program,
+    content: &'a str,
+    short_name: &'a str,
+    case_sensitive: bool,
+}
+
+fn collect_references<'a>(
+    node: Node<'a>,
+    nodes: &mut Vec<Node<'a>>,
+    file: &FileContext,
+    state: &State,
+    found: &mut impl FnMut(Reference),
+) {
+    nodes.push(node);
+
+    let is_name = matches!(
+        node,
+        Node::DirectVariable(_)
+            | Node::LocalIdentifier(_)
+            | Node::QualifiedIdentifier(_)
+            | Node::FullyQualifiedIdentifier(_)
+    );
+    if is_name && ends_with_name(&get_node_name(file.content, node), file) {
+        let names = get_name_context(file.program, file.content, node.span().start.offset);
+        let scope = Scope {
+            document: file.content,
+            nodes,
+            names: &names,
+        };
+
+        if let Some(reference) = get_reference(&scope, state) {
+            found(reference);
+        }
+    }
+
+    for child in node.children() {
+        collect_references(child, nodes, file, state, found);
+    }
+
+    nodes.pop();
+}
+
+fn ends_with_name(name: &str, file: &FileContext) -> bool {
+    let name = name.rsplit('\\').next().unwrap_or(name);
+    let name = name.trim_start_matches('$');
+    let short_name = file.short_name.trim_start_matches('$');
+
+    match file.case_sensitive {
+        true => name == short_name,
+        false => name.eq_ignore_ascii_case(short_name),
+    }
+}
+
+fn get_variable_reference(span: Span, scope: &Scope, state: &State) -> Option<Reference> {
+    let nodes = scope.nodes;
+    let parent = nodes.get(nodes.len().checked_sub(2)?)?;
+    let name = get_node_name(scope.document, span);
+
+    match parent {
+        // `public $foo;`
+        Node::PropertyAbstractItem(_) | Node::PropertyConcreteItem(_) => {
+            get_member_declaration(&name, MemberKind::Property, span, scope)
+        }
+        // `public function __construct(private $foo)`
+        Node::FunctionLikeParameter(parameter) if parameter.is_promoted_property() => {
+            get_member_declaration(&name, MemberKind::Property, span, scope)
+        }
+        _ => {
+            // `Foo::$bar`
+            let grandparent = nodes.get(nodes.len().checked_sub(3)?);
+            if let Some(Node::StaticPropertyAccess(access)) = grandparent {
+                return get_member_reference(
+                    &access.class,
+                    &name,
+                    MemberKind::Property,
+                    span,
+                    scope,
+                    state,
+                );
+            }
+
+            let resolved = find_variable_definition(&name, span.start.offset, scope)?;
+            Some(Reference {
+                target: ReferenceTarget::Variable {
+                    scope: scope.nodes[resolved.depth].span().start.offset,
+                    name,
+                },
+                span,
+                is_declaration: resolved.definition.span().start.offset == span.start.offset,
+            })
+        }
+    }
+}
+
+fn get_identifier_reference(
+    span: Span,
+    depth: usize,
+    scope: &Scope,
+    state: &State,
+) -> Option<Reference> {
+    let name = get_node_name(scope.document, span);
+    let parent = scope.nodes[depth];
+    let reference = |target| {
+        Some(Reference {
+            target,
+            span,
+            is_declaration: false,
+        })
+    };
+    let declaration = |target| {
+        Some(Reference {
+            target,
+            span,
+            is_declaration: true,
+        })
+    };
+
+    match parent {
+        Node::Class(_) | Node::Interface(_) | Node::Trait(_) | Node::Enum(_) => declaration(
+            ReferenceTarget::ClassLike(qualify_name(&scope.names.namespace, &name)),
+        ),
+        Node::Function(_) => declaration(ReferenceTarget::Function(qualify_name(
+            &scope.names.namespace,
+            &name,
+        ))),
+        Node::ConstantItem(_) => declaration(ReferenceTarget::Constant(qualify_name(
+            &scope.names.namespace,
+            &name,
+        ))),
+        Node::Method(_) => get_member_declaration(&name, MemberKind::Method, span, scope),
+        Node::ClassLikeConstantItem(_) => {
+            get_member_declaration(&name, MemberKind::Constant, span, scope)
+        }
+        Node::EnumCaseUnitItem(_) | Node::EnumCaseBackedItem(_) => {
+            get_member_declaration(&name, MemberKind::EnumCase, span, scope)
+        }
+        Node::Extends(_)
+        | Node::Implements(_)
+        | Node::TraitUse(_)
+        | Node::Attribute(_)
+        | Node::Hint(_) => {
+            if is_builtin_type(&name) {
+                return None;
+            }
+            let class = resolve_class_name(&name, scope, state).into_iter().next()?;
+            reference(ReferenceTarget::ClassLike(class))
+        }
+        Node::ConstantAccess(_) => {
+            let fqn = resolve_global_name(&scope.names.resolve_constant(&name), |fqn| {
+                state.constant_map.contains_key(fqn)
+            });
+            reference(ReferenceTarget::Constant(fqn))
+        }
+        Node::UseItem(item) => {
+            let items = scope.nodes[..depth]
+                .iter()
+                .rev()
+                .find_map(|node| match node {
+                    Node::UseItems(items) => Some(*items),
+                    _ => None,
+                })?;
+            let (kind, fqn, _) = get_imports(items, scope.document)
+                .into_iter()
+                .find(|(_, _, candidate)| candidate.span() == item.span())?;
+
+            reference(match kind {
+                ImportKind::Class => ReferenceTarget::ClassLike(fqn),
+                ImportKind::Function => ReferenceTarget::Function(fqn),
+                ImportKind::Constant => ReferenceTarget::Constant(fqn),
+            })
+        }
+        Node::ClassLikeMemberSelector(_) => {
+            let (receiver, kind) = match scope.nodes.get(depth.checked_sub(1)?)? {
+                Node::MethodCall(call) => (&call.object, MemberKind::Method),
+                Node::NullSafeMethodCall(call) => (&call.object, MemberKind::Method),
+                Node::StaticMethodCall(call) => (&call.class, MemberKind::Method),
+                Node::MethodClosureCreation(creation) => (&creation.object, MemberKind::Method),
+                Node::StaticMethodClosureCreation(creation) => {
+                    (&creation.class, MemberKind::Method)
+                }
+                Node::PropertyAccess(access) => (&access.object, MemberKind::Property),
+                Node::NullSafePropertyAccess(access) => (&access.object, MemberKind::Property),
+                _ => return None,
+            };
+
+            get_member_reference(receiver, &name, kind, span, scope, state)
+        }
+        Node::ClassLikeConstantSelector(_) => match scope.nodes.get(depth.checked_sub(1)?)? {
+            Node::ClassConstantAccess(access) if !name.eq_ignore_ascii_case("class") => {
+                get_member_reference(
+                    &access.class,
+                    &name,
+                    MemberKind::Constant,
+                    span,
+                    scope,
+                    state,
+                )
+            }
+            _ => None,
+        },
+        Node::Expression(_) => match scope.nodes.get(depth.checked_sub(1)?)? {
+            Node::FunctionCall(_) | Node::FunctionClosureCreation(_) => {
+                let fqn = resolve_global_name(&scope.names.resolve_function(&name), |fqn| {
+                    state
+                        .function_map
+                        .contains_key(&get_symbol_key(SymbolKind::Function, fqn))
+                });
+                reference(ReferenceTarget::Function(fqn))
+            }
+            Node::Instantiation(_)
+            | Node::ClassConstantAccess(_)
+            | Node::StaticPropertyAccess(_)
+            | Node::StaticMethodCall(_)
+            | Node::StaticMethodClosureCreation(_)
+            | Node::Binary(_) => {
+                let class = resolve_class_name(&name, scope, state).into_iter().next()?;
+                reference(ReferenceTarget::ClassLike(class))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn get_member_declaration(
+    name: &str,
+    kind: MemberKind,
+    span: Span,
+    scope: &Scope,
+) -> Option<Reference> {
+    Some(Reference {
+        target: ReferenceTarget::Member {
+            class: get_current_class(scope)?,
+            kind,
+            name: name.trim_start_matches('$').to_string(),
+        },
+        span,
+        is_declaration: true,
+    })
+}
+
+fn get_member_reference(
+    receiver: &mago_ast::Expression,
+    name: &str,
+    kind: MemberKind,
+    span: Span,
+    scope: &Scope,
+    state: &State,
+) -> Option<Reference> {
+    let name = name.trim_start_matches('$');
+    let found = resolve_expression_classes(receiver, scope, state)
+        .iter()
+        .find_map(|class| find_member(class, name, kind, state))?;
+
+    Some(Reference {
+        target: ReferenceTarget::Member {
+            class: found.class.fqn,
+            kind: found.member.kind,
+            name: found.member.name,
+        },
+        span,
+        is_declaration: false,
+    })
+}
+
+/// Picks the candidate PHP would use at runtime: the first one that is
+/// declared, or the global one when none of them is known.
+fn resolve_global_name(candidates: &[String], is_declared: impl Fn(&str) -> bool) -> String {
+    candidates
+        .iter()
+        .find(|fqn| is_declared(fqn))
+        .or(candidates.last())
+        .cloned()
+        .unwrap_or_default()
+}
@@ -0,0 +1,362 @@
+use mago_ast::{ClassLikeMemberSelector, Expression, Node};
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use mago_span::HasSpan;
+use tower_lsp::lsp_types::{
+    Documentation, Location, MarkupContent, MarkupKind, ParameterInformation, ParameterLabel,
+    Position, SignatureHelp, SignatureInformation, Url,
+};
+
+use crate::lsp::state::State;
+
+use super::{
+    docblock::{find_docblock, DocBlock},
+    indexer::{find_function_symbol, MemberKind, Parameter},
+    inference::{infer_expression_type, Scope},
+    members::find_member,
+    names::get_name_context,
+    tree::{get_node_name, get_nodes_for_position},
+    types::{get_classes, shorten_class_names},
+    utils::{get_offset_from_position, get_position_from_offset},
+};
+
+/// The arguments of a call the cursor is in, as far as they are typed.
+struct OpenCall<'a> {
+    parenthesis: usize,
+    /// how many arguments come before the one at the cursor
+    index: usize,
+    /// the text of the argument at the cursor
+    argument: &'a str,
+}
+
+/// The declaration of whatever is being called.
+struct Callable {
+    name: String,
+    parameters: Vec<Parameter>,
+    return_type: Option<String>,
+    location: Location,
+}
+
+/// Describes the parameters of the function, method or constructor the
+/// arguments at the position are passed to, highlighting the parameter the
+/// argument at the position is for.
+pub fn get_signature_help(uri: &Url, position: &Position, state: &State) -> Option<SignatureHelp> {
+    let document = state.document_map.get(uri)?.clone();
+    let offset = get_offset_from_position(&document, position);
+    let call = find_open_call(&document[..offset])?;
+
+    // the call may not be closed yet while its arguments are typed, nor have
+    // a value for the argument just named
+    let callable = ["", ")", ");", "null)", "null);"]
+        .iter()
+        .find_map(|closing| {
+            let content = format!("{}{}{}", &document[..offset], closing, &document[offset..]);
+            resolve_callable(&content, call.parenthesis, state)
+        })?;
+    let docblock = find_declaration_docblock(&callable.location, state);
+
+    let mut label = format!("{}(", callable.name);
+    let mut parameters = vec![];
+    for (index, parameter) in callable.parameters.iter().enumerate() {
+        if index > 0 {
+            label.push_str(", ");
+        }
+        let start = label.encode_utf16().count() as u32;
+        label.push_str(&parameter.to_string());
+        let end = label.encode_utf16().count() as u32;
+
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, end]),
+            documentation: docblock
+                .as_ref()
+                .and_then(|docblock| docblock.get_param_description(&parameter.name))
+                .map(Documentation::String),
+        });
+    }
+    label.push(')');
+    if let Some(return_type) = &callable.return_type {
+        label.push_str(&format!(": {}", shorten_class_names(return_type)));
+    }
+
+    let active_parameter = get_active_parameter(&call, &callable.parameters);
+    let documentation = docblock
+        .filter(|docblock| !docblock.description.is_empty())
+        .map(|docblock| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: docblock.description,
+            })
+        });
+
+    Some(SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label,
+            documentation,
+            parameters: Some(parameters),
+            active_parameter,
+        }],
+        active_signature: Some(0),
+        active_parameter,
+    })
+}
+
+/// Finds the parenthesis of the call whose arguments the text ends in. The
+/// text is scanned from the start so strings and comments are skipped whole.
+fn find_open_call(before: &str) -> Option<OpenCall<'_>> {
+    // the brackets that are still open with the commas directly inside of them
+    let mut open: Vec<(usize, char, Vec<usize>)> = vec![];
+    let mut chars = before.char_indices().peekable();
+
+    while let Some((index, char)) = chars.next() {
+        let next = chars.peek().map(|(_, next)| *next);
+        match char {
+            '\'' | '"' | '`' => {
+                while let Some((_, inner)) = chars.next() {
+                    match inner {
+                        '\\' => {
+                            chars.next();
+                        }
+                        _ if inner == char => break,
+                        _ => {}
+                    }
+                }
+            }
+            // `#[` opens an attribute rather than a comment
+            '/' if next == Some('/') => skip_line(&mut chars),
+            '#' if next != Some('[') => skip_line(&mut chars),
+            '/' if next == Some('*') => {
+                chars.next();
+                let mut previous = ' ';
+                for (_, inner) in chars.by_ref() {
+                    if previous == '*' && inner == '/' {
+                        break;
+                    }
+                    previous = inner;
+                }
+            }
+            '(' | '[' | '{' => open.push((index, char, vec![])),
+            ')' | ']' | '}' => {
+                open.pop();
+            }
+            ',' => {
+                if let Some((_, _, commas)) = open.last_mut() {
+                    commas.push(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // inside of an array or a block rather than arguments
+    let (parenthesis, '(', commas) = open.pop()? else {
+        return None;
+    };
+    let start = commas.last().map_or(parenthesis, |comma| *comma) + 1;
+
+    Some(OpenCall {
+        parenthesis,
+        index: commas.len(),
+        argument: &before[start..],
+    })
+}
+
+fn skip_line(chars: &mut impl Iterator<Item = (usize, char)>) {
+    for (_, char) in chars {
+        if char == '\n' {
+            break;
+        }
+    }
+}
+
+/// Returns the parameter the argument at the cursor is for: the one it names,
+/// otherwise the one at its position, variadic parameters taking every
+/// argument after them.
+fn get_active_parameter(call: &OpenCall, parameters: &[Parameter]) -> Option<u32> {
+    let argument = call.argument.trim_start();
+    let name_length = argument
+        .find(|char: char| !char.is_alphanumeric() && char != '_')
+        .unwrap_or(argument.len());
+    let rest = argument[name_length..].trim_start();
+
+    // `foo(name: $value)`
+    if name_length > 0 && rest.starts_with(':') && !rest.starts_with("::") {
+        let name = format!("${}", &argument[..name_length]);
+        return parameters
+            .iter()
+            .position(|parameter| parameter.name == name)
+            .map(|index| index as u32);
+    }
+
+    match parameters.get(call.index) {
+        Some(_) => Some(call.index as u32),
+        None => parameters
+            .last()
+            .filter(|parameter| parameter.is_variadic)
+            .map(|_| (parameters.len() - 1) as u32),
+    }
+}
+
+/// Resolves the function, method or constructor called with the arguments
+/// opened by the parenthesis at the offset.
+fn resolve_callable(content: &str, parenthesis: usize, state: &State) -> Option<Callable> {
+    let interner = ThreadedInterner::new();
+    let (program, _) = parse(
+        &interner,
+        Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+    );
+    let position = get_position_from_offset(content, parenthesis);
+    let nodes = get_nodes_for_position(&Node::Program(&program), content, &position);
+
+    let is_opened_here = |span: mago_span::Span| span.start.offset == parenthesis;
+    let depth = nodes.iter().rposition(|node| match node {
+        Node::FunctionCall(call) => is_opened_here(call.argument_list.left_parenthesis),
+        Node::MethodCall(call) => is_opened_here(call.argument_list.left_parenthesis),
+        Node::NullSafeMethodCall(call) => is_opened_here(call.argument_list.left_parenthesis),
+        Node::StaticMethodCall(call) => is_opened_here(call.argument_list.left_parenthesis),
+        Node::Instantiation(instantiation) => instantiation
+            .arguments
+            .as_ref()
+            .is_some_and(|arguments| is_opened_here(arguments.left_parenthesis)),
+        _ => false,
+    })?;
+
+    let names = get_name_context(&program, content, parenthesis);
+    let scope = Scope {
+        document: content,
+        nodes: &nodes[..depth],
+        names: &names,
+    };
+    let find_method = |object: &Expression, method: &str| {
+        let method = get_classes(&infer_expression_type(object, &scope, state))
+            .into_iter()
+            .find_map(|class| find_member(&class, method, MemberKind::Method, state))?;
+
+        Some(Callable {
+            name: method.member.name.clone(),
+            parameters: method.member.parameters.clone(),
+            return_type: method
+                .member
+                .type_hint
+                .clone()
+                .or(method.member.doc_type.clone()),
+            location: method.location(),
+        })
+    };
+    let method_name = |selector: &ClassLikeMemberSelector| match selector {
+        ClassLikeMemberSelector::Identifier(identifier) => Some(get_node_name(content, identifier)),
+        _ => None,
+    };
+
+    match nodes[depth] {
+        Node::FunctionCall(call) => {
+            let Expression::Identifier(function) = call.function.as_ref() else {
+                return None;
+            };
+            let symbol = names
+                .resolve_function(&get_node_name(content, function))
+                .iter()
+                .find_map(|fqn| find_function_symbol(fqn, state))?;
+            let info = symbol.function?;
+
+            Some(Callable {
+                name: symbol.fqn.rsplit('\\').next()?.to_string(),
+                parameters: info.parameters,
+                return_type: info.type_hint.or(info.doc_type),
+                location: symbol.location,
+            })
+        }
+        Node::MethodCall(call) => find_method(&call.object, &method_name(&call.method)?),
+        Node::NullSafeMethodCall(call) => find_method(&call.object, &method_name(&call.method)?),
+        Node::StaticMethodCall(call) => find_method(&call.class, &method_name(&call.method)?),
+        // constructors are shown under the name of their class
+        Node::Instantiation(instantiation) => {
+            let constructor = find_method(&instantiation.class, "__construct")?;
+            let class = get_node_name(content, &instantiation.class);
+
+            Some(Callable {
+                name: class.rsplit('\\').next()?.to_string(),
+                return_type: None,
+                ..constructor
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Returns the docblock of the function-like declared at the location,
+/// reading the file from the open documents first since those may not be
+/// saved yet.
+fn find_declaration_docblock(location: &Location, state: &State) -> Option<DocBlock> {
+    let content = match state.document_map.get(&location.uri) {
+        Some(document) => document.clone(),
+        None => std::fs::read_to_string(location.uri.to_file_path().ok()?).ok()?,
+    };
+
+    let interner = ThreadedInterner::new();
+    let (program, _) = parse(
+        &interner,
+        Input::new(SourceIdentifier::dummy(), content.as_bytes()),
+    );
+    let nodes = get_nodes_for_position(&Node::Program(&program), &content, &location.range.start);
+    let declaration = nodes.iter().rev().find_map(|node| match node {
+        Node::Function(function) => Some(function.span()),
+        Node::Method(method) => Some(method.span()),
+        _ => None,
+    })?;
+
+    find_docblock(&program, &content, declaration.start.offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::indexer::Parameter;
+
+    use super::{find_open_call, get_active_parameter};
+
+    #[test]
+    fn finds_the_active_parameter() {
+        let parameter = |name: &str, is_variadic| Parameter {
+            name: name.to_string(),
+            type_hint: None,
+            default_value: None,
+            is_variadic,
+            is_by_reference: false,
+        };
+        let parameters = [
+            parameter("$name", false),
+            parameter("$notify", false),
+            parameter("$tags", true),
+        ];
+        let active = |before: &str| {
+            let call = find_open_call(before).unwrap();
+            get_active_parameter(&call, &parameters)
+        };
+
+        assert_eq!(active("$user->rename("), Some(0));
+        assert_eq!(active("rename(trim($name), "), Some(1));
+        assert_eq!(active("rename('a, b', [1, 2], "), Some(2));
+        assert_eq!(active("rename('a', true, 'b', 'c'"), Some(2));
+        assert_eq!(active("rename(notify: "), Some(1));
+        assert_eq!(active("rename(name: 'a', notify: true, tags: "), Some(2));
+        assert_eq!(active("rename(missing: "), None);
+        assert_eq!(active("rename(User::ROLE"), Some(0));
+        assert!(find_open_call("rename($a); $b = [").is_none());
+    }
+
+    #[test]
+    fn skips_strings_and_comments() {
+        let open_call = |before: &str| {
+            let call = find_open_call(before).unwrap();
+            (call.parenthesis, call.index)
+        };
+
+        assert_eq!(open_call(r"foo('it\'s', "), (3, 1));
+        assert_eq!(open_call(r#"foo("say \"hi\" (", "#), (3, 1));
+        assert_eq!(open_call("foo(\n// don't (\n$a, "), (3, 1));
+        assert_eq!(open_call("foo(/* a, b) */ $a, # c,\n"), (3, 1));
+        assert_eq!(open_call("foo(#[Pure] fn() => 1, "), (3, 1));
+        assert!(find_open_call("foo(); // bar(").is_none());
+    }
+}
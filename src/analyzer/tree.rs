@@ -1,48 +1,53 @@
 use mago_ast::Node;
-use mago_source::Source;
-use mago_span::{HasPosition, HasSpan};
+use mago_span::{HasPosition, HasSpan, Span};
 use tower_lsp::lsp_types::{Position, Range};
 
-pub fn get_node_for_position<'a>(
+use super::utils::{get_offset_from_position, get_position_from_offset};
+
+/// Returns every node that contains the needle, ordered from the outermost
+/// (the node passed in) to the innermost one.
+pub fn get_nodes_for_position<'a>(
     node: &Node<'a>,
-    source: &Source,
+    document: &str,
     needle: &Position,
-) -> Option<Node<'a>> {
-    let range = get_range(node, source);
+) -> Vec<Node<'a>> {
+    let mut nodes = vec![];
+    let offset = get_offset_from_position(document, needle);
+    collect_nodes_for_offset(node, offset, &mut nodes);
+
+    nodes
+}
 
-    if (range.start.line..=range.end.line).contains(&needle.line)
-        && (range.start.character..=range.end.character).contains(&needle.character)
-    {
-        return Some(*node);
+fn collect_nodes_for_offset<'a>(node: &Node<'a>, offset: usize, out: &mut Vec<Node<'a>>) {
+    if !span_contains_offset(node.span(), offset) {
+        return;
     }
 
-    for node in node.children() {
-        if let Some(n) = get_node_for_position(&node, source, needle) {
-            return Some(n);
+    out.push(*node);
+
+    for child in node.children() {
+        if span_contains_offset(child.span(), offset) {
+            collect_nodes_for_offset(&child, offset, out);
+            return;
         }
     }
+}
 
-    None
+fn span_contains_offset(span: Span, offset: usize) -> bool {
+    span.start.offset <= offset && offset <= span.end.offset
 }
 
 //move somwhere else?
-pub fn get_range(node: impl HasSpan, source: &Source) -> Range {
+pub fn get_range(node: impl HasSpan, document: &str) -> Range {
     Range {
-        start: Position {
-            line: (source.line_number(node.start_position().offset())) as u32,
-            character: (source.column_number(node.start_position().offset())) as u32,
-        },
-        end: Position {
-            line: (source.line_number(node.end_position().offset())) as u32,
-            character: (source.column_number(node.end_position().offset())) as u32,
-        },
+        start: get_position_from_offset(document, node.start_position().offset()),
+        end: get_position_from_offset(document, node.end_position().offset()),
     }
 }
 
 //move somwhere else?
 pub fn range_contains_position(range: &Range, position: &Position) -> bool {
-    (range.start.line..=range.end.line).contains(&position.line)
-        && (range.start.character..=range.end.character).contains(&position.character)
+    range.start <= *position && *position <= range.end
 }
 //move somwhere else?
 pub fn get_node_name(document: &str, node: impl HasSpan) -> String {
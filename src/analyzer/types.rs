@@ -0,0 +1,623 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::lsp::state::State;
+
+use super::{
+    members::{find_class_symbol, get_parent_class},
+    phpdoc::{parse_doc_type, DocType, Template},
+};
+
+const BUILTIN_TYPES: [&str; 17] = [
+    "null", "int", "float", "string", "bool", "array", "callable", "iterable", "object", "mixed",
+    "void", "never", "true", "false", "resource", "integer", "boolean",
+];
+
+/// Types only docblocks know about, together with the builtin types they are
+/// narrowed down from.
+const PSEUDO_TYPES: [(&str, &str); 24] = [
+    ("double", "float"),
+    ("scalar", "int|float|string|bool"),
+    ("numeric", "int|float|string"),
+    ("number", "int|float"),
+    ("array-key", "int|string"),
+    ("class-string", "string"),
+    ("interface-string", "string"),
+    ("trait-string", "string"),
+    ("enum-string", "string"),
+    ("callable-string", "string"),
+    ("numeric-string", "string"),
+    ("non-empty-string", "string"),
+    ("non-falsy-string", "string"),
+    ("truthy-string", "string"),
+    ("literal-string", "string"),
+    ("lowercase-string", "string"),
+    ("positive-int", "int"),
+    ("negative-int", "int"),
+    ("non-positive-int", "int"),
+    ("non-negative-int", "int"),
+    ("non-empty-array", "array"),
+    ("list", "array"),
+    ("non-empty-list", "array"),
+    ("callable-array", "array"),
+];
+
+pub fn is_builtin_type(name: &str) -> bool {
+    BUILTIN_TYPES.contains(&name.to_ascii_lowercase().as_str())
+}
+
+/// Whether a name in a docblock type refers to a builtin or pseudo type rather
+/// than to a class.
+pub fn is_doc_builtin_type(name: &str) -> bool {
+    let lowercase = name.to_ascii_lowercase();
+
+    is_builtin_type(&lowercase)
+        || PSEUDO_TYPES.iter().any(|(pseudo, _)| *pseudo == lowercase)
+        || matches!(
+            lowercase.as_str(),
+            "self" | "static" | "parent" | "void" | "key-of" | "value-of"
+        )
+}
+
+/// One member of a union type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    /// A class-like, by its fully qualified name.
+    Class(String),
+    /// A builtin type like `int` or `null`, always lowercase.
+    Builtin(String),
+    /// A class-like or `iterable` together with its template arguments, e.g.
+    /// `Collection<int, User>`.
+    Generic(String, Vec<Vec<Type>>),
+    /// An array whose keys and values are known to be of some types, e.g.
+    /// `User[]` or `array<string, User>`. The key types are empty when unknown.
+    Array(Vec<Type>, Vec<Type>),
+}
+
+impl Type {
+    pub fn builtin(name: &str) -> Type {
+        Type::Builtin(name.to_string())
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.is_builtin("null")
+    }
+
+    /// Whether the type is the builtin type or a more precise form of it.
+    pub fn is_builtin(&self, builtin: &str) -> bool {
+        match self {
+            Type::Builtin(name) => name == builtin,
+            Type::Generic(name, _) => name == "iterable" && builtin == "iterable",
+            Type::Array(..) => builtin == "array",
+            Type::Class(_) => false,
+        }
+    }
+
+    /// Returns the fully qualified name of the class-like the type is an
+    /// instance of.
+    pub fn class(&self) -> Option<&str> {
+        match self {
+            Type::Class(class) => Some(class),
+            Type::Generic(class, _) if class != "iterable" => Some(class),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Class(name) | Type::Builtin(name) => write!(f, "{}", name),
+            Type::Generic(name, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| format_types(argument))
+                    .collect::<Vec<_>>();
+                write!(f, "{}<{}>", name, arguments.join(", "))
+            }
+            Type::Array(keys, values) if values.is_empty() => match keys.is_empty() {
+                true => write!(f, "array"),
+                false => write!(f, "array<{}, mixed>", format_types(keys)),
+            },
+            Type::Array(keys, values) if keys.is_empty() => match values.len() {
+                1 => write!(f, "{}[]", values[0]),
+                _ => write!(f, "({})[]", format_types(values)),
+            },
+            Type::Array(keys, values) => {
+                write!(f, "array<{}, {}>", format_types(keys), format_types(values))
+            }
+        }
+    }
+}
+
+/// The types template parameters stand for, by the name of the template.
+pub type Templates = HashMap<String, Vec<Type>>;
+
+/// What the names in a type are resolved against.
+pub struct TypeContext<'a> {
+    /// The class-like declaring the member the type belongs to, what `self`
+    /// refers to.
+    pub declaring_class: &'a str,
+    /// The class-like the member was accessed on together with its template
+    /// arguments, what `static` and `$this` refer to.
+    pub called_class: &'a str,
+    pub called_arguments: &'a [Vec<Type>],
+    pub templates: &'a Templates,
+}
+
+/// Adds the type to the union unless it is already part of it.
+pub fn add_type(types: &mut Vec<Type>, r#type: Type) {
+    if !types.contains(&r#type) {
+        types.push(r#type);
+    }
+}
+
+pub fn union(mut types: Vec<Type>, other: Vec<Type>) -> Vec<Type> {
+    for r#type in other {
+        add_type(&mut types, r#type);
+    }
+
+    types
+}
+
+pub fn format_types(types: &[Type]) -> String {
+    types
+        .iter()
+        .map(|r#type| r#type.to_string())
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Drops the namespaces of the class names in a type for display,
+/// `App\Models\User|null` becomes `User|null`.
+pub fn shorten_class_names(r#type: &str) -> String {
+    let mut shortened = String::new();
+    let mut name = String::new();
+    for char in r#type.chars().chain(std::iter::once(' ')) {
+        if char.is_alphanumeric() || char == '_' || char == '\\' {
+            name.push(char);
+            continue;
+        }
+
+        shortened.push_str(name.rsplit('\\').next().unwrap_or_default());
+        name.clear();
+        shortened.push(char);
+    }
+    shortened.pop();
+
+    shortened
+}
+
+pub fn get_classes(types: &[Type]) -> Vec<String> {
+    let mut classes: Vec<String> = vec![];
+    for class in types.iter().filter_map(Type::class) {
+        if !classes.iter().any(|known| known == class) {
+            classes.push(class.to_string());
+        }
+    }
+
+    classes
+}
+
+/// Turns a type as stored in the index into the types of its union. `self`
+/// refers to the class declaring the member and `static` to the class it was
+/// called on.
+pub fn parse_type(
+    type_hint: &str,
+    declaring_class: &str,
+    called_class: &str,
+    state: &State,
+) -> Vec<Type> {
+    resolve_type(
+        type_hint,
+        &TypeContext {
+            declaring_class,
+            called_class,
+            called_arguments: &[],
+            templates: &Templates::new(),
+        },
+        state,
+    )
+}
+
+/// Same as `parse_type` but with template parameters standing for the types
+/// they were given.
+pub fn resolve_type(type_hint: &str, context: &TypeContext, state: &State) -> Vec<Type> {
+    match parse_doc_type(type_hint) {
+        Some(doc_type) => convert_type(&doc_type, context, state),
+        None => vec![],
+    }
+}
+
+fn convert_type(doc_type: &DocType, context: &TypeContext, state: &State) -> Vec<Type> {
+    let convert_all = |doc_types: &[DocType]| -> Vec<Type> {
+        doc_types.iter().fold(vec![], |types, doc_type| {
+            union(types, convert_type(doc_type, context, state))
+        })
+    };
+
+    match doc_type {
+        DocType::Named { name, arguments } if arguments.is_empty() => {
+            match context.templates.get(name) {
+                Some(types) => types.clone(),
+                None => parse_name(name, context, state),
+            }
+        }
+        DocType::Named { name, arguments } => {
+            let arguments: Vec<Vec<Type>> = arguments
+                .iter()
+                .map(|argument| convert_type(argument, context, state))
+                .collect();
+            let last = || arguments.last().cloned().unwrap_or_default();
+
+            match name.to_ascii_lowercase().as_str() {
+                "array" | "non-empty-array" => match arguments.len() {
+                    1 => vec![Type::Array(vec![], last())],
+                    _ => vec![Type::Array(arguments[0].clone(), last())],
+                },
+                "list" | "non-empty-list" => vec![Type::Array(vec![Type::builtin("int")], last())],
+                "iterable" => vec![Type::Generic(String::from("iterable"), arguments)],
+                _ => parse_name(name, context, state)
+                    .into_iter()
+                    .map(|r#type| match r#type {
+                        Type::Class(class) => Type::Generic(class, arguments.clone()),
+                        r#type => r#type,
+                    })
+                    .collect(),
+            }
+        }
+        DocType::Array(inner) => vec![Type::Array(vec![], convert_type(inner, context, state))],
+        DocType::Shape { name, .. } if name.eq_ignore_ascii_case("object") => {
+            vec![Type::builtin("object")]
+        }
+        DocType::Shape { fields, .. } => {
+            let mut keys = vec![];
+            for field in fields.iter() {
+                let is_string_key = field.key.as_ref().is_some_and(|key| {
+                    !key.starts_with(|char: char| char.is_ascii_digit() || char == '-')
+                });
+                add_type(
+                    &mut keys,
+                    Type::builtin(if is_string_key { "string" } else { "int" }),
+                );
+            }
+            let values = fields.iter().fold(vec![], |types, field| {
+                union(types, convert_type(&field.value, context, state))
+            });
+
+            vec![Type::Array(keys, values)]
+        }
+        DocType::Callable { name, .. } => parse_name(name, context, state),
+        DocType::Nullable(inner) => union(
+            convert_type(inner, context, state),
+            vec![Type::builtin("null")],
+        ),
+        DocType::Union(types) | DocType::Intersection(types) => convert_all(types),
+        DocType::Conditional { then, r#else, .. } => union(
+            convert_type(then, context, state),
+            convert_type(r#else, context, state),
+        ),
+        DocType::Literal(literal) if literal.starts_with(['\'', '"']) => {
+            vec![Type::builtin("string")]
+        }
+        DocType::Literal(literal) if literal.contains("::") => vec![],
+        DocType::Literal(literal) if literal.contains('.') => vec![Type::builtin("float")],
+        DocType::Literal(_) => vec![Type::builtin("int")],
+    }
+}
+
+fn parse_name(name: &str, context: &TypeContext, state: &State) -> Vec<Type> {
+    let lowercase = name.to_ascii_lowercase();
+
+    match lowercase.as_str() {
+        "" => vec![],
+        "self" => vec![Type::Class(context.declaring_class.to_string())],
+        "static" | "$this" if !context.called_arguments.is_empty() => vec![Type::Generic(
+            context.called_class.to_string(),
+            context.called_arguments.to_vec(),
+        )],
+        "static" | "$this" => vec![Type::Class(context.called_class.to_string())],
+        "parent" => get_parent_class(context.declaring_class, state)
+            .map(Type::Class)
+            .into_iter()
+            .collect(),
+        "integer" => vec![Type::builtin("int")],
+        "boolean" => vec![Type::builtin("bool")],
+        _ if is_builtin_type(&lowercase) => vec![Type::Builtin(lowercase)],
+        _ => match PSEUDO_TYPES.iter().find(|(pseudo, _)| *pseudo == lowercase) {
+            Some((_, builtin)) => builtin.split('|').map(Type::builtin).collect(),
+            None => vec![Type::Class(name.trim_start_matches('\\').to_string())],
+        },
+    }
+}
+
+/// Returns the types the templates of `target` stand for when a member is
+/// accessed on `class` with the template arguments, following the `@extends`,
+/// `@implements` and `@use` annotations from `class` up to `target`.
+/// Templates nobody gave an argument for stand for their bound.
+pub fn get_template_types(
+    class: &str,
+    arguments: &[Vec<Type>],
+    target: &str,
+    state: &State,
+) -> Templates {
+    let arguments = get_generic_supertypes(class, arguments, state)
+        .into_iter()
+        .find(|(supertype, _)| supertype.eq_ignore_ascii_case(target))
+        .map(|(_, arguments)| arguments)
+        .unwrap_or_default();
+
+    bind_templates(target, &arguments, state)
+}
+
+/// Returns the types of the keys and of the values of the iterables. Generic
+/// classes that do not say which iterable they are, are taken to follow the
+/// `Collection<TKey, TValue>` convention.
+pub fn get_iterable_types(types: &[Type], state: &State) -> (Vec<Type>, Vec<Type>) {
+    let mut keys = vec![];
+    let mut values = vec![];
+
+    for r#type in types {
+        let (class, arguments) = match r#type {
+            Type::Array(key_types, value_types) => {
+                keys = union(keys, key_types.clone());
+                values = union(values, value_types.clone());
+                continue;
+            }
+            Type::Generic(class, arguments) => (class.as_str(), arguments.as_slice()),
+            Type::Class(class) => (class.as_str(), [].as_slice()),
+            Type::Builtin(_) => continue,
+        };
+
+        let supertypes = get_generic_supertypes(class, arguments, state);
+        let iterable = supertypes
+            .iter()
+            .find(|(supertype, _)| {
+                ITERABLE_TYPES
+                    .iter()
+                    .any(|iterable| iterable.eq_ignore_ascii_case(supertype))
+            })
+            .or_else(|| {
+                supertypes
+                    .iter()
+                    .find(|(_, arguments)| !arguments.is_empty())
+            });
+
+        let Some((supertype, arguments)) = iterable else {
+            continue;
+        };
+        let (key_types, value_types) = match arguments.as_slice() {
+            [] => continue,
+            [value] => (vec![], value.clone()),
+            // `Generator<TKey, TValue, TSend, TReturn>`
+            [key, value, ..] if supertype.eq_ignore_ascii_case("Generator") => {
+                (key.clone(), value.clone())
+            }
+            [.., key, value] => (key.clone(), value.clone()),
+        };
+        keys = union(keys, key_types);
+        values = union(values, value_types);
+    }
+
+    (keys, values)
+}
+
+/// The interfaces and types whose template arguments are the types of the
+/// keys and values they iterate over.
+const ITERABLE_TYPES: [&str; 6] = [
+    "iterable",
+    "Traversable",
+    "Iterator",
+    "IteratorAggregate",
+    "Generator",
+    "ArrayIterator",
+];
+
+/// Returns the class-like with its template arguments followed by its
+/// supertypes with the arguments it passes to them, closest first.
+fn get_generic_supertypes(
+    class: &str,
+    arguments: &[Vec<Type>],
+    state: &State,
+) -> Vec<(String, Vec<Vec<Type>>)> {
+    let mut supertypes = vec![];
+    let mut queue = vec![(class.to_string(), arguments.to_vec())];
+
+    while let Some((class, arguments)) = queue.pop() {
+        if supertypes
+            .iter()
+            .any(|(known, _): &(String, _)| known.eq_ignore_ascii_case(&class))
+        {
+            continue;
+        }
+
+        let info = find_class_symbol(&class, state).and_then(|symbol| symbol.class);
+        supertypes.push((class.clone(), arguments.clone()));
+        let Some(info) = info else {
+            continue;
+        };
+
+        let templates = bind_templates(&class, &arguments, state);
+        let context = TypeContext {
+            declaring_class: &class,
+            called_class: &class,
+            called_arguments: &arguments,
+            templates: &templates,
+        };
+        let generics: Vec<(String, Vec<Vec<Type>>)> = info
+            .generics
+            .iter()
+            .flat_map(|generic| resolve_type(generic, &context, state))
+            .filter_map(|r#type| match r#type {
+                Type::Generic(supertype, arguments) => Some((supertype, arguments)),
+                _ => None,
+            })
+            .collect();
+        let plain = info
+            .extends
+            .iter()
+            .chain(info.implements.iter())
+            .chain(info.traits.iter())
+            .filter(|supertype| {
+                !generics
+                    .iter()
+                    .any(|(generic, _)| generic.eq_ignore_ascii_case(supertype))
+            })
+            .map(|supertype| (supertype.clone(), vec![]))
+            .collect::<Vec<_>>();
+
+        // the queue is a stack, the closest supertypes go last
+        queue.extend(plain.into_iter().rev());
+        queue.extend(generics.into_iter().rev());
+    }
+
+    supertypes
+}
+
+/// Returns the types the templates of a class-like stand for given its
+/// template arguments.
+fn bind_templates(class: &str, arguments: &[Vec<Type>], state: &State) -> Templates {
+    let Some(info) = find_class_symbol(class, state).and_then(|symbol| symbol.class) else {
+        return Templates::new();
+    };
+
+    let mut templates = get_bound_types(&info.templates, class, state);
+    for (template, types) in info.templates.iter().zip(arguments) {
+        if !types.is_empty() {
+            templates.insert(template.name.clone(), types.clone());
+        }
+    }
+
+    templates
+}
+
+/// Returns the types template parameters stand for when nothing tells what
+/// they were given: their bounds, or nothing at all for unbounded ones.
+pub fn get_bound_types(templates: &[Template], declaring_class: &str, state: &State) -> Templates {
+    templates
+        .iter()
+        .map(|template| {
+            let types = template
+                .bound
+                .as_ref()
+                .map(|bound| parse_type(bound, declaring_class, declaring_class, state))
+                .unwrap_or_default();
+
+            (template.name.clone(), types)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analyzer::types::{
+            get_iterable_types, parse_type, resolve_type, shorten_class_names, Templates, Type,
+            TypeContext,
+        },
+        lsp::state::State,
+    };
+
+    #[test]
+    fn parse_union_types() {
+        let state = State::default();
+        let parse = |type_hint| parse_type(type_hint, "App\\User", "App\\Admin", &state);
+
+        assert_eq!(
+            parse("App\\Post|null"),
+            vec![
+                Type::Class(String::from("App\\Post")),
+                Type::builtin("null")
+            ]
+        );
+        assert_eq!(
+            parse("?static"),
+            vec![
+                Type::Class(String::from("App\\Admin")),
+                Type::builtin("null")
+            ]
+        );
+        assert_eq!(
+            parse("self[]|array-key|(App\\A&App\\B)"),
+            vec![
+                Type::Array(vec![], vec![Type::Class(String::from("App\\User"))]),
+                Type::builtin("int"),
+                Type::builtin("string"),
+                Type::Class(String::from("App\\A")),
+                Type::Class(String::from("App\\B")),
+            ]
+        );
+        assert_eq!(
+            parse("App\\Collection<int, App\\User>|array{id: int, name?: string}"),
+            vec![
+                Type::Generic(
+                    String::from("App\\Collection"),
+                    vec![
+                        vec![Type::builtin("int")],
+                        vec![Type::Class(String::from("App\\User"))]
+                    ]
+                ),
+                Type::Array(
+                    vec![Type::builtin("string")],
+                    vec![Type::builtin("int"), Type::builtin("string")]
+                )
+            ]
+        );
+        assert_eq!(
+            parse("list<App\\User>|class-string<App\\User>"),
+            vec![
+                Type::Array(
+                    vec![Type::builtin("int")],
+                    vec![Type::Class(String::from("App\\User"))]
+                ),
+                Type::builtin("string")
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_templates() {
+        let state = State::default();
+        let templates = Templates::from([(
+            String::from("T"),
+            vec![Type::Class(String::from("App\\User"))],
+        )]);
+        let context = TypeContext {
+            declaring_class: "App\\Collection",
+            called_class: "App\\Collection",
+            called_arguments: &[],
+            templates: &templates,
+        };
+
+        assert_eq!(
+            resolve_type("array<int, T>|T|null", &context, &state),
+            vec![
+                Type::Array(
+                    vec![Type::builtin("int")],
+                    vec![Type::Class(String::from("App\\User"))]
+                ),
+                Type::Class(String::from("App\\User")),
+                Type::builtin("null"),
+            ]
+        );
+        assert_eq!(
+            get_iterable_types(
+                &resolve_type("Generator<int, T, mixed, void>", &context, &state),
+                &state
+            ),
+            (
+                vec![Type::builtin("int")],
+                vec![Type::Class(String::from("App\\User"))]
+            )
+        );
+    }
+
+    #[test]
+    fn shorten_class_names_in_types() {
+        assert_eq!(shorten_class_names("App\\Models\\User|null"), "User|null");
+        assert_eq!(
+            shorten_class_names("array<int, App\\User>|static"),
+            "array<int, User>|static"
+        );
+        assert_eq!(shorten_class_names("string"), "string");
+    }
+}
@@ -1,25 +1,109 @@
-use tower_lsp::lsp_types::{Location, Position};
-use tree_sitter::{Node, Point, Tree};
+use tower_lsp::lsp_types::Position;
+use tree_sitter::{InputEdit, Node, Point, Tree};
 
-pub fn get_point_from_position(position: &Position) -> Point {
-    Point {
-        row: position.line as usize,
-        column: position.character as usize,
+pub fn get_node_for_point(tree: &Tree, point: Point) -> Option<Node<'_>> {
+    tree.root_node().descendant_for_point_range(point, point)
+}
+
+/// Converts an lsp position (utf-16 based character offset) to a byte offset in the text.
+/// Positions past the end of a line or the document are clamped.
+pub fn get_offset_from_position(text: &str, position: &Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(idx) => line_start += idx + 1,
+            None => return text.len(),
+        }
+    }
+
+    let line_end = text[line_start..]
+        .find('\n')
+        .map_or(text.len(), |idx| line_start + idx);
+
+    let mut utf16_offset = 0;
+    for (idx, c) in text[line_start..line_end].char_indices() {
+        if utf16_offset >= position.character as usize {
+            return line_start + idx;
+        }
+        utf16_offset += c.len_utf16();
     }
+
+    line_end
 }
 
-pub fn get_position_from_point(point: &Point) -> Position {
+/// Converts a byte offset in the text to an lsp position (utf-16 based character offset).
+pub fn get_position_from_offset(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
     Position {
-        line: point.row as u32,
-        character: point.column as u32,
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
     }
 }
 
-pub fn get_node_for_point(tree: &Tree, point: Point) -> Option<Node> {
-    tree.root_node().descendant_for_point_range(point, point)
+/// Returns the innermost function, method, closure or arrow function around a
+/// node, which is the scope its variables live in.
+pub fn get_function_like_parent<'a>(node: &Node<'a>) -> Option<Node<'a>> {
+    let mut parent = node.parent();
+    while let Some(node) = parent {
+        if matches!(
+            node.kind(),
+            "function_definition" | "method_declaration" | "anonymous_function" | "arrow_function"
+        ) {
+            return Some(node);
+        }
+        parent = node.parent();
+    }
+
+    None
+}
+
+pub fn get_point_from_offset(text: &str, offset: usize) -> Point {
+    let before = &text[..offset];
+    let row = before.matches('\n').count();
+    let column = before.rfind('\n').map_or(offset, |idx| offset - idx - 1);
+
+    Point { row, column }
+}
+
+/// Replaces the given byte range of the text and returns the edit tree sitter
+/// needs to adjust a previously parsed tree.
+pub fn apply_text_edit(text: &mut String, start: usize, end: usize, new_text: &str) -> InputEdit {
+    let start_position = get_point_from_offset(text, start);
+    let old_end_position = get_point_from_offset(text, end);
+
+    text.replace_range(start..end, new_text);
+
+    let new_end_byte = start + new_text.len();
+
+    InputEdit {
+        start_byte: start,
+        old_end_byte: end,
+        new_end_byte,
+        start_position,
+        old_end_position,
+        new_end_position: get_point_from_offset(text, new_end_byte),
+    }
 }
 
-pub fn find_nearest_location(a: Position, b: Vec<Location>) -> Option<Location> {
-    b.into_iter()
-        .min_by_key(|location| a.line.abs_diff(location.range.start.line))
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+
+    use super::{get_offset_from_position, get_position_from_offset};
+
+    #[test]
+    fn converts_between_offsets_and_utf16_positions() {
+        let text = "<?php\n$city = 'Zürich'; $emoji = '😀'; $x = 1;\n";
+        let offset = text.find("$x").unwrap();
+
+        let position = get_position_from_offset(text, offset);
+        assert_eq!(position, Position::new(1, 33));
+        assert_eq!(get_offset_from_position(text, &position), offset);
+        assert_eq!(
+            get_position_from_offset(text, text.len()),
+            Position::new(2, 0)
+        );
+    }
 }
@@ -0,0 +1,271 @@
+use mago_ast::{
+    ArrayElement, AssignmentOperator, DirectVariable, Expression, Foreach, ForeachTarget,
+    FunctionLikeParameter, FunctionLikeParameterList, Node, StaticItem, TryCatchClause, Variable,
+};
+use mago_span::{HasSpan, Span};
+
+use super::{inference::Scope, tree::get_node_name};
+
+/// Where a variable gets its value from.
+#[derive(Debug, Clone, Copy)]
+pub enum VariableDefinition<'a> {
+    /// `$this`, defined by the class-like around it.
+    This(Node<'a>),
+    Parameter(&'a FunctionLikeParameter),
+    /// `$foo = $value`
+    Assignment(&'a DirectVariable, &'a Expression),
+    /// `[$foo, $bar] = $value` or `list($foo, $bar) = $value`
+    Destructuring(&'a DirectVariable, &'a Expression),
+    /// `foreach ($items as $key => $value)`
+    Foreach(&'a DirectVariable, &'a Foreach),
+    Catch(&'a DirectVariable, &'a TryCatchClause),
+    Global(&'a DirectVariable),
+    Static(&'a DirectVariable, Option<&'a Expression>),
+}
+
+impl VariableDefinition<'_> {
+    /// The span of the variable where it is defined or of the class-like name
+    /// for `$this`.
+    pub fn span(&self) -> Span {
+        match self {
+            VariableDefinition::This(node) => match node {
+                Node::Class(class) => class.name.span(),
+                Node::Interface(interface) => interface.name.span(),
+                Node::Trait(r#trait) => r#trait.name.span(),
+                Node::Enum(r#enum) => r#enum.name.span(),
+                _ => node.span(),
+            },
+            VariableDefinition::Parameter(parameter) => parameter.variable.span(),
+            VariableDefinition::Assignment(variable, _)
+            | VariableDefinition::Destructuring(variable, _)
+            | VariableDefinition::Foreach(variable, _)
+            | VariableDefinition::Catch(variable, _)
+            | VariableDefinition::Global(variable)
+            | VariableDefinition::Static(variable, _) => variable.span(),
+        }
+    }
+}
+
+/// A variable definition together with the depth in `Scope::nodes` of the
+/// function-like (or program) it was found in.
+#[derive(Debug, Clone, Copy)]
+pub struct ResolvedVariable<'a> {
+    pub definition: VariableDefinition<'a>,
+    pub depth: usize,
+}
+
+/// Finds where the variable used at the byte offset was last defined before
+/// that offset. Only the function-like the variable is used in is searched,
+/// except for arrow functions and variables a closure captures with `use`,
+/// which continue in the scope around them.
+pub fn find_variable_definition<'a>(
+    name: &str,
+    offset: usize,
+    scope: &Scope<'a, '_>,
+) -> Option<ResolvedVariable<'a>> {
+    if name == "$this" {
+        return scope
+            .nodes
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, node)| {
+                matches!(
+                    node,
+                    Node::Class(_) | Node::Interface(_) | Node::Trait(_) | Node::Enum(_)
+                )
+            })
+            .map(|(depth, node)| ResolvedVariable {
+                definition: VariableDefinition::This(*node),
+                depth,
+            });
+    }
+
+    let mut offset = offset;
+    for (depth, node) in scope.nodes.iter().enumerate().rev() {
+        let (parameters, captures) = match node {
+            Node::Program(_) => (None, false),
+            Node::Method(method) => (Some(&method.parameter_list), false),
+            Node::Function(function) => (Some(&function.parameter_list), false),
+            Node::Closure(closure) => {
+                let captured = closure.use_clause.iter().any(|clause| {
+                    clause
+                        .variables
+                        .iter()
+                        .any(|used| get_node_name(scope.document, &used.variable) == name)
+                });
+                (Some(&closure.parameter_list), captured)
+            }
+            // arrow functions see every variable of the scope they are declared in
+            Node::ArrowFunction(arrow_function) => (Some(&arrow_function.parameter_list), true),
+            // class bodies have no variables, only the arguments of an anonymous class do
+            Node::AnonymousClass(class) if offset < class.left_brace.start.offset => continue,
+            Node::Class(_)
+            | Node::AnonymousClass(_)
+            | Node::Interface(_)
+            | Node::Trait(_)
+            | Node::Enum(_) => return None,
+            _ => continue,
+        };
+
+        let mut last = None;
+        find_last_definition(node, name, offset, scope.document, true, &mut last);
+        let definition = last.or_else(|| {
+            parameters
+                .and_then(|parameters| find_parameter(parameters, name, scope.document))
+                .map(VariableDefinition::Parameter)
+        });
+
+        if let Some(definition) = definition {
+            return Some(ResolvedVariable { definition, depth });
+        }
+        if !captures {
+            return None;
+        }
+
+        offset = node.span().start.offset;
+    }
+
+    None
+}
+
+fn find_parameter<'a>(
+    parameters: &'a FunctionLikeParameterList,
+    name: &str,
+    document: &str,
+) -> Option<&'a FunctionLikeParameter> {
+    parameters
+        .parameters
+        .iter()
+        .find(|parameter| get_node_name(document, &parameter.variable) == name)
+}
+
+/// Walks the scope in document order, so the last definition found before the
+/// offset wins. Nested function-likes and class-likes have scopes of their own
+/// and are skipped.
+fn find_last_definition<'a>(
+    node: &Node<'a>,
+    name: &str,
+    offset: usize,
+    document: &str,
+    is_root: bool,
+    last: &mut Option<VariableDefinition<'a>>,
+) {
+    if node.span().start.offset > offset {
+        return;
+    }
+
+    let is_named = |variable: &DirectVariable| get_node_name(document, variable) == name;
+    let is_defined = |variable: &DirectVariable| variable.span().start.offset <= offset;
+
+    match node {
+        Node::Closure(_)
+        | Node::ArrowFunction(_)
+        | Node::Function(_)
+        | Node::Class(_)
+        | Node::AnonymousClass(_)
+        | Node::Interface(_)
+        | Node::Trait(_)
+        | Node::Enum(_)
+        | Node::Method(_)
+            if !is_root =>
+        {
+            return
+        }
+        Node::Assignment(assignment) => {
+            if let AssignmentOperator::Assign(_) = assignment.operator {
+                // the assigned variable is defined once the whole assignment is
+                // evaluated, `$foo = $foo + 1` refers to the previous `$foo`
+                let is_defined = |variable: &DirectVariable| {
+                    assignment.span().end.offset <= offset
+                        || (variable.span().start.offset <= offset
+                            && offset <= variable.span().end.offset)
+                };
+
+                match assignment.lhs.as_ref() {
+                    Expression::Variable(Variable::Direct(variable)) => {
+                        if is_named(variable) && is_defined(variable) {
+                            *last = Some(VariableDefinition::Assignment(variable, &assignment.rhs));
+                        }
+                    }
+                    lhs => {
+                        for variable in get_destructured_variables(lhs) {
+                            if is_named(variable) && is_defined(variable) {
+                                *last = Some(VariableDefinition::Destructuring(
+                                    variable,
+                                    &assignment.rhs,
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Node::Foreach(foreach) => {
+            let targets = match &foreach.target {
+                ForeachTarget::Value(target) => vec![target.value.as_ref()],
+                ForeachTarget::KeyValue(target) => vec![target.key.as_ref(), target.value.as_ref()],
+            };
+
+            for variable in targets.into_iter().flat_map(get_destructured_variables) {
+                if is_named(variable) && is_defined(variable) {
+                    *last = Some(VariableDefinition::Foreach(variable, foreach));
+                }
+            }
+        }
+        Node::TryCatchClause(clause) => {
+            if let Some(variable) = &clause.variable {
+                if is_named(variable) && is_defined(variable) {
+                    *last = Some(VariableDefinition::Catch(variable, clause));
+                }
+            }
+        }
+        Node::Global(global) => {
+            for variable in global.variables.iter() {
+                if let Variable::Direct(variable) = variable {
+                    if is_named(variable) && is_defined(variable) {
+                        *last = Some(VariableDefinition::Global(variable));
+                    }
+                }
+            }
+        }
+        Node::Static(r#static) => {
+            for item in r#static.items.iter() {
+                let (variable, value) = match item {
+                    StaticItem::Abstract(item) => (&item.variable, None),
+                    StaticItem::Concrete(item) => (&item.variable, Some(&item.value)),
+                };
+
+                if is_named(variable) && is_defined(variable) {
+                    *last = Some(VariableDefinition::Static(variable, value));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    for child in node.children() {
+        find_last_definition(&child, name, offset, document, false, last);
+    }
+}
+
+/// Returns the variables a destructuring target assigns to, e.g. `$a` and `$b`
+/// for `[$a, 'key' => [$b]]`. A plain variable is returned as it is.
+fn get_destructured_variables(target: &Expression) -> Vec<&DirectVariable> {
+    let elements = match target {
+        Expression::Variable(Variable::Direct(variable)) => return vec![variable],
+        Expression::Array(array) => &array.elements,
+        Expression::List(list) => &list.elements,
+        Expression::LegacyArray(array) => &array.elements,
+        _ => return vec![],
+    };
+
+    elements
+        .iter()
+        .flat_map(|element| match element {
+            ArrayElement::KeyValue(element) => get_destructured_variables(&element.value),
+            ArrayElement::Value(element) => get_destructured_variables(&element.value),
+            _ => vec![],
+        })
+        .collect()
+}
@@ -3,15 +3,29 @@ use std::sync::RwLock;
 use mago_interner::ThreadedInterner;
 use mago_lexer::input::Input;
 use mago_parser::parse;
-use mago_source::{Source, SourceIdentifier};
+use mago_source::SourceIdentifier;
 use mago_span::HasSpan;
 
 use tower_lsp::{
-    lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range, TextDocumentItem},
+    lsp_types::{
+        Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+        DidCloseTextDocumentParams, DidSaveTextDocumentParams, FileChangeType, Range,
+        TextDocumentContentChangeEvent, TextDocumentItem, Url,
+    },
     Client,
 };
 
-use crate::{analyzer::parser::Parser, lsp::state::State};
+use tree_sitter::Tree;
+
+use crate::{
+    analyzer::{
+        composer::{is_autoload_file, reload_autoload},
+        indexer::{index_file, index_path, remove_file},
+        parser::Parser,
+        utils::{apply_text_edit, get_offset_from_position, get_position_from_offset},
+    },
+    lsp::state::State,
+};
 
 pub async fn handle_did_open(
     document: &TextDocumentItem,
@@ -19,29 +33,172 @@ pub async fn handle_did_open(
     client: &Client,
     parser: &RwLock<Parser>,
 ) {
+    let diags = update_document(&document.uri, document.text.clone(), state, parser);
+
+    client
+        .publish_diagnostics(document.uri.clone(), diags, Some(document.version))
+        .await;
+}
+
+pub async fn handle_did_change(
+    params: &DidChangeTextDocumentParams,
+    state: &State,
+    client: &Client,
+    parser: &RwLock<Parser>,
+) {
+    let uri = &params.text_document.uri;
+    let diags = apply_document_changes(uri, &params.content_changes, state, parser);
+
+    client
+        .publish_diagnostics(uri.clone(), diags, Some(params.text_document.version))
+        .await;
+}
+
+pub async fn handle_did_close(params: &DidCloseTextDocumentParams, state: &State, client: &Client) {
+    let uri = &params.text_document.uri;
+    close_document(uri, state);
+
+    client.publish_diagnostics(uri.clone(), vec![], None).await;
+}
+
+pub fn handle_did_save(params: &DidSaveTextDocumentParams, state: &State) {
+    let Ok(path) = params.text_document.uri.to_file_path() else {
+        return;
+    };
+
+    let text = match &params.text {
+        Some(text) => text.clone(),
+        None => match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(_) => return,
+        },
+    };
+
+    index_file(&path, &text, state);
+}
+
+/// Reindexes the php files that changed on disk and reloads the class map when
+/// composer regenerated its autoload files.
+pub fn handle_did_change_watched_files(
+    params: &DidChangeWatchedFilesParams,
+    state: &State,
+    parser: &RwLock<Parser>,
+) {
+    let root_path = state.root_path.read().unwrap().clone();
+    let vendor_path = std::path::Path::new(&root_path).join("vendor");
+    let mut autoload_changed = false;
+
+    for event in params.changes.iter() {
+        let Ok(path) = event.uri.to_file_path() else {
+            continue;
+        };
+
+        if is_autoload_file(&path, &root_path) {
+            autoload_changed = true;
+            continue;
+        }
+
+        if path.extension().is_none_or(|ext| ext != "php") {
+            continue;
+        }
+        let Some(path_str) = path.to_str() else {
+            continue;
+        };
+
+        if event.typ == FileChangeType::DELETED {
+            remove_file(path_str, state);
+        } else if state.symbol_index.contains_key(path_str) {
+            if let Ok(content) = std::fs::read_to_string(&path) {
+                index_file(&path, &content, state);
+            }
+        } else if !path.starts_with(&vendor_path) {
+            // dependencies are indexed lazily once something points at them
+            index_path(&path, state);
+        }
+    }
+
+    if autoload_changed {
+        reload_autoload(parser, state);
+    }
+}
+
+/// Drops everything kept in memory for a document that is no longer open.
+pub fn close_document(uri: &Url, state: &State) {
+    state.document_program.remove(uri);
+    state.document_map.remove(uri);
+    state.ast_map.remove(uri);
+}
+
+/// Parses the text of a document and refreshes the program, text and tree
+/// stored for it, returning the parse errors as diagnostics.
+pub fn update_document(
+    uri: &Url,
+    text: String,
+    state: &State,
+    parser: &RwLock<Parser>,
+) -> Vec<Diagnostic> {
+    parse_document(uri, text, None, state, parser)
+}
+
+/// Applies the changes in order to the stored text of a document. Range edits
+/// are also applied to the previous tree so tree sitter can reuse it instead of
+/// parsing the whole document again.
+pub fn apply_document_changes(
+    uri: &Url,
+    changes: &[TextDocumentContentChangeEvent],
+    state: &State,
+    parser: &RwLock<Parser>,
+) -> Vec<Diagnostic> {
+    let mut text = state
+        .document_map
+        .get(uri)
+        .map(|document| document.clone())
+        .unwrap_or_default();
+    let mut old_tree = state.ast_map.remove(uri).map(|(_, tree)| tree);
+
+    for change in changes {
+        match change.range {
+            Some(range) => {
+                let start = get_offset_from_position(&text, &range.start);
+                let end = get_offset_from_position(&text, &range.end).max(start);
+                let edit = apply_text_edit(&mut text, start, end, &change.text);
+
+                if let Some(tree) = old_tree.as_mut() {
+                    tree.edit(&edit);
+                }
+            }
+            None => {
+                text = change.text.clone();
+                old_tree = None;
+            }
+        }
+    }
+
+    parse_document(uri, text, old_tree.as_ref(), state, parser)
+}
+
+fn parse_document(
+    uri: &Url,
+    text: String,
+    old_tree: Option<&Tree>,
+    state: &State,
+    parser: &RwLock<Parser>,
+) -> Vec<Diagnostic> {
     let interner = ThreadedInterner::new();
     let source_id = SourceIdentifier::dummy();
-    let source = Source::standalone(&interner, document.uri.path(), document.text.as_str()); // is this right?
-                                                                                             // instead of initializing a standolone source we should probably intiliaze a sourcemanager
-                                                                                             // somewhere
-    let input = Input::new(source_id, document.text.as_bytes());
+    let input = Input::new(source_id, text.as_bytes());
     let (program, error) = parse(&interner, input);
 
+    let mut diags = vec![];
     if let Some(e) = error {
         let span = e.span();
 
         let range = Range {
-            start: Position {
-                line: (source.line_number(span.start.offset)) as u32,
-                character: (source.column_number(span.start.offset)) as u32,
-            },
-            end: Position {
-                line: (source.line_number(span.end.offset)) as u32,
-                character: (source.column_number(span.end.offset)) as u32,
-            },
+            start: get_position_from_offset(&text, span.start.offset),
+            end: get_position_from_offset(&text, span.end.offset),
         };
 
-        let diags = vec![Diagnostic::new(
+        diags.push(Diagnostic::new(
             range,
             Some(DiagnosticSeverity::ERROR),
             None,
@@ -49,23 +206,19 @@ pub async fn handle_did_open(
             e.to_string(),
             None,
             None,
-        )];
+        ));
+    }
 
-        client
-            .publish_diagnostics(document.uri.clone(), diags, None)
-            .await;
+    //todo remove after we ditch tree sitter for mago parser
+    let tree = match old_tree {
+        Some(old_tree) => parser.write().unwrap().reparse(&text, old_tree),
+        None => parser.write().unwrap().parse(&text),
     }
+    .expect("to parse file");
 
-    state.document_program.insert(document.uri.clone(), program);
-    state
-        .document_map
-        .insert(document.uri.clone(), document.text.clone());
+    state.document_program.insert(uri.clone(), program);
+    state.document_map.insert(uri.clone(), text);
+    state.ast_map.insert(uri.clone(), tree);
 
-    //todo remove after we ditch tree sitter for mago parser
-    let tree = parser
-        .write()
-        .unwrap()
-        .parse(document.text.clone())
-        .expect("to parse file");
-    state.ast_map.insert(document.uri.clone(), tree);
+    diags
 }
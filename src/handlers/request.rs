@@ -1,25 +1,45 @@
 use std::{path::Path, sync::RwLock};
 
 use mago_ast::{
-    Access, ClassConstantAccess, ClassLikeMember, Expression, Hint, Node, Property, UseItems,
+    Access, ClassLikeConstantSelector, ClassLikeMember, ClassLikeMemberSelector, Expression, Hint,
+    Node, Program, Property, UseItems, Variable,
 };
-use mago_interner::ThreadedInterner;
-use mago_source::Source;
 use mago_span::{HasPosition, HasSpan};
 use streaming_iterator::StreamingIterator;
-use tower_lsp::lsp_types::{GotoDefinitionResponse, Location, Position, Url};
+use tower_lsp::lsp_types::{
+    request::GotoImplementationResponse, CompletionResponse, GotoDefinitionResponse, Hover,
+    Location, Position, SignatureHelp, Url,
+};
 use tracing::debug;
 use tree_sitter::{Query, QueryCursor, Tree};
 
 use crate::{
     analyzer::{
+        completion::get_completion_list,
+        composer::resolve_class_path,
+        docblock::{get_docblock_reference, DocReference},
+        hover::get_hover,
+        implementations::find_implementations,
+        indexer::{
+            collect_symbols, find_constant_location, find_function_location, get_symbol_location,
+            index_path, qualify_name, MemberKind, SymbolKind,
+        },
+        inference::{resolve_class_name, resolve_expression_classes, with_scope_at, Scope},
+        members::find_member,
+        names::get_name_context,
         parser::Parser,
-        query::{named_type_declaration_query, namespace_use_query, variable_declaration_query},
-        tree::{get_node_for_position, get_node_name, get_range, range_contains_position},
+        query::{
+            named_type_declaration_query, namespace_definition_query, namespace_use_query,
+            variable_declaration_query,
+        },
+        references::{find_references, get_reference, ReferenceTarget},
+        signature::get_signature_help,
+        tree::{get_node_name, get_nodes_for_position, get_range, range_contains_position},
         utils::{
-            find_nearest_location, get_node_for_point, get_point_from_position,
-            get_position_from_point,
+            get_function_like_parent, get_node_for_point, get_offset_from_position,
+            get_point_from_offset, get_position_from_offset,
         },
+        variables::find_variable_definition,
     },
     lsp::state::State,
 };
@@ -30,35 +50,63 @@ pub fn handle_go_to_definition(
     state: &State,
     parser: &RwLock<Parser>,
 ) -> Option<GotoDefinitionResponse> {
-    let program = state.document_program.get(uri).expect("to get the program");
-    let document = state.document_map.get(uri).expect("to get the document");
-    let tree = state.ast_map.get(uri).expect("to get the tree");
-
-    let source = Source::standalone(&ThreadedInterner::new(), uri.path(), &document); // todo
-                                                                                      // we
-                                                                                      // probably
-                                                                                      // shouldn't
-                                                                                      // standalone
-                                                                                      // sources?
+    // the document may have been closed in the meantime
+    let program = state.document_program.get(uri)?;
+    let document = state.document_map.get(uri)?;
+    let tree = state.ast_map.get(uri)?;
 
     // move to somewhere else
-    let node = get_node_for_position(&Node::Program(&program), &source, position);
-    debug!("Node: {:?}", node);
-    if let Some(n) = node {
+    // walk from the innermost node outwards so the most specific node wins
+    let nodes = get_nodes_for_position(&Node::Program(&program), &document, position);
+    let names = get_name_context(
+        &program,
+        &document,
+        get_offset_from_position(&document, position),
+    );
+    let scope = Scope {
+        document: &document,
+        nodes: &nodes,
+        names: &names,
+    };
+
+    if let Some(location) =
+        find_docblock_definition(&scope, &program, uri, position, state, parser, &tree)
+    {
+        return Some(GotoDefinitionResponse::Scalar(location));
+    }
+    if let Some(location) =
+        find_function_call_definition(&scope, &program, uri, &document, position, state)
+    {
+        return Some(GotoDefinitionResponse::Scalar(location));
+    }
+    if let Some(location) =
+        find_constant_definition(&scope, &program, uri, &document, position, state)
+    {
+        return Some(GotoDefinitionResponse::Scalar(location));
+    }
+    if let Some(location) = find_method_call_definition(&scope, &document, position, state) {
+        return Some(GotoDefinitionResponse::Scalar(location));
+    }
+    if let Some(location) = find_property_access_definition(&scope, &document, position, state) {
+        return Some(GotoDefinitionResponse::Scalar(location));
+    }
+    if let Some(location) = find_class_constant_definition(&scope, &document, position, state) {
+        return Some(GotoDefinitionResponse::Scalar(location));
+    }
+    if let Some(location) = find_variable_definition_location(&scope, uri, &document, position) {
+        return Some(GotoDefinitionResponse::Scalar(location));
+    }
+
+    let location = nodes.into_iter().rev().find_map(|n| {
         let name = document[n.start_position().offset()..n.end_position().offset()].to_string();
-        debug!("name: {:?}", name);
+        debug!("Node: {:?}, name: {:?}", n.kind(), name);
 
-        let location = match n {
+        match n {
             Node::UseItems(UseItems::Sequence(sequence)) => {
                 sequence.items.iter().find_map(|use_item| {
-                    if range_contains_position(&get_range(use_item, &source), position) {
-                        let fqn = get_node_name(&document, use_item);
-                        let path = state.class_map.get(&fqn);
-                        get_named_type_declaration_location(
-                            Path::new(path.unwrap().as_str()),
-                            fqn.split('\\').next_back().unwrap(),
-                            parser,
-                        )
+                    if range_contains_position(&get_range(use_item, &document), position) {
+                        let fqn = get_node_name(&document, &use_item.name);
+                        find_class_location(&fqn, state, parser)
                     } else {
                         None
                     }
@@ -79,9 +127,7 @@ pub fn handle_go_to_definition(
             Node::FunctionLikeParameterList(param_list) => {
                 param_list.parameters.iter().find_map(|parameter| {
                     if let Some(ref hint) = parameter.hint {
-                        find_hint_definition(
-                            hint, &document, state, parser, &tree, uri, &source, position,
-                        )
+                        find_hint_definition(hint, &document, state, parser, &tree, uri, position)
                     } else {
                         None
                     }
@@ -89,9 +135,7 @@ pub fn handle_go_to_definition(
             }
             Node::FunctionLikeParameter(parameter) => {
                 let param_hint_result = if let Some(ref hint) = parameter.hint {
-                    find_hint_definition(
-                        hint, &document, state, parser, &tree, uri, &source, position,
-                    )
+                    find_hint_definition(hint, &document, state, parser, &tree, uri, position)
                 } else {
                     None
                 };
@@ -101,7 +145,7 @@ pub fn handle_go_to_definition(
                         match &default_value.value {
                             Expression::Instantiation(instantiation) => {
                                 if range_contains_position(
-                                    &get_range(&instantiation.class, &source),
+                                    &get_range(&instantiation.class, &document),
                                     position,
                                 ) {
                                     find_named_type_definition(
@@ -118,7 +162,7 @@ pub fn handle_go_to_definition(
                             }
                             Expression::Access(Access::ClassConstant(class_constant)) => {
                                 if range_contains_position(
-                                    &get_range(&class_constant.class, &source),
+                                    &get_range(&class_constant.class, &document),
                                     position,
                                 ) {
                                     find_named_type_definition(
@@ -150,7 +194,7 @@ pub fn handle_go_to_definition(
             ),
             Node::Implements(implements_node) => {
                 implements_node.types.iter().find_map(|implements_type| {
-                    if range_contains_position(&get_range(implements_type, &source), position) {
+                    if range_contains_position(&get_range(implements_type, &document), position) {
                         find_named_type_definition(
                             &get_node_name(&document, implements_type),
                             &document,
@@ -165,7 +209,7 @@ pub fn handle_go_to_definition(
                 })
             }
             Node::Extends(extends) => extends.types.iter().find_map(|extends_type| {
-                if range_contains_position(&get_range(extends_type, &source), position) {
+                if range_contains_position(&get_range(extends_type, &document), position) {
                     find_named_type_definition(
                         &get_node_name(&document, extends_type),
                         &document,
@@ -181,7 +225,7 @@ pub fn handle_go_to_definition(
             Node::ClassLikeMember(class_member_node) => match class_member_node {
                 ClassLikeMember::TraitUse(trait_use) => {
                     trait_use.trait_names.iter().find_map(|trait_name| {
-                        if range_contains_position(&get_range(trait_name, &source), position) {
+                        if range_contains_position(&get_range(trait_name, &document), position) {
                             find_named_type_definition(
                                 &get_node_name(&document, trait_name),
                                 &document,
@@ -205,7 +249,6 @@ pub fn handle_go_to_definition(
                             parser,
                             &tree,
                             uri,
-                            &source,
                             position,
                         )
                     } else {
@@ -220,8 +263,7 @@ pub fn handle_go_to_definition(
                             .find_map(|parameter| {
                                 if let Some(ref hint) = parameter.hint {
                                     find_hint_definition(
-                                        hint, &document, state, parser, &tree, uri, &source,
-                                        position,
+                                        hint, &document, state, parser, &tree, uri, position,
                                     )
                                 } else {
                                     None
@@ -231,9 +273,7 @@ pub fn handle_go_to_definition(
                 }
                 ClassLikeMember::Property(Property::Plain(property)) => {
                     if let Some(ref hint) = property.hint {
-                        find_hint_definition(
-                            hint, &document, state, parser, &tree, uri, &source, position,
-                        )
+                        find_hint_definition(hint, &document, state, parser, &tree, uri, position)
                     } else {
                         None
                     }
@@ -241,15 +281,16 @@ pub fn handle_go_to_definition(
                 _ => None,
             },
             _ => None,
-        };
-
-        if let Some(found) = location {
-            return Some(GotoDefinitionResponse::Scalar(found));
         }
-    };
+    });
+
+    if let Some(found) = location {
+        return Some(GotoDefinitionResponse::Scalar(found));
+    }
 
     //todo remove all of this after we ditch tree sitter for mago parser
-    let current_point = get_point_from_position(position);
+    let current_point =
+        get_point_from_offset(&document, get_offset_from_position(&document, position));
     let current_node = get_node_for_point(&tree, current_point).expect("to get node");
 
     let parent = current_node
@@ -257,16 +298,78 @@ pub fn handle_go_to_definition(
         .expect("to get parent of current node");
 
     match parent.kind() {
-        "variable_name" | "member_access_expression" => {
-            let location = find_variable_declaration(&current_node, &document, uri, &tree)
-                .expect("to find variable declaration");
-
-            Some(GotoDefinitionResponse::Scalar(location))
-        }
+        // mago gives up on documents with syntax errors, tree sitter still recovers the scope
+        "variable_name" => find_variable_declaration(&current_node, &document, uri)
+            .map(GotoDefinitionResponse::Scalar),
+        // mago gives up on documents with syntax errors, tree sitter still recovers the type
+        "named_type" => find_named_type_definition(
+            current_node
+                .utf8_text(document.as_bytes())
+                .expect("to get type name"),
+            &document,
+            uri,
+            state,
+            parser,
+            &tree,
+        )
+        .map(GotoDefinitionResponse::Scalar),
         _ => None,
     }
 }
 
+pub fn handle_references(
+    uri: &Url,
+    position: &Position,
+    include_declaration: bool,
+    state: &State,
+) -> Option<Vec<Location>> {
+    let target = get_reference_target(uri, position, state)?;
+
+    Some(find_references(&target, include_declaration, uri, state))
+}
+
+pub fn handle_implementation(
+    uri: &Url,
+    position: &Position,
+    state: &State,
+) -> Option<GotoImplementationResponse> {
+    let target = get_reference_target(uri, position, state)?;
+
+    Some(GotoImplementationResponse::Array(find_implementations(
+        &target, state,
+    )))
+}
+
+pub fn handle_hover(uri: &Url, position: &Position, state: &State) -> Option<Hover> {
+    with_scope_at(uri, position, state, |scope| get_hover(scope, state))
+}
+
+pub fn handle_completion(
+    uri: &Url,
+    position: &Position,
+    state: &State,
+) -> Option<CompletionResponse> {
+    let list = get_completion_list(uri, position, state);
+    match list.items.is_empty() {
+        true => None,
+        false => Some(CompletionResponse::List(list)),
+    }
+}
+
+pub fn handle_signature_help(
+    uri: &Url,
+    position: &Position,
+    state: &State,
+) -> Option<SignatureHelp> {
+    get_signature_help(uri, position, state)
+}
+
+fn get_reference_target(uri: &Url, position: &Position, state: &State) -> Option<ReferenceTarget> {
+    with_scope_at(uri, position, state, |scope| get_reference(scope, state))
+        .map(|reference| reference.target)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn find_hint_definition(
     hint: &Hint,
     document: &str,
@@ -274,10 +377,9 @@ fn find_hint_definition(
     parser: &RwLock<Parser>,
     tree: &Tree,
     uri: &Url,
-    source: &Source,
     position: &Position,
 ) -> Option<Location> {
-    if !range_contains_position(&get_range(hint, source), position) {
+    if !range_contains_position(&get_range(hint, document), position) {
         return None;
     }
 
@@ -302,6 +404,45 @@ fn find_hint_definition(
     }
 }
 
+/// Finds the declaration of a name written in a docblock tag, like `User` in
+/// `@param User $user` or `User::find` in `@see User::find()`.
+fn find_docblock_definition(
+    scope: &Scope,
+    program: &Program,
+    uri: &Url,
+    position: &Position,
+    state: &State,
+    parser: &RwLock<Parser>,
+    tree: &Tree,
+) -> Option<Location> {
+    let offset = get_offset_from_position(scope.document, position);
+
+    match get_docblock_reference(program, scope.document, offset)? {
+        DocReference::ClassLike(name) => {
+            find_named_type_definition(&name, scope.document, uri, state, parser, tree)
+        }
+        DocReference::Member { class, name, kind } => {
+            resolve_class_name(&class, scope, state)
+                .iter()
+                .find_map(|class| {
+                    // `@see Foo::bar` may leave out the parentheses of a method
+                    find_member(class, &name, kind, state).or_else(|| match kind {
+                        MemberKind::Constant => {
+                            find_member(class, &name, MemberKind::Method, state)
+                        }
+                        _ => None,
+                    })
+                })
+                .map(|found| found.location())
+        }
+        DocReference::Function(name) => scope
+            .names
+            .resolve_function(&name)
+            .iter()
+            .find_map(|fqn| find_function_location(fqn, state)),
+    }
+}
+
 fn find_named_type_definition(
     name: &str,
     document: &str,
@@ -310,6 +451,10 @@ fn find_named_type_definition(
     parser: &RwLock<Parser>,
     tree: &Tree,
 ) -> Option<Location> {
+    if let Some(fqn) = name.strip_prefix('\\') {
+        return find_class_location(fqn, state, parser);
+    }
+
     let query = namespace_use_query().expect("to create query");
     let mut cursor = QueryCursor::new();
     let mut matches = cursor.matches(&query, tree.root_node(), document.as_bytes());
@@ -324,24 +469,20 @@ fn find_named_type_definition(
                 .utf8_text(document.as_bytes())
                 .expect("to get use statement");
 
-            let path = state.class_map.get(fqn);
-
-            if path.is_none() {
-                continue;
-            }
-
             if fqn.ends_with(format!("\\{}", name).as_str()) {
-                let path = path.unwrap();
-
-                if let Some(location) =
-                    get_named_type_declaration_location(Path::new(path.as_str()), name, parser)
-                {
+                if let Some(location) = find_class_location(fqn, state, parser) {
                     return Some(location);
                 }
             }
         }
     }
 
+    // names that are not imported are relative to the current namespace
+    let fqn = qualify_name(&get_current_namespace(document, tree), name);
+    if let Some(location) = find_class_location(&fqn, state, parser) {
+        return Some(location);
+    }
+
     //if there is no use statement try searching the current directory for the class
     // first try to check the current_dir/class_name.php
 
@@ -375,6 +516,267 @@ fn find_named_type_definition(
     None
 }
 
+fn find_variable_definition_location(
+    scope: &Scope,
+    uri: &Url,
+    document: &str,
+    position: &Position,
+) -> Option<Location> {
+    let Some(Node::DirectVariable(variable)) = scope.nodes.last() else {
+        return None;
+    };
+    // `Foo::$bar` is a property rather than a variable
+    let is_property = scope.nodes.iter().any(|node| match node {
+        Node::StaticPropertyAccess(access) => {
+            access.property.span().start.offset == variable.span().start.offset
+        }
+        _ => false,
+    });
+    if is_property {
+        return None;
+    }
+
+    let resolved = find_variable_definition(
+        &get_node_name(scope.document, variable),
+        get_offset_from_position(scope.document, position),
+        scope,
+    )?;
+
+    Some(Location::new(
+        uri.clone(),
+        get_range(resolved.definition.span(), document),
+    ))
+}
+
+fn find_function_call_definition(
+    scope: &Scope,
+    program: &Program,
+    uri: &Url,
+    document: &str,
+    position: &Position,
+    state: &State,
+) -> Option<Location> {
+    let call = scope.nodes.iter().rev().find_map(|node| match node {
+        Node::FunctionCall(call) => Some(*call),
+        _ => None,
+    })?;
+
+    let Expression::Identifier(id) = call.function.as_ref() else {
+        return None;
+    };
+    if !range_contains_position(&get_range(id, document), position) {
+        return None;
+    }
+
+    let candidates = scope
+        .names
+        .resolve_function(&get_node_name(scope.document, id));
+    find_global_definition(
+        &candidates,
+        SymbolKind::Function,
+        scope,
+        program,
+        uri,
+        state,
+    )
+}
+
+fn find_constant_definition(
+    scope: &Scope,
+    program: &Program,
+    uri: &Url,
+    document: &str,
+    position: &Position,
+    state: &State,
+) -> Option<Location> {
+    let access = scope.nodes.iter().rev().find_map(|node| match node {
+        Node::ConstantAccess(access) => Some(*access),
+        _ => None,
+    })?;
+    if !range_contains_position(&get_range(&access.name, document), position) {
+        return None;
+    }
+
+    let candidates = scope
+        .names
+        .resolve_constant(&get_node_name(scope.document, &access.name));
+    find_global_definition(
+        &candidates,
+        SymbolKind::Constant,
+        scope,
+        program,
+        uri,
+        state,
+    )
+}
+
+/// Looks up the first candidate name declared either in the current document
+/// or in an indexed file.
+fn find_global_definition(
+    candidates: &[String],
+    kind: SymbolKind,
+    scope: &Scope,
+    program: &Program,
+    uri: &Url,
+    state: &State,
+) -> Option<Location> {
+    // the current document may not be indexed or may have unsaved changes
+    let symbols = collect_symbols(program, scope.document, uri);
+
+    candidates.iter().find_map(|fqn| {
+        let declared = symbols.iter().find(|symbol| {
+            symbol.kind == kind
+                && match kind {
+                    // function names are case insensitive
+                    SymbolKind::Function => symbol.fqn.eq_ignore_ascii_case(fqn),
+                    _ => symbol.fqn == *fqn,
+                }
+        });
+
+        match declared {
+            Some(symbol) => Some(symbol.location.clone()),
+            None if kind == SymbolKind::Function => find_function_location(fqn, state),
+            None => find_constant_location(fqn, state),
+        }
+    })
+}
+
+fn find_method_call_definition(
+    scope: &Scope,
+    document: &str,
+    position: &Position,
+    state: &State,
+) -> Option<Location> {
+    let (receiver, method) = scope.nodes.iter().rev().find_map(|node| {
+        let (receiver, method) = match node {
+            Node::MethodCall(call) => (&call.object, &call.method),
+            Node::NullSafeMethodCall(call) => (&call.object, &call.method),
+            Node::StaticMethodCall(call) => (&call.class, &call.method),
+            Node::MethodClosureCreation(creation) => (&creation.object, &creation.method),
+            Node::StaticMethodClosureCreation(creation) => (&creation.class, &creation.method),
+            _ => return None,
+        };
+
+        match method {
+            ClassLikeMemberSelector::Identifier(method)
+                if range_contains_position(&get_range(method, document), position) =>
+            {
+                Some((receiver, method))
+            }
+            _ => None,
+        }
+    })?;
+
+    let name = get_node_name(scope.document, method);
+    resolve_expression_classes(receiver, scope, state)
+        .iter()
+        .find_map(|class| find_member(class, &name, MemberKind::Method, state))
+        .map(|found| found.location())
+}
+
+fn find_property_access_definition(
+    scope: &Scope,
+    document: &str,
+    position: &Position,
+    state: &State,
+) -> Option<Location> {
+    let (receiver, name) = scope.nodes.iter().rev().find_map(|node| {
+        let (receiver, property) = match node {
+            Node::PropertyAccess(access) => (&access.object, &access.property),
+            Node::NullSafePropertyAccess(access) => (&access.object, &access.property),
+            Node::StaticPropertyAccess(access) => {
+                return match &access.property {
+                    Variable::Direct(property)
+                        if range_contains_position(&get_range(property, document), position) =>
+                    {
+                        Some((&access.class, get_node_name(scope.document, property)))
+                    }
+                    _ => None,
+                };
+            }
+            _ => return None,
+        };
+
+        match property {
+            ClassLikeMemberSelector::Identifier(property)
+                if range_contains_position(&get_range(property, document), position) =>
+            {
+                Some((receiver, get_node_name(scope.document, property)))
+            }
+            _ => None,
+        }
+    })?;
+
+    let name = name.trim_start_matches('$');
+    resolve_expression_classes(receiver, scope, state)
+        .iter()
+        .find_map(|class| find_member(class, name, MemberKind::Property, state))
+        .map(|found| found.location())
+}
+
+fn find_class_constant_definition(
+    scope: &Scope,
+    document: &str,
+    position: &Position,
+    state: &State,
+) -> Option<Location> {
+    let (class, constant) = scope.nodes.iter().rev().find_map(|node| match node {
+        Node::ClassConstantAccess(access) => match &access.constant {
+            ClassLikeConstantSelector::Identifier(constant)
+                if range_contains_position(&get_range(constant, document), position) =>
+            {
+                Some((&access.class, constant))
+            }
+            _ => None,
+        },
+        _ => None,
+    })?;
+
+    let name = get_node_name(scope.document, constant);
+    // `Foo::class` is the name of the class rather than a constant
+    if name.eq_ignore_ascii_case("class") {
+        return None;
+    }
+
+    resolve_expression_classes(class, scope, state)
+        .iter()
+        .find_map(|class| find_member(class, &name, MemberKind::Constant, state))
+        .map(|found| found.location())
+}
+
+fn find_class_location(fqn: &str, state: &State, parser: &RwLock<Parser>) -> Option<Location> {
+    let path = resolve_class_path(fqn, state)?;
+    if !state.symbol_index.contains_key(&path) {
+        index_path(Path::new(&path), state);
+    }
+
+    get_symbol_location(&path, fqn, state).or_else(|| {
+        get_named_type_declaration_location(
+            Path::new(&path),
+            fqn.split('\\').next_back().unwrap(),
+            parser,
+        )
+    })
+}
+
+fn get_current_namespace(document: &str, tree: &Tree) -> String {
+    let query = namespace_definition_query().expect("to create query");
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(&query, tree.root_node(), document.as_bytes());
+
+    while let Some(match_) = matches.next() {
+        if let Some(capture) = match_.captures.first() {
+            return capture
+                .node
+                .utf8_text(document.as_bytes())
+                .expect("to get namespace")
+                .to_string();
+        }
+    }
+
+    String::new()
+}
+
 //TODO move to analyzer crate
 fn get_named_type_declaration_location(
     path: &Path,
@@ -385,19 +787,15 @@ fn get_named_type_declaration_location(
         return None;
     }
 
-    let content = std::fs::read_to_string(path).expect("to read destination file");
-
-    let tree = parser
-        .write()
-        .unwrap()
-        .parse(content.as_str())
-        .expect("to parse file");
+    // the class map can point at files that were deleted since
+    let content = std::fs::read_to_string(path).ok()?;
+    let tree = parser.write().unwrap().parse(content.as_str())?;
 
     if let Some(location) = capture_named_type_location(
         &named_type_declaration_query().expect("to create query"),
         name,
         &tree,
-        content.as_bytes(),
+        &content,
         path,
     ) {
         return Some(location);
@@ -410,22 +808,24 @@ fn capture_named_type_location(
     query: &Query,
     name: &str,
     tree: &Tree,
-    content: &[u8],
+    content: &str,
     path: &Path,
 ) -> Option<Location> {
     let mut cursor = QueryCursor::new();
-    let mut matches = cursor.matches(query, tree.root_node(), content);
+    let mut matches = cursor.matches(query, tree.root_node(), content.as_bytes());
 
     while let Some(match_) = matches.next() {
         for capture in match_.captures {
             let node = capture.node;
-            let node_text = node.utf8_text(content).expect("to get class name");
+            let node_text = node
+                .utf8_text(content.as_bytes())
+                .expect("to get class name");
             if node_text == name {
                 return Some(Location::new(
                     Url::from_file_path(path).unwrap(),
                     tower_lsp::lsp_types::Range::new(
-                        get_position_from_point(&node.start_position()),
-                        get_position_from_point(&node.end_position()),
+                        get_position_from_offset(content, node.start_byte()),
+                        get_position_from_offset(content, node.end_byte()),
                     ),
                 ));
             }
@@ -435,65 +835,67 @@ fn capture_named_type_location(
     None
 }
 
+/// Finds the last declaration of the variable before it in the innermost
+/// function-like around it, or in the file when there is none.
 fn find_variable_declaration(
     current_node: &tree_sitter::Node,
     document: &str,
     uri: &Url,
-    tree: &tree_sitter::Tree,
 ) -> Option<Location> {
-    // not sure if this is the correct approach
-    // we try to find every occurence of the variable name
-    // and then we pick the closest location to our current node
-    // maybe we should just look at the scope where the current node
-    // is located and based on that determine where the variable name is declared
-    // but this kinda works so fuck it
-
-    let var_declare_query =
-        variable_declaration_query().expect("to create variable declaration query");
-
-    let var_name = current_node
+    let query = variable_declaration_query().expect("to create variable declaration query");
+    let name = current_node
         .utf8_text(document.as_bytes())
         .expect("to get current variable name");
 
-    let locations =
-        match_variable_locations_for_query(var_name, &var_declare_query, tree, document, uri);
-
-    find_nearest_location(
-        get_position_from_point(&current_node.start_position()),
-        locations,
-    )
-}
-
-fn match_variable_locations_for_query(
-    var_name: &str,
-    query: &Query,
-    tree: &Tree,
-    document: &str,
-    uri: &Url,
-) -> Vec<Location> {
-    let mut out = Vec::new();
-    let mut cursor = QueryCursor::new();
-
-    let mut matches = cursor.matches(query, tree.root_node(), document.as_bytes());
-
-    while let Some(match_) = matches.next() {
-        for capture in match_.captures {
-            let declare_var_name = capture
-                .node
-                .utf8_text(document.as_bytes())
-                .expect("a text")
-                .trim_start_matches('$');
+    let mut scope = get_function_like_parent(current_node);
+    loop {
+        let root = scope.unwrap_or_else(|| get_root_node(current_node));
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&query, root, document.as_bytes());
+
+        let mut last = None;
+        while let Some(match_) = matches.next() {
+            for capture in match_.captures {
+                let node = capture.node;
+                let declared_name = node
+                    .utf8_text(document.as_bytes())
+                    .expect("a text")
+                    .trim_start_matches('$');
+
+                if declared_name == name
+                    && node.start_byte() <= current_node.start_byte()
+                    && get_function_like_parent(&node) == scope
+                {
+                    last = Some(node);
+                }
+            }
+        }
 
-            if declare_var_name == var_name {
-                let range = tower_lsp::lsp_types::Range::new(
-                    get_position_from_point(&capture.node.start_position()),
-                    get_position_from_point(&capture.node.end_position()),
-                );
+        if let Some(node) = last {
+            return Some(Location::new(
+                uri.clone(),
+                tower_lsp::lsp_types::Range::new(
+                    get_position_from_offset(document, node.start_byte()),
+                    get_position_from_offset(document, node.end_byte()),
+                ),
+            ));
+        }
 
-                out.push(Location::new(uri.clone(), range))
+        // arrow functions see the variables of the scope they are declared in
+        match scope {
+            Some(node) if node.kind() == "arrow_function" => {
+                scope = get_function_like_parent(&node)
             }
+            _ => return None,
         }
     }
+}
+
+fn get_root_node<'a>(node: &tree_sitter::Node<'a>) -> tree_sitter::Node<'a> {
+    let mut root = *node;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
 
-    out
+    root
 }
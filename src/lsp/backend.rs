@@ -1,19 +1,27 @@
-use crate::analyzer::composer::load_autoload_class_map;
+use crate::analyzer::cache::save_index_cache;
 use crate::analyzer::parser::Parser;
-use crate::handlers::notification::handle_did_open;
-use crate::handlers::request::handle_go_to_definition;
-use std::sync::RwLock;
+use crate::handlers::notification::{
+    handle_did_change, handle_did_change_watched_files, handle_did_close, handle_did_open,
+    handle_did_save,
+};
+use crate::handlers::request::{
+    handle_completion, handle_go_to_definition, handle_hover, handle_implementation,
+    handle_references, handle_signature_help,
+};
+use std::sync::{Arc, RwLock};
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::Client;
 use tower_lsp::LanguageServer;
+use tracing::warn;
 
+use super::indexing::index_in_background;
 use super::state::State;
 
 pub struct Backend {
     pub client: Client,
-    pub parser: RwLock<Parser>,
-    pub state: State,
+    pub parser: Arc<RwLock<Parser>>,
+    pub state: Arc<State>,
 }
 
 #[tower_lsp::async_trait]
@@ -23,14 +31,13 @@ impl LanguageServer for Backend {
             let mut guard = self.state.root_path.write().unwrap();
             *guard = String::from(root_uri.path());
         }
-        load_autoload_class_map(&self.parser, &self.state);
 
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Options(
                     TextDocumentSyncOptions {
                         open_close: Some(true),
-                        change: Some(TextDocumentSyncKind::FULL), // we probably want incremental?
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
                         save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
                             include_text: Some(true),
                         })),
@@ -39,13 +46,66 @@ impl LanguageServer for Backend {
                     },
                 )),
                 definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec![
+                        String::from(">"),
+                        String::from(":"),
+                        String::from("\\"),
+                        String::from("$"),
+                    ]),
+                    ..Default::default()
+                }),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec![String::from("("), String::from(",")]),
+                    ..Default::default()
+                }),
                 ..Default::default()
             },
             ..Default::default()
         })
     }
 
+    async fn initialized(&self, _: InitializedParams) {
+        let watchers = [
+            "**/*.php",
+            "**/composer.json",
+            "**/composer.lock",
+            "**/vendor/composer/autoload_*.php",
+        ]
+        .into_iter()
+        .map(|pattern| FileSystemWatcher {
+            glob_pattern: GlobPattern::String(pattern.to_string()),
+            kind: None,
+        })
+        .collect();
+
+        let registration = Registration {
+            id: String::from("pherris-watched-files"),
+            method: String::from("workspace/didChangeWatchedFiles"),
+            register_options: serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+                watchers,
+            })
+            .ok(),
+        };
+
+        if let Err(err) = self.client.register_capability(vec![registration]).await {
+            warn!("failed to register file watchers: {}", err);
+        }
+
+        tokio::spawn(index_in_background(
+            self.client.clone(),
+            Arc::clone(&self.parser),
+            Arc::clone(&self.state),
+        ));
+    }
+
     async fn shutdown(&self) -> Result<()> {
+        if let Err(err) = save_index_cache(&self.state) {
+            warn!("failed to write the index cache: {}", err);
+        }
         Ok(())
     }
 
@@ -59,6 +119,10 @@ impl LanguageServer for Backend {
         .await
     }
 
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        handle_did_change(&params, &self.state, &self.client, &self.parser).await
+    }
+
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
@@ -71,5 +135,77 @@ impl LanguageServer for Backend {
         ))
     }
 
-    async fn did_save(&self, _params: DidSaveTextDocumentParams) {}
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        // every file of the workspace may have to be read and parsed
+        let state = Arc::clone(&self.state);
+        let task = tokio::task::spawn_blocking(move || {
+            handle_references(
+                &params.text_document_position.text_document.uri,
+                &params.text_document_position.position,
+                params.context.include_declaration,
+                &state,
+            )
+        });
+
+        Ok(task.await.unwrap_or_else(|err| {
+            warn!("finding references failed: {}", err);
+            None
+        }))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        Ok(handle_hover(
+            &params.text_document_position_params.text_document.uri,
+            &params.text_document_position_params.position,
+            &self.state,
+        ))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        Ok(handle_completion(
+            &params.text_document_position.text_document.uri,
+            &params.text_document_position.position,
+            &self.state,
+        ))
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        Ok(handle_signature_help(
+            &params.text_document_position_params.text_document.uri,
+            &params.text_document_position_params.position,
+            &self.state,
+        ))
+    }
+
+    async fn goto_implementation(
+        &self,
+        params: request::GotoImplementationParams,
+    ) -> Result<Option<request::GotoImplementationResponse>> {
+        Ok(handle_implementation(
+            &params.text_document_position_params.text_document.uri,
+            &params.text_document_position_params.position,
+            &self.state,
+        ))
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        handle_did_save(&params, &self.state)
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        handle_did_close(&params, &self.state, &self.client).await
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        // reindexing and reloading the autoload maps reads and parses files
+        let state = Arc::clone(&self.state);
+        let parser = Arc::clone(&self.parser);
+        let task = tokio::task::spawn_blocking(move || {
+            handle_did_change_watched_files(&params, &state, &parser)
+        });
+
+        if let Err(err) = task.await {
+            warn!("handling the changed files failed: {}", err);
+        }
+    }
 }
@@ -0,0 +1,108 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use tower_lsp::{
+    lsp_types::{
+        notification::Progress, request::WorkDoneProgressCreate, NumberOrString, ProgressParams,
+        ProgressParamsValue, WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressCreateParams,
+        WorkDoneProgressEnd, WorkDoneProgressReport,
+    },
+    Client,
+};
+use tracing::warn;
+
+use crate::analyzer::{
+    cache::{load_index_cache, save_index_cache},
+    composer::{load_autoload_class_map, load_autoload_files, load_autoload_prefixes},
+    indexer::index_project_files,
+    parser::Parser,
+};
+
+use super::state::State;
+
+const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Loads the cache from the previous run, the composer autoload maps, the
+/// classes of the workspace and the files composer always includes, then
+/// writes the index back to the cache.
+pub fn load_workspace(parser: &RwLock<Parser>, state: &State) {
+    load_index_cache(state);
+    load_autoload_class_map(parser, state);
+    index_project_files(state);
+    load_autoload_prefixes(parser, state);
+    load_autoload_files(parser, state);
+
+    if let Err(err) = save_index_cache(state) {
+        warn!("failed to write the index cache: {}", err);
+    }
+}
+
+/// Loads the workspace on a blocking thread and reports how many files were
+/// indexed through `$/progress`. Requests arriving meanwhile are answered from
+/// whatever has been indexed so far.
+pub async fn index_in_background(client: Client, parser: Arc<RwLock<Parser>>, state: Arc<State>) {
+    let token = NumberOrString::String(String::from("pherris/indexing"));
+    let report_progress = client
+        .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+            token: token.clone(),
+        })
+        .await
+        .is_ok();
+
+    let send = |progress: WorkDoneProgress| {
+        let client = client.clone();
+        let token = token.clone();
+        async move {
+            if report_progress {
+                client
+                    .send_notification::<Progress>(ProgressParams {
+                        token,
+                        value: ProgressParamsValue::WorkDone(progress),
+                    })
+                    .await;
+            }
+        }
+    };
+
+    send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+        title: String::from("Indexing"),
+        cancellable: Some(false),
+        message: None,
+        percentage: Some(0),
+    }))
+    .await;
+
+    let mut task = tokio::task::spawn_blocking({
+        let state = Arc::clone(&state);
+        move || load_workspace(&parser, &state)
+    });
+    let mut interval = tokio::time::interval(REPORT_INTERVAL);
+
+    loop {
+        tokio::select! {
+            result = &mut task => {
+                if let Err(err) = result {
+                    warn!("indexing the workspace failed: {}", err);
+                }
+                break;
+            }
+            _ = interval.tick() => {
+                let (indexed, total) = state.index_progress.get();
+                send(WorkDoneProgress::Report(WorkDoneProgressReport {
+                    cancellable: Some(false),
+                    message: Some(format!("{}/{} files", indexed, total)),
+                    percentage: (total > 0).then(|| (indexed * 100 / total) as u32),
+                }))
+                .await;
+            }
+        }
+    }
+
+    let (indexed, _) = state.index_progress.get();
+    send(WorkDoneProgress::End(WorkDoneProgressEnd {
+        message: Some(format!("Indexed {} files", indexed)),
+    }))
+    .await;
+}
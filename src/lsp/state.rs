@@ -5,12 +5,31 @@ use mago_ast::Program;
 use tower_lsp::lsp_types::Url;
 use tree_sitter::Tree;
 
+use crate::analyzer::cache::{FileStamp, IndexCache};
+use crate::analyzer::indexer::{IndexProgress, Symbol};
+use crate::analyzer::namespaces::NamespaceTrie;
+
 pub struct State {
     pub document_program: DashMap<Url, Program>,
     pub document_map: DashMap<Url, String>,
     pub root_path: RwLock<String>,
     pub class_map: DashMap<String, String>,
+    /// The namespaces of the classes in the class map, kept in step with it.
+    pub namespaces: RwLock<NamespaceTrie>,
     pub ast_map: DashMap<Url, Tree>,
+    /// The files functions are declared in, keyed by the lowercased fully
+    /// qualified name since function names are case insensitive.
+    pub function_map: DashMap<String, String>,
+    pub constant_map: DashMap<String, String>,
+    pub symbol_index: DashMap<String, Vec<Symbol>>,
+    /// The class-likes directly extending or implementing a class-like, keyed by
+    /// the lowercased fully qualified name of the parent.
+    pub subtypes: DashMap<String, Vec<String>>,
+    pub psr4_map: DashMap<String, Vec<String>>,
+    pub psr0_map: DashMap<String, Vec<String>>,
+    pub file_stamps: DashMap<String, FileStamp>,
+    pub index_cache: RwLock<IndexCache>,
+    pub index_progress: IndexProgress,
 }
 
 impl Default for State {
@@ -37,8 +56,18 @@ impl State {
             document_program,
             document_map,
             root_path,
+            namespaces: RwLock::new(NamespaceTrie::from_class_map(&class_map)),
             class_map,
             ast_map,
+            function_map: DashMap::default(),
+            constant_map: DashMap::default(),
+            symbol_index: DashMap::default(),
+            subtypes: DashMap::default(),
+            psr4_map: DashMap::default(),
+            psr0_map: DashMap::default(),
+            file_stamps: DashMap::default(),
+            index_cache: RwLock::default(),
+            index_progress: IndexProgress::default(),
         }
     }
 }
@@ -1,7 +1,7 @@
 use pherris::analyzer::parser::Parser;
 use pherris::lsp::backend::Backend;
 use pherris::lsp::state::State;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use tower_lsp::{LspService, Server};
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::fmt::Subscriber;
@@ -23,8 +23,8 @@ async fn main() {
     let stdout = tokio::io::stdout();
     let (service, socket) = LspService::new(|client| Backend {
         client,
-        parser: RwLock::new(Parser::new().unwrap()),
-        state: State::default(),
+        parser: Arc::new(RwLock::new(Parser::new().unwrap())),
+        state: Arc::new(State::default()),
     });
     Server::new(stdin, stdout, socket).serve(service).await;
 }
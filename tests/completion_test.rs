@@ -0,0 +1,486 @@
+use dashmap::DashMap;
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use pherris::analyzer::parser::Parser;
+use pherris::handlers::request::handle_completion;
+use pherris::lsp::state::State;
+use std::io::Write;
+use std::path::Path;
+use std::sync::RwLock;
+use tempfile::TempDir;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionResponse, CompletionTextEdit, Position, Range,
+    TextEdit, Url,
+};
+
+const MODEL: &str = r#"<?php
+        namespace App\Models;
+
+        abstract class Model
+        {
+            public static string $table = '';
+
+            protected int $id = 0;
+
+            private array $attributes = [];
+
+            public function save(): bool
+            {
+                return true;
+            }
+
+            public static function find(int $id): ?static
+            {
+                return null;
+            }
+
+            protected function boot(): void
+            {
+            }
+
+            private function fill(array $attributes): void
+            {
+            }
+        }
+    "#;
+
+const USER: &str = r#"<?php
+        namespace App\Models;
+
+        class User extends Model
+        {
+            public const ROLE = 'user';
+
+            public string $name = '';
+
+            private string $password = '';
+
+            public function rename(string $name, bool $notify = false): static
+            {
+                return $this;
+            }
+
+            private function hash(): string
+            {
+                return '';
+            }
+        }
+    "#;
+
+const STATUS: &str = r#"<?php
+        namespace App\Models;
+
+        enum Status: string
+        {
+            case Active = 'active';
+            case Banned = 'banned';
+
+            public const DEFAULT = self::Active;
+        }
+    "#;
+
+#[test]
+fn test_complete_instance_members() {
+    let document = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+
+        function show(User $user)
+        {
+            $user->
+        }
+    "#;
+    let (state, _temp_dir, target_uri) = setup_test_environment("src/Http/show.php", document);
+
+    let items = complete(&target_uri, &Position::new(7, 19), &state);
+
+    assert_eq!(labels(&items), vec!["name", "rename", "save"]);
+    let rename = find_item(&items, "rename");
+    assert_eq!(rename.kind, Some(CompletionItemKind::METHOD));
+    assert_eq!(
+        rename.detail.as_deref(),
+        Some("public function rename(string $name, bool $notify = false): static")
+    );
+    assert_eq!(
+        find_item(&items, "name").detail.as_deref(),
+        Some("public string $name")
+    );
+}
+
+#[test]
+fn test_complete_static_members() {
+    let document = r#"<?php
+        namespace App\Http;
+
+        use App\Models\Status;
+        use App\Models\User;
+
+        function show()
+        {
+            $role = User::R;
+            $status = Status::A;
+        }
+    "#;
+    let (state, _temp_dir, target_uri) = setup_test_environment("src/Http/show.php", document);
+
+    let items = complete(&target_uri, &Position::new(8, 27), &state);
+    assert_eq!(labels(&items), vec!["$table", "ROLE", "find"]);
+    assert_eq!(
+        find_item(&items, "find").detail.as_deref(),
+        Some("public static function find(int $id): static|null")
+    );
+
+    let items = complete(&target_uri, &Position::new(9, 31), &state);
+    assert_eq!(labels(&items), vec!["Active", "Banned", "DEFAULT"]);
+    assert_eq!(
+        find_item(&items, "Active").kind,
+        Some(CompletionItemKind::ENUM_MEMBER)
+    );
+}
+
+#[test]
+fn test_complete_members_visible_from_the_class() {
+    let document = r#"<?php
+        namespace App\Models;
+
+        class Admin extends User
+        {
+            private bool $isRoot = false;
+
+            public function promote(): void
+            {
+                $this->i;
+                parent::b();
+            }
+        }
+    "#;
+    let (state, _temp_dir, target_uri) = setup_test_environment("src/Models/Admin.php", document);
+
+    // private members of the parents stay hidden
+    let items = complete(&target_uri, &Position::new(9, 24), &state);
+    assert_eq!(
+        labels(&items),
+        vec!["boot", "id", "isRoot", "name", "promote", "rename", "save"]
+    );
+
+    // `parent::` calls instance methods as well
+    let items = complete(&target_uri, &Position::new(10, 25), &state);
+    assert_eq!(
+        labels(&items),
+        vec!["$table", "ROLE", "boot", "find", "rename", "save"]
+    );
+}
+
+#[test]
+fn test_complete_class_names_with_imports() {
+    let document = r#"<?php
+        namespace App\Http;
+
+        use App\Models\Post;
+        use Illuminate\Foundation\User;
+
+        function show(Post $post)
+        {
+            $user = new Us
+        }
+    "#;
+    let (state, temp_dir, target_uri) = setup_test_environment("src/Http/show.php", document);
+    add_classes(&state, &temp_dir);
+
+    let items = complete(&target_uri, &Position::new(8, 26), &state);
+
+    assert_eq!(labels(&items), vec!["ModelsUser", "User"]);
+    let user = find_item(&items, "User");
+    assert_eq!(user.detail.as_deref(), Some("Illuminate\\Foundation\\User"));
+    assert_eq!(user.additional_text_edits, None);
+
+    // the short name is taken by the imported class
+    let aliased = find_item(&items, "ModelsUser");
+    assert_eq!(aliased.kind, Some(CompletionItemKind::CLASS));
+    assert_eq!(
+        aliased.additional_text_edits,
+        Some(vec![insert(
+            4,
+            8,
+            "use App\\Models\\User as ModelsUser;\n        "
+        )])
+    );
+}
+
+#[test]
+fn test_complete_class_names_in_type_hints() {
+    let document = r#"<?php
+        namespace App\Http;
+
+        function show(int $id, St)
+        {
+        }
+    "#;
+    let (state, temp_dir, target_uri) = setup_test_environment("src/Http/show.php", document);
+    add_classes(&state, &temp_dir);
+
+    let items = complete(&target_uri, &Position::new(3, 33), &state);
+
+    assert_eq!(labels(&items), vec!["Status"]);
+    assert_eq!(
+        find_item(&items, "Status").additional_text_edits,
+        Some(vec![insert(1, 27, "\n\nuse App\\Models\\Status;")])
+    );
+
+    // the name of the function is not a type
+    assert!(complete(&target_uri, &Position::new(3, 19), &state).is_empty());
+}
+
+#[test]
+fn test_complete_namespace_segments() {
+    let document = r#"<?php
+        namespace App\Http;
+
+        use App\Models\
+        use App\
+
+        function show()
+        {
+            return new \Illuminate\F
+        }
+    "#;
+    let (state, temp_dir, target_uri) = setup_test_environment("src/Http/show.php", document);
+    add_classes(&state, &temp_dir);
+
+    let items = complete(&target_uri, &Position::new(3, 23), &state);
+    assert_eq!(labels(&items), vec!["Model", "Status", "User"]);
+    assert_eq!(
+        find_item(&items, "User").detail.as_deref(),
+        Some("App\\Models\\User")
+    );
+
+    let items = complete(&target_uri, &Position::new(4, 16), &state);
+    assert_eq!(labels(&items), vec!["Models"]);
+    assert_eq!(
+        find_item(&items, "Models").kind,
+        Some(CompletionItemKind::MODULE)
+    );
+
+    let items = complete(&target_uri, &Position::new(8, 36), &state);
+    assert_eq!(labels(&items), vec!["Foundation"]);
+}
+
+#[test]
+fn test_complete_namespace_declaration() {
+    let document = r#"<?php
+        namespace App\
+
+        final class Kernel
+        {
+        }
+    "#;
+    let (state, temp_dir, target_uri) = setup_test_environment("src/Http/Kernel.php", document);
+    add_classes(&state, &temp_dir);
+
+    let items = complete(&target_uri, &Position::new(1, 22), &state);
+
+    // classes are not namespaces
+    assert_eq!(labels(&items), vec!["App\\Http", "Models"]);
+    let expected = find_item(&items, "App\\Http");
+    assert_eq!(expected.preselect, Some(true));
+    assert_eq!(
+        expected.text_edit,
+        Some(CompletionTextEdit::Edit(TextEdit {
+            range: Range::new(Position::new(1, 18), Position::new(1, 22)),
+            new_text: String::from("App\\Http"),
+        }))
+    );
+}
+
+#[test]
+fn test_complete_variables_in_scope() {
+    let document = r#"<?php
+        namespace App\Models;
+
+        class Admin extends User
+        {
+            public function promote(array $roles, ?Model $model): void
+            {
+                $count = 0;
+                foreach ($roles as $key => $role) {
+                }
+                $callback = function (string $reason) use ($count) {
+                    return $reason;
+                };
+                $
+                $later = true;
+            }
+        }
+    "#;
+    let (state, _temp_dir, target_uri) = setup_test_environment("src/Models/Admin.php", document);
+
+    let items = complete(&target_uri, &Position::new(13, 17), &state);
+
+    let expected = vec![
+        "$GLOBALS",
+        "$_COOKIE",
+        "$_ENV",
+        "$_FILES",
+        "$_GET",
+        "$_POST",
+        "$_REQUEST",
+        "$_SERVER",
+        "$_SESSION",
+        "$callback",
+        "$count",
+        "$key",
+        "$model",
+        "$role",
+        "$roles",
+        "$this",
+    ];
+    assert_eq!(labels(&items), expected);
+
+    for (name, r#type) in [
+        ("$count", "int"),
+        ("$model", "App\\Models\\Model|null"),
+        ("$this", "App\\Models\\Admin"),
+        ("$callback", "Closure"),
+        ("$_GET", "array"),
+    ] {
+        assert_eq!(find_item(&items, name).detail.as_deref(), Some(r#type));
+    }
+    assert_eq!(
+        find_item(&items, "$count").text_edit,
+        Some(CompletionTextEdit::Edit(TextEdit {
+            range: Range::new(Position::new(13, 16), Position::new(13, 17)),
+            new_text: String::from("$count"),
+        }))
+    );
+}
+
+#[test]
+fn test_complete_variables_of_closures() {
+    let document = r#"<?php
+        namespace App\Models;
+
+        function promote(array $roles, int $count)
+        {
+            $callback = function (string $reason) use ($count) {
+                $filter = fn (Model $model) => $r
+            };
+        }
+    "#;
+    let (state, _temp_dir, target_uri) = setup_test_environment("src/Models/promote.php", document);
+
+    // arrow functions see the variables around them
+    let items = complete(&target_uri, &Position::new(6, 49), &state);
+
+    assert_eq!(labels(&items), vec!["$reason"]);
+    assert_eq!(
+        find_item(&items, "$reason").detail.as_deref(),
+        Some("string")
+    );
+
+    let items = complete(&target_uri, &Position::new(6, 48), &state);
+    let labels = labels(&items);
+    assert!(labels.contains(&"$model") && labels.contains(&"$count"));
+    assert!(!labels.contains(&"$roles") && !labels.contains(&"$this"));
+}
+
+fn add_classes(state: &State, temp_dir: &TempDir) {
+    for (fqn, path) in [
+        ("App\\Models\\Model", "src/Models/Model.php"),
+        ("App\\Models\\User", "src/Models/User.php"),
+        ("App\\Models\\Status", "src/Models/Status.php"),
+        (
+            "Illuminate\\Foundation\\User",
+            "vendor/Illuminate/Foundation/User.php",
+        ),
+    ] {
+        let path = temp_dir.path().join(path);
+        state
+            .class_map
+            .insert(fqn.to_string(), path.to_str().unwrap().to_string());
+        state.namespaces.write().unwrap().insert(fqn);
+    }
+}
+
+fn insert(line: u32, character: u32, new_text: &str) -> TextEdit {
+    let position = Position::new(line, character);
+    TextEdit {
+        range: Range::new(position, position),
+        new_text: new_text.to_string(),
+    }
+}
+
+fn complete(uri: &Url, position: &Position, state: &State) -> Vec<CompletionItem> {
+    match handle_completion(uri, position, state) {
+        Some(CompletionResponse::Array(items)) => items,
+        Some(CompletionResponse::List(list)) => list.items,
+        None => vec![],
+    }
+}
+
+fn labels(items: &[CompletionItem]) -> Vec<&str> {
+    let mut labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+    labels.sort();
+
+    labels
+}
+
+fn find_item<'a>(items: &'a [CompletionItem], label: &str) -> &'a CompletionItem {
+    items
+        .iter()
+        .find(|item| item.label == label)
+        .unwrap_or_else(|| panic!("expected a completion item for {}", label))
+}
+
+fn setup_test_environment(path: &str, document: &str) -> (State, TempDir, Url) {
+    let temp_dir = TempDir::new().expect("to initialize temp dir");
+    let target_path = &temp_dir.path().join(path);
+    let target_uri = Url::from_file_path(target_path).unwrap();
+
+    let mut parser = Parser::new().expect("to create a parser");
+    let tree = parser.parse(document).expect("to parse file");
+
+    let ast_map = DashMap::new();
+    ast_map.insert(target_uri.clone(), tree);
+    let document_map = DashMap::new();
+    document_map.insert(target_uri.clone(), String::from(document));
+
+    let interner = ThreadedInterner::new();
+    let input = Input::new(SourceIdentifier::dummy(), document.as_bytes());
+    let (program, _) = parse(&interner, input);
+    let document_program = DashMap::new();
+    document_program.insert(target_uri.clone(), program);
+
+    let state = State::new(
+        document_program,
+        document_map,
+        RwLock::new(String::from(temp_dir.path().to_str().unwrap())),
+        DashMap::new(),
+        ast_map,
+    );
+    state.psr4_map.insert(
+        String::from("App\\"),
+        vec![format!("{}/src", temp_dir.path().to_str().unwrap())],
+    );
+
+    for (file_path, file_content) in [
+        (path, document),
+        ("src/Models/Model.php", MODEL),
+        ("src/Models/User.php", USER),
+        ("src/Models/Status.php", STATUS),
+    ] {
+        let full_path = temp_dir.path().join(file_path);
+        std::fs::create_dir_all(full_path.parent().unwrap()).expect("to create directory");
+        prepare_php_file(&full_path, file_content);
+    }
+
+    (state, temp_dir, target_uri)
+}
+
+fn prepare_php_file(file_path: &Path, file_contents: &str) {
+    let mut file = std::fs::File::create(file_path).expect("to create file");
+    file.write_all(file_contents.as_bytes())
+        .expect("to write to file");
+}
@@ -0,0 +1,245 @@
+use pherris::analyzer::parser::Parser;
+use pherris::handlers::notification::{
+    apply_document_changes, close_document, handle_did_save, update_document,
+};
+use pherris::handlers::request::handle_go_to_definition;
+use pherris::lsp::state::State;
+use std::io::Write;
+use std::path::Path;
+use std::sync::RwLock;
+use tempfile::TempDir;
+use tower_lsp::lsp_types::{
+    DidSaveTextDocumentParams, GotoDefinitionResponse, Position, Range,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier, Url,
+};
+
+#[test]
+fn test_update_document_replaces_stale_document() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        r#"<?php
+        $variable = 0;
+        echo $variable;
+    "#,
+    );
+
+    update_document(
+        &target_uri,
+        String::from(
+            r#"<?php
+        $obj = new MyClass();
+    "#,
+        ),
+        &state,
+        &parser_lock,
+    );
+
+    let class_content = r#"<?php
+        namespace MyApp\Testing;
+
+        class MyClass {}
+    "#;
+    let class_path = format!("{}/{}", temp_dir.path().to_str().unwrap(), "class.php");
+    prepare_php_file(Path::new(&class_path), class_content);
+
+    assert_eq!(
+        state.document_map.get(&target_uri).unwrap().as_str(),
+        "<?php\n        $obj = new MyClass();\n    "
+    );
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(1, 19), &state, &parser_lock);
+
+    let class_uri = Url::from_file_path(Path::new(&class_path)).unwrap();
+    assert_definition_response(response, &class_uri, 3, 14);
+}
+
+#[test]
+fn test_update_document_reports_and_clears_parse_errors() {
+    let (state, _temp_dir, target_uri, parser_lock) = setup_test_environment("<?php\n");
+
+    let diags = update_document(
+        &target_uri,
+        String::from("<?php\n$variable = ;\n"),
+        &state,
+        &parser_lock,
+    );
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].range.start.line, 1);
+
+    let diags = update_document(
+        &target_uri,
+        String::from("<?php\n$variable = 1;\n"),
+        &state,
+        &parser_lock,
+    );
+    assert!(diags.is_empty());
+}
+
+#[test]
+fn test_apply_incremental_changes() {
+    let (state, _temp_dir, target_uri, parser_lock) = setup_test_environment(
+        r#"<?php
+        $variable = 0;
+        echo $variable;
+    "#,
+    );
+
+    apply_document_changes(
+        &target_uri,
+        &[
+            range_change((1, 8), (1, 17), "$renamed"),
+            range_change((2, 13), (2, 22), "$renamed"),
+            range_change((2, 21), (2, 21), " . 'ěšč'"),
+            range_change((2, 26), (2, 27), "x"),
+        ],
+        &state,
+        &parser_lock,
+    );
+
+    let expected = "<?php\n        $renamed = 0;\n        echo $renamed . 'ěxč';\n    ";
+    assert_eq!(
+        state.document_map.get(&target_uri).unwrap().as_str(),
+        expected
+    );
+
+    let fresh_tree = parser_lock.write().unwrap().parse(expected).unwrap();
+    assert_eq!(
+        state
+            .ast_map
+            .get(&target_uri)
+            .unwrap()
+            .root_node()
+            .to_sexp(),
+        fresh_tree.root_node().to_sexp()
+    );
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(2, 15), &state, &parser_lock);
+
+    assert_definition_response(response, &target_uri, 1, 8);
+}
+
+#[test]
+fn test_apply_full_change_after_range_change() {
+    let (state, _temp_dir, target_uri, parser_lock) = setup_test_environment("<?php\n");
+
+    apply_document_changes(
+        &target_uri,
+        &[
+            range_change((1, 0), (1, 0), "$a = 1;"),
+            TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: String::from("<?php\n$b = 2;\n"),
+            },
+            range_change((1, 1), (1, 2), "c"),
+        ],
+        &state,
+        &parser_lock,
+    );
+
+    assert_eq!(
+        state.document_map.get(&target_uri).unwrap().as_str(),
+        "<?php\n$c = 2;\n"
+    );
+}
+
+#[test]
+fn test_close_document_releases_document() {
+    let (state, _temp_dir, target_uri, _parser_lock) = setup_test_environment("<?php\n");
+
+    close_document(&target_uri, &state);
+
+    assert!(state.document_map.get(&target_uri).is_none());
+    assert!(state.document_program.get(&target_uri).is_none());
+    assert!(state.ast_map.get(&target_uri).is_none());
+}
+
+#[test]
+fn test_save_reindexes_class_locations() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        r#"<?php
+        use MyApp\Testing\MyClass;
+        $obj = new MyClass();
+    "#,
+    );
+
+    let class_path = format!("{}/{}", temp_dir.path().to_str().unwrap(), "src/class.php");
+    std::fs::create_dir_all(temp_dir.path().join("src")).expect("to create directory");
+    prepare_php_file(
+        Path::new(&class_path),
+        "<?php\nnamespace MyApp\\Old;\n\nclass MyClass {}\n",
+    );
+    state
+        .class_map
+        .insert(String::from("MyApp\\Old\\MyClass"), class_path.clone());
+
+    let class_uri = Url::from_file_path(Path::new(&class_path)).unwrap();
+    let saved_content = "<?php\nnamespace MyApp\\Testing;\n\nclass MyClass {}\n";
+    prepare_php_file(Path::new(&class_path), saved_content);
+    handle_did_save(
+        &DidSaveTextDocumentParams {
+            text_document: TextDocumentIdentifier::new(class_uri.clone()),
+            text: Some(String::from(saved_content)),
+        },
+        &state,
+    );
+
+    assert!(state.class_map.get("MyApp\\Old\\MyClass").is_none());
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(2, 19), &state, &parser_lock);
+
+    assert_definition_response(response, &class_uri, 3, 6);
+}
+
+fn range_change(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+    TextDocumentContentChangeEvent {
+        range: Some(Range::new(
+            Position::new(start.0, start.1),
+            Position::new(end.0, end.1),
+        )),
+        range_length: None,
+        text: String::from(text),
+    }
+}
+
+fn setup_test_environment(main_content: &str) -> (State, TempDir, Url, RwLock<Parser>) {
+    let temp_dir = TempDir::new().expect("to initialize temp dir");
+    let path_str = format!("{}/{}", temp_dir.path().to_str().unwrap(), "test.php");
+    let target_uri = Url::from_file_path(Path::new(&path_str)).unwrap();
+    prepare_php_file(Path::new(&path_str), main_content);
+
+    let state = State::default();
+    let parser_lock = RwLock::new(Parser::new().expect("to create a parser"));
+    update_document(
+        &target_uri,
+        String::from(main_content),
+        &state,
+        &parser_lock,
+    );
+
+    (state, temp_dir, target_uri, parser_lock)
+}
+
+fn assert_definition_response(
+    response: Option<GotoDefinitionResponse>,
+    expected_uri: &Url,
+    expected_line: u32,
+    expected_character: u32,
+) {
+    assert!(response.is_some());
+    if let GotoDefinitionResponse::Scalar(location) = response.unwrap() {
+        assert_eq!(location.uri.as_str(), expected_uri.as_str());
+        assert_eq!(location.range.start.line, expected_line);
+        assert_eq!(location.range.start.character, expected_character);
+    } else {
+        panic!("response is not a location");
+    }
+}
+
+fn prepare_php_file(file_path: &Path, file_contents: &str) {
+    let mut file = std::fs::File::create(file_path).expect("to create file");
+    file.write_all(file_contents.as_bytes())
+        .expect("to write to file");
+}
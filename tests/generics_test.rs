@@ -0,0 +1,221 @@
+use dashmap::DashMap;
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use pherris::analyzer::inference::type_at;
+use pherris::analyzer::parser::Parser;
+use pherris::analyzer::types::format_types;
+use pherris::handlers::request::handle_go_to_definition;
+use pherris::lsp::state::State;
+use std::io::Write;
+use std::path::Path;
+use std::sync::RwLock;
+use tempfile::TempDir;
+use tower_lsp::lsp_types::{GotoDefinitionResponse, Position, Url};
+
+const TEST: &str = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+        use App\Models\UserCollection;
+
+        /**
+         * @param list<User> $list
+         * @param mixed $iterable
+         * @phpstan-param iterable<int, User> $iterable
+         */
+        function show(UserCollection $users, array $list, $iterable): void
+        {
+            foreach ($users as $id => $user) {
+                $user->getName();
+            }
+            $first = $users->first();
+            foreach ($list as $item) {
+                echo $item;
+            }
+            foreach ($iterable as $entry) {
+                echo $entry;
+            }
+            $element = $list[0];
+            $all = $users->all();
+        }
+    "#;
+
+const COLLECTION: &str = r#"<?php
+        namespace App\Models;
+
+        /**
+         * @template TKey of array-key
+         * @template TValue
+         * @implements \IteratorAggregate<TKey, TValue>
+         */
+        abstract class Collection implements \IteratorAggregate
+        {
+            /**
+             * @return TValue|null
+             */
+            public function first()
+            {
+                return null;
+            }
+
+            /**
+             * @return array<TKey, TValue>
+             * @psalm-return list<TValue>
+             */
+            public function all(): array
+            {
+                return [];
+            }
+        }
+    "#;
+
+const USER_COLLECTION: &str = r#"<?php
+        namespace App\Models;
+
+        /**
+         * @extends Collection<int, User>
+         */
+        class UserCollection extends Collection
+        {
+        }
+    "#;
+
+const USER: &str = r#"<?php
+        namespace App\Models;
+
+        class User
+        {
+            public function getName(): string
+            {
+                return '';
+            }
+        }
+    "#;
+
+#[test]
+fn test_foreach_over_generic_collection() {
+    let (state, _temp_dir, target_uri, _) = setup_test_environment();
+
+    assert_type(&state, &target_uri, Position::new(13, 32), "int");
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(13, 39),
+        "App\\Models\\User",
+    );
+}
+
+#[test]
+fn test_template_return_types() {
+    let (state, _temp_dir, target_uri, _) = setup_test_environment();
+
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(16, 14),
+        "App\\Models\\User|null",
+    );
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(24, 14),
+        "array<int, App\\Models\\User>",
+    );
+}
+
+#[test]
+fn test_list_and_iterable_element_types() {
+    let (state, _temp_dir, target_uri, _) = setup_test_environment();
+
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(18, 23),
+        "App\\Models\\User",
+    );
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(21, 23),
+        "App\\Models\\User",
+    );
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(23, 14),
+        "App\\Models\\User",
+    );
+}
+
+#[test]
+fn test_go_to_method_of_collection_element() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(14, 26), &state, &parser_lock);
+
+    let Some(GotoDefinitionResponse::Scalar(location)) = response else {
+        panic!("response is not a location");
+    };
+    let expected_uri = Url::from_file_path(temp_dir.path().join("src/Models/User.php")).unwrap();
+    assert_eq!(location.uri, expected_uri);
+    assert_eq!(location.range.start, Position::new(5, 28));
+}
+
+fn assert_type(state: &State, uri: &Url, position: Position, expected: &str) {
+    assert_eq!(format_types(&type_at(uri, &position, state)), expected);
+}
+
+fn setup_test_environment() -> (State, TempDir, Url, RwLock<Parser>) {
+    let temp_dir = TempDir::new().expect("to initialize temp dir");
+    let path_str = format!("{}/{}", temp_dir.path().to_str().unwrap(), "test.php");
+    let target_path = Path::new(&path_str);
+    let target_uri = Url::from_file_path(target_path).unwrap();
+
+    let mut parser = Parser::new().expect("to create a parser");
+    let tree = parser.parse(TEST).expect("to parse file");
+
+    let ast_map = DashMap::new();
+    ast_map.insert(target_uri.clone(), tree);
+    let document_map = DashMap::new();
+    document_map.insert(target_uri.clone(), String::from(TEST));
+
+    let interner = ThreadedInterner::new();
+    let input = Input::new(SourceIdentifier::dummy(), TEST.as_bytes());
+    let (program, _) = parse(&interner, input);
+    let document_program = DashMap::new();
+    document_program.insert(target_uri.clone(), program);
+
+    let state = State::new(
+        document_program,
+        document_map,
+        RwLock::new(String::from(temp_dir.path().to_str().unwrap())),
+        DashMap::new(),
+        ast_map,
+    );
+    state.psr4_map.insert(
+        String::from("App\\"),
+        vec![format!("{}/src", temp_dir.path().to_str().unwrap())],
+    );
+
+    prepare_php_file(target_path, TEST);
+    for (file_path, file_content) in [
+        ("src/Models/Collection.php", COLLECTION),
+        ("src/Models/UserCollection.php", USER_COLLECTION),
+        ("src/Models/User.php", USER),
+    ] {
+        let full_path = temp_dir.path().join(file_path);
+        std::fs::create_dir_all(full_path.parent().unwrap()).expect("to create directory");
+        prepare_php_file(&full_path, file_content);
+    }
+
+    (state, temp_dir, target_uri, RwLock::new(parser))
+}
+
+fn prepare_php_file(file_path: &Path, file_contents: &str) {
+    let mut file = std::fs::File::create(file_path).expect("to create file");
+    file.write_all(file_contents.as_bytes())
+        .expect("to write to file");
+}
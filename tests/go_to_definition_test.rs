@@ -3,6 +3,7 @@ use mago_interner::ThreadedInterner;
 use mago_lexer::input::Input;
 use mago_parser::parse;
 use mago_source::SourceIdentifier;
+use pherris::analyzer::indexer::index_file;
 use pherris::analyzer::parser::Parser;
 use pherris::handlers::request::handle_go_to_definition;
 use pherris::lsp::state::State;
@@ -118,6 +119,168 @@ fn test_find_variable_definition_in_foreach_pair() {
     assert_definition_response(response, &target_uri, 2, 26);
 }
 
+#[test]
+fn test_find_variable_declaration_in_enclosing_function_only() {
+    let main_content = r#"<?php
+        function first() {
+            $value = 1;
+        }
+
+        function second() {
+            $value = 2;
+            echo $value;
+        }
+    "#;
+
+    let (state, _temp_dir, target_uri, parser_lock) =
+        setup_test_environment(main_content, vec![], vec![]);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(7, 19), &state, &parser_lock);
+
+    assert_definition_response(response, &target_uri, 6, 12);
+}
+
+#[test]
+fn test_find_variable_declaration_before_reassignment() {
+    let main_content = r#"<?php
+        $total = 1;
+        $total = $total + 1;
+    "#;
+
+    let (state, _temp_dir, target_uri, parser_lock) =
+        setup_test_environment(main_content, vec![], vec![]);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(2, 19), &state, &parser_lock);
+
+    assert_definition_response(response, &target_uri, 1, 8);
+}
+
+#[test]
+fn test_find_variable_declaration_after_multibyte_characters() {
+    let main_content = r#"<?php
+        $city = 'Zürich'; $emoji = '😀'; $total = 1;
+        echo $city, $emoji, $total;
+    "#;
+
+    let (state, _temp_dir, target_uri, parser_lock) =
+        setup_test_environment(main_content, vec![], vec![]);
+
+    // positions count utf-16 units, `ü` takes two bytes and `😀` two units
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(2, 29), &state, &parser_lock);
+
+    assert_definition_response(response, &target_uri, 1, 41);
+}
+
+#[test]
+fn test_find_variable_captured_by_closure() {
+    let main_content = r#"<?php
+        $factor = 2;
+        $double = function ($number) use ($factor) {
+            $unused = 1;
+            return $number * $factor;
+        };
+        $triple = function () {
+            return $factor;
+        };
+    "#;
+
+    let (state, _temp_dir, target_uri, parser_lock) =
+        setup_test_environment(main_content, vec![], vec![]);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(4, 31), &state, &parser_lock);
+    assert_definition_response(response, &target_uri, 1, 8);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(7, 21), &state, &parser_lock);
+    assert!(response.is_none());
+}
+
+#[test]
+fn test_find_global_and_static_variable_declarations() {
+    let main_content = r#"<?php
+        function counter() {
+            global $config;
+            static $calls = 0;
+            $calls++;
+            return $config;
+        }
+    "#;
+
+    let (state, _temp_dir, target_uri, parser_lock) =
+        setup_test_environment(main_content, vec![], vec![]);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(4, 14), &state, &parser_lock);
+    assert_definition_response(response, &target_uri, 3, 19);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(5, 21), &state, &parser_lock);
+    assert_definition_response(response, &target_uri, 2, 19);
+}
+
+#[test]
+fn test_find_catch_variable_declaration() {
+    let main_content = r#"<?php
+        try {
+        } catch (Exception $exception) {
+            echo $exception;
+        }
+    "#;
+
+    let (state, _temp_dir, target_uri, parser_lock) =
+        setup_test_environment(main_content, vec![], vec![]);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(3, 20), &state, &parser_lock);
+
+    assert_definition_response(response, &target_uri, 2, 27);
+}
+
+#[test]
+fn test_find_destructured_variable_declarations() {
+    let main_content = r#"<?php
+        [$first, [$second]] = [1, [2]];
+        list('key' => $third) = ['key' => 3];
+        echo $second, $third;
+    "#;
+
+    let (state, _temp_dir, target_uri, parser_lock) =
+        setup_test_environment(main_content, vec![], vec![]);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(3, 15), &state, &parser_lock);
+    assert_definition_response(response, &target_uri, 1, 18);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(3, 24), &state, &parser_lock);
+    assert_definition_response(response, &target_uri, 2, 22);
+}
+
+#[test]
+fn test_find_this_declaration() {
+    let main_content = r#"<?php
+        class Counter
+        {
+            public function increment()
+            {
+                return $this;
+            }
+        }
+    "#;
+
+    let (state, _temp_dir, target_uri, parser_lock) =
+        setup_test_environment(main_content, vec![], vec![]);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(5, 25), &state, &parser_lock);
+
+    assert_definition_response(response, &target_uri, 1, 14);
+}
+
 #[test]
 fn test_find_class_definition_in_same_folder() {
     let main_content = r#"<?php
@@ -642,6 +805,535 @@ fn test_go_to_class_definition_on_static_method_call_statement() {
     assert_definition_response(response, &class_uri, 3, 14);
 }
 
+#[test]
+fn test_find_class_definition_using_psr4_prefix() {
+    let main_content = r#"<?php
+        use MyApp\Testing\MyClass;
+        $obj = new MyClass();
+    "#;
+
+    let class_content = r#"<?php
+        namespace MyApp\Testing;
+
+        class MyClass {}
+    "#;
+
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![("src/Testing/MyClass.php", class_content)],
+        vec![],
+    );
+    state.psr4_map.insert(
+        String::from("MyApp\\"),
+        vec![format!("{}/src", temp_dir.path().to_str().unwrap())],
+    );
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(2, 19), &state, &parser_lock);
+
+    let class_path = format!(
+        "{}/{}",
+        temp_dir.path().to_str().unwrap(),
+        "src/Testing/MyClass.php"
+    );
+    let class_uri = Url::from_file_path(Path::new(&class_path)).unwrap();
+    assert_definition_response(response, &class_uri, 3, 14);
+}
+
+#[test]
+fn test_find_class_definition_in_deleted_file() {
+    let main_content = r#"<?php
+        use MyApp\Testing\Removed;
+        $obj = new Removed();
+    "#;
+
+    let (state, _temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![],
+        vec![("MyApp\\Testing\\Removed", "src/Testing/Removed.php")],
+    );
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(2, 19), &state, &parser_lock);
+
+    assert!(response.is_none());
+}
+
+#[test]
+fn test_go_to_definition_in_closed_document() {
+    let (state, _temp_dir, target_uri, parser_lock) =
+        setup_test_environment("<?php\n$variable = 0;\n", vec![], vec![]);
+    state.document_map.remove(&target_uri);
+    state.document_program.remove(&target_uri);
+    state.ast_map.remove(&target_uri);
+
+    let response = handle_go_to_definition(&target_uri, &Position::new(1, 2), &state, &parser_lock);
+
+    assert!(response.is_none());
+}
+
+#[test]
+fn test_find_class_definition_in_current_namespace_using_psr4_prefix() {
+    let main_content = r#"<?php
+        namespace MyApp\Controllers;
+
+        $obj = new Models\MyClass();
+    "#;
+
+    let class_content = r#"<?php
+        namespace MyApp\Controllers\Models;
+
+        class MyClass {}
+    "#;
+
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![("src/Controllers/Models/MyClass.php", class_content)],
+        vec![],
+    );
+    state.psr4_map.insert(
+        String::from("MyApp\\"),
+        vec![format!("{}/src", temp_dir.path().to_str().unwrap())],
+    );
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(3, 25), &state, &parser_lock);
+
+    let class_path = format!(
+        "{}/{}",
+        temp_dir.path().to_str().unwrap(),
+        "src/Controllers/Models/MyClass.php"
+    );
+    let class_uri = Url::from_file_path(Path::new(&class_path)).unwrap();
+    assert_definition_response(response, &class_uri, 3, 14);
+}
+
+#[test]
+fn test_find_global_function_definition_from_namespace() {
+    let main_content = r#"<?php
+        namespace MyApp\Controllers;
+
+        echo array_first([1, 2]);
+    "#;
+
+    let helpers_content = r#"<?php
+        if (!function_exists('array_first')) {
+            function array_first($array) {}
+        }
+    "#;
+
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![("vendor/helpers.php", helpers_content)],
+        vec![],
+    );
+    let helpers_path = format!(
+        "{}/{}",
+        temp_dir.path().to_str().unwrap(),
+        "vendor/helpers.php"
+    );
+    state
+        .function_map
+        .insert(String::from("array_first"), helpers_path.clone());
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(3, 16), &state, &parser_lock);
+
+    let helpers_uri = Url::from_file_path(Path::new(&helpers_path)).unwrap();
+    assert_definition_response(response, &helpers_uri, 2, 21);
+}
+
+#[test]
+fn test_find_qualified_function_definition() {
+    let main_content = r#"<?php
+        echo Foo\helper();
+    "#;
+
+    let helpers_content = r#"<?php
+        namespace Foo;
+
+        function helper() {}
+    "#;
+
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![("src/helpers.php", helpers_content)],
+        vec![],
+    );
+    let helpers_path = format!(
+        "{}/{}",
+        temp_dir.path().to_str().unwrap(),
+        "src/helpers.php"
+    );
+    state
+        .function_map
+        .insert(String::from("foo\\helper"), helpers_path.clone());
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(1, 19), &state, &parser_lock);
+
+    let helpers_uri = Url::from_file_path(Path::new(&helpers_path)).unwrap();
+    assert_definition_response(response, &helpers_uri, 3, 17);
+}
+
+#[test]
+fn test_find_imported_function_definition() {
+    let main_content = r#"<?php
+        namespace App;
+
+        use function Vendor\Helpers\format_date;
+
+        echo format_date();
+    "#;
+
+    let helpers_content = r#"<?php
+        namespace Vendor\Helpers;
+
+        function format_date() {}
+    "#;
+
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![("vendor/helpers.php", helpers_content)],
+        vec![],
+    );
+    let helpers_path = format!(
+        "{}/{}",
+        temp_dir.path().to_str().unwrap(),
+        "vendor/helpers.php"
+    );
+    state.function_map.insert(
+        String::from("vendor\\helpers\\format_date"),
+        helpers_path.clone(),
+    );
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(5, 16), &state, &parser_lock);
+
+    let helpers_uri = Url::from_file_path(Path::new(&helpers_path)).unwrap();
+    assert_definition_response(response, &helpers_uri, 3, 17);
+}
+
+#[test]
+fn test_namespaced_function_takes_precedence_over_global_function() {
+    let main_content = r#"<?php
+        namespace App;
+
+        echo helper();
+    "#;
+
+    let namespaced_content = r#"<?php
+        namespace App;
+
+        function helper() {}
+    "#;
+
+    let global_content = r#"<?php
+        function helper() {}
+    "#;
+
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![
+            ("src/helpers.php", namespaced_content),
+            ("vendor/helpers.php", global_content),
+        ],
+        vec![],
+    );
+    let namespaced_path = format!(
+        "{}/{}",
+        temp_dir.path().to_str().unwrap(),
+        "src/helpers.php"
+    );
+    let global_path = format!(
+        "{}/{}",
+        temp_dir.path().to_str().unwrap(),
+        "vendor/helpers.php"
+    );
+    state
+        .function_map
+        .insert(String::from("app\\helper"), namespaced_path.clone());
+    state
+        .function_map
+        .insert(String::from("helper"), global_path);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(3, 16), &state, &parser_lock);
+
+    let namespaced_uri = Url::from_file_path(Path::new(&namespaced_path)).unwrap();
+    assert_definition_response(response, &namespaced_uri, 3, 17);
+}
+
+#[test]
+fn test_function_names_are_case_insensitive() {
+    let main_content = r#"<?php
+        namespace App;
+
+        echo Array_First([]);
+        echo \App\Helper();
+    "#;
+
+    let namespaced_content = r#"<?php
+        namespace App;
+
+        function helper() {}
+    "#;
+
+    let global_content = r#"<?php
+        function array_first($array) {}
+    "#;
+
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![
+            ("src/helpers.php", namespaced_content),
+            ("vendor/helpers.php", global_content),
+        ],
+        vec![],
+    );
+    let namespaced_path = temp_dir.path().join("src/helpers.php");
+    let global_path = temp_dir.path().join("vendor/helpers.php");
+    index_file(&namespaced_path, namespaced_content, &state);
+    index_file(&global_path, global_content, &state);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(3, 16), &state, &parser_lock);
+    let global_uri = Url::from_file_path(&global_path).unwrap();
+    assert_definition_response(response, &global_uri, 1, 17);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(4, 20), &state, &parser_lock);
+    let namespaced_uri = Url::from_file_path(&namespaced_path).unwrap();
+    assert_definition_response(response, &namespaced_uri, 3, 17);
+}
+
+#[test]
+fn test_find_function_definition_in_current_document() {
+    let main_content = r#"<?php
+        function local_helper() {}
+
+        local_helper();
+    "#;
+
+    let (state, _temp_dir, target_uri, parser_lock) =
+        setup_test_environment(main_content, vec![], vec![]);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(3, 12), &state, &parser_lock);
+
+    assert_definition_response(response, &target_uri, 1, 17);
+}
+
+#[test]
+fn test_find_defined_constant_definition_from_namespace() {
+    let main_content = r#"<?php
+        namespace App;
+
+        echo APP_VERSION;
+    "#;
+
+    let constants_content = r#"<?php
+        define('APP_VERSION', '1.0');
+    "#;
+
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![("vendor/constants.php", constants_content)],
+        vec![],
+    );
+    let constants_path = format!(
+        "{}/{}",
+        temp_dir.path().to_str().unwrap(),
+        "vendor/constants.php"
+    );
+    state
+        .constant_map
+        .insert(String::from("APP_VERSION"), constants_path.clone());
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(3, 18), &state, &parser_lock);
+
+    let constants_uri = Url::from_file_path(Path::new(&constants_path)).unwrap();
+    assert_definition_response(response, &constants_uri, 1, 16);
+}
+
+#[test]
+fn test_find_imported_constant_definition() {
+    let main_content = r#"<?php
+        namespace App;
+
+        use const Vendor\Config\DEBUG;
+
+        echo DEBUG;
+    "#;
+
+    let config_content = r#"<?php
+        namespace Vendor\Config;
+
+        const DEBUG = true;
+    "#;
+
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![("vendor/config.php", config_content)],
+        vec![],
+    );
+    let config_path = format!(
+        "{}/{}",
+        temp_dir.path().to_str().unwrap(),
+        "vendor/config.php"
+    );
+    state
+        .constant_map
+        .insert(String::from("Vendor\\Config\\DEBUG"), config_path.clone());
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(5, 15), &state, &parser_lock);
+
+    let config_uri = Url::from_file_path(Path::new(&config_path)).unwrap();
+    assert_definition_response(response, &config_uri, 3, 14);
+}
+
+#[test]
+fn test_go_to_definition_from_docblock_types() {
+    let main_content = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+        use App\Exceptions\NotFoundException;
+
+        /**
+         * @mixin User
+         */
+        class Controller
+        {
+            /**
+             * @param User|null $user The user to show
+             * @return User[]
+             * @throws NotFoundException
+             */
+            public function show($user) {}
+        }
+    "#;
+
+    let user_content = r#"<?php
+        namespace App\Models;
+
+        class User
+        {
+            public function rename() {}
+        }
+    "#;
+
+    let exception_content = r#"<?php
+        namespace App\Exceptions;
+
+        class NotFoundException extends \Exception {}
+    "#;
+
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![
+            ("src/Models/User.php", user_content),
+            ("src/Exceptions/NotFoundException.php", exception_content),
+        ],
+        vec![
+            ("App\\Models\\User", "src/Models/User.php"),
+            (
+                "App\\Exceptions\\NotFoundException",
+                "src/Exceptions/NotFoundException.php",
+            ),
+        ],
+    );
+    let user_uri = Url::from_file_path(temp_dir.path().join("src/Models/User.php")).unwrap();
+    let exception_uri =
+        Url::from_file_path(temp_dir.path().join("src/Exceptions/NotFoundException.php")).unwrap();
+
+    for position in [
+        Position::new(7, 19),
+        Position::new(12, 23),
+        Position::new(13, 24),
+    ] {
+        let response = handle_go_to_definition(&target_uri, &position, &state, &parser_lock);
+        assert_definition_response(response, &user_uri, 3, 14);
+    }
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(14, 25), &state, &parser_lock);
+    assert_definition_response(response, &exception_uri, 3, 14);
+
+    // variables and descriptions are not types
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(12, 34), &state, &parser_lock);
+    assert!(response.is_none());
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(12, 42), &state, &parser_lock);
+    assert!(response.is_none());
+}
+
+#[test]
+fn test_go_to_definition_from_see_tags() {
+    let main_content = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+
+        /**
+         * @see User::rename()
+         * @see User::$name
+         * @see format_name()
+         */
+        function show() {}
+    "#;
+
+    let user_content = r#"<?php
+        namespace App\Models;
+
+        class User
+        {
+            public string $name = '';
+
+            public function rename() {}
+        }
+    "#;
+
+    let helpers_content = r#"<?php
+        function format_name() {}
+    "#;
+
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment(
+        main_content,
+        vec![
+            ("src/Models/User.php", user_content),
+            ("src/helpers.php", helpers_content),
+        ],
+        vec![("App\\Models\\User", "src/Models/User.php")],
+    );
+    let helpers_path = temp_dir.path().join("src/helpers.php");
+    state.function_map.insert(
+        String::from("format_name"),
+        helpers_path.to_str().unwrap().to_string(),
+    );
+    let user_uri = Url::from_file_path(temp_dir.path().join("src/Models/User.php")).unwrap();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(6, 24), &state, &parser_lock);
+    assert_definition_response(response, &user_uri, 7, 28);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(6, 17), &state, &parser_lock);
+    assert_definition_response(response, &user_uri, 3, 14);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(7, 24), &state, &parser_lock);
+    assert_definition_response(response, &user_uri, 5, 26);
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(8, 18), &state, &parser_lock);
+    let helpers_uri = Url::from_file_path(helpers_path).unwrap();
+    assert_definition_response(response, &helpers_uri, 1, 17);
+}
+
 fn setup_test_environment(
     main_content: &str,
     additional_files: Vec<(&str, &str)>,
@@ -0,0 +1,416 @@
+use dashmap::DashMap;
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use pherris::analyzer::parser::Parser;
+use pherris::handlers::request::handle_go_to_definition;
+use pherris::lsp::state::State;
+use std::io::Write;
+use std::path::Path;
+use std::sync::RwLock;
+use tempfile::TempDir;
+use tower_lsp::lsp_types::{GotoDefinitionResponse, Position, Url};
+
+const CONTROLLER: &str = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+
+        class Controller extends BaseController
+        {
+            public function index(User $user)
+            {
+                $user->save();
+                $other = new User();
+                $other->factory();
+                User::query();
+                $this->helper();
+                self::helper();
+                static::HELPER();
+                parent::boot();
+                $this->repository()->find();
+                $callback = fn () => $other->factory();
+            }
+
+            public function helper() {}
+
+            public function repository(): Repository {}
+
+            public function __construct(private User $user) {}
+
+            public function show()
+            {
+                $this->user->email;
+                $this->user?->connection;
+                $this->user->factoryName;
+                User::$table;
+                self::VERSION;
+                Status::Active;
+                Status::LABEL;
+            }
+        }
+    "#;
+
+const USER: &str = r#"<?php
+        namespace App\Models;
+
+        class User extends Model
+        {
+            use HasFactory;
+
+            public string $email;
+        }
+    "#;
+
+const MODEL: &str = r#"<?php
+        namespace App\Models;
+
+        abstract class Model
+        {
+            public function save() {}
+
+            public static function query() {}
+
+            protected ?string $connection = null;
+            protected static string $table;
+        }
+    "#;
+
+const HAS_FACTORY: &str = r#"<?php
+        namespace App\Models;
+
+        trait HasFactory
+        {
+            public function factory() {}
+
+            public string $factoryName;
+        }
+    "#;
+
+const BASE_CONTROLLER: &str = r#"<?php
+        namespace App\Http;
+
+        class BaseController
+        {
+            public static function boot() {}
+
+            public const VERSION = 1;
+        }
+    "#;
+
+const STATUS: &str = r#"<?php
+        namespace App\Http;
+
+        enum Status: string implements HasLabel
+        {
+            case Active = 'active';
+        }
+    "#;
+
+const HAS_LABEL: &str = r#"<?php
+        namespace App\Http;
+
+        interface HasLabel
+        {
+            const LABEL = 'label';
+        }
+    "#;
+
+const REPOSITORY: &str = r#"<?php
+        namespace App\Http;
+
+        class Repository
+        {
+            public function find() {}
+        }
+    "#;
+
+#[test]
+fn test_go_to_inherited_method_of_parameter() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(9, 25), &state, &parser_lock);
+
+    assert_definition_response(
+        response,
+        &file_uri(&temp_dir, "src/Models/Model.php"),
+        5,
+        28,
+    );
+}
+
+#[test]
+fn test_go_to_trait_method_of_instantiated_variable() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(11, 26), &state, &parser_lock);
+
+    assert_definition_response(
+        response,
+        &file_uri(&temp_dir, "src/Models/HasFactory.php"),
+        5,
+        28,
+    );
+}
+
+#[test]
+fn test_go_to_method_of_variable_captured_by_arrow_function() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(18, 47), &state, &parser_lock);
+
+    assert_definition_response(
+        response,
+        &file_uri(&temp_dir, "src/Models/HasFactory.php"),
+        5,
+        28,
+    );
+}
+
+#[test]
+fn test_go_to_static_method_of_imported_class() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(12, 24), &state, &parser_lock);
+
+    assert_definition_response(
+        response,
+        &file_uri(&temp_dir, "src/Models/Model.php"),
+        7,
+        35,
+    );
+}
+
+#[test]
+fn test_go_to_method_of_current_class() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    for position in [
+        Position::new(13, 25),
+        Position::new(14, 24),
+        Position::new(15, 26),
+    ] {
+        let response = handle_go_to_definition(&target_uri, &position, &state, &parser_lock);
+
+        assert_definition_response(response, &file_uri(&temp_dir, "test.php"), 21, 28);
+    }
+}
+
+#[test]
+fn test_go_to_method_of_parent_class() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(16, 26), &state, &parser_lock);
+
+    assert_definition_response(
+        response,
+        &file_uri(&temp_dir, "src/Http/BaseController.php"),
+        5,
+        35,
+    );
+}
+
+#[test]
+fn test_go_to_method_of_returned_object() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(17, 39), &state, &parser_lock);
+
+    assert_definition_response(
+        response,
+        &file_uri(&temp_dir, "src/Http/Repository.php"),
+        5,
+        28,
+    );
+}
+
+#[test]
+fn test_go_to_promoted_constructor_property() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(29, 24), &state, &parser_lock);
+
+    assert_definition_response(response, &file_uri(&temp_dir, "test.php"), 25, 53);
+}
+
+#[test]
+fn test_go_to_property_of_property() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(29, 31), &state, &parser_lock);
+
+    assert_definition_response(response, &file_uri(&temp_dir, "src/Models/User.php"), 7, 26);
+}
+
+#[test]
+fn test_go_to_inherited_property_through_nullsafe_access() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(30, 33), &state, &parser_lock);
+
+    assert_definition_response(
+        response,
+        &file_uri(&temp_dir, "src/Models/Model.php"),
+        9,
+        30,
+    );
+}
+
+#[test]
+fn test_go_to_trait_property() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(31, 32), &state, &parser_lock);
+
+    assert_definition_response(
+        response,
+        &file_uri(&temp_dir, "src/Models/HasFactory.php"),
+        7,
+        26,
+    );
+}
+
+#[test]
+fn test_go_to_static_property() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(32, 24), &state, &parser_lock);
+
+    assert_definition_response(
+        response,
+        &file_uri(&temp_dir, "src/Models/Model.php"),
+        10,
+        36,
+    );
+}
+
+#[test]
+fn test_go_to_inherited_class_constant() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(33, 24), &state, &parser_lock);
+
+    assert_definition_response(
+        response,
+        &file_uri(&temp_dir, "src/Http/BaseController.php"),
+        7,
+        25,
+    );
+}
+
+#[test]
+fn test_go_to_enum_case() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(34, 26), &state, &parser_lock);
+
+    assert_definition_response(response, &file_uri(&temp_dir, "src/Http/Status.php"), 5, 17);
+}
+
+#[test]
+fn test_go_to_interface_constant_of_enum() {
+    let (state, temp_dir, target_uri, parser_lock) = setup_test_environment();
+
+    let response =
+        handle_go_to_definition(&target_uri, &Position::new(35, 26), &state, &parser_lock);
+
+    assert_definition_response(
+        response,
+        &file_uri(&temp_dir, "src/Http/HasLabel.php"),
+        5,
+        18,
+    );
+}
+
+fn setup_test_environment() -> (State, TempDir, Url, RwLock<Parser>) {
+    let temp_dir = TempDir::new().expect("to initialize temp dir");
+    let path_str = format!("{}/{}", temp_dir.path().to_str().unwrap(), "test.php");
+    let target_path = Path::new(&path_str);
+    let target_uri = Url::from_file_path(target_path).unwrap();
+
+    let mut parser = Parser::new().expect("to create a parser");
+    let tree = parser.parse(CONTROLLER).expect("to parse file");
+
+    let ast_map = DashMap::new();
+    ast_map.insert(target_uri.clone(), tree);
+    let document_map = DashMap::new();
+    document_map.insert(target_uri.clone(), String::from(CONTROLLER));
+
+    let interner = ThreadedInterner::new();
+    let input = Input::new(SourceIdentifier::dummy(), CONTROLLER.as_bytes());
+    let (program, _) = parse(&interner, input);
+    let document_program = DashMap::new();
+    document_program.insert(target_uri.clone(), program);
+
+    let class_map = DashMap::new();
+    class_map.insert(String::from("App\\Http\\Controller"), path_str.clone());
+
+    let state = State::new(
+        document_program,
+        document_map,
+        RwLock::new(String::from(temp_dir.path().to_str().unwrap())),
+        class_map,
+        ast_map,
+    );
+    state.psr4_map.insert(
+        String::from("App\\"),
+        vec![format!("{}/src", temp_dir.path().to_str().unwrap())],
+    );
+
+    prepare_php_file(target_path, CONTROLLER);
+    for (file_path, file_content) in [
+        ("src/Models/User.php", USER),
+        ("src/Models/Model.php", MODEL),
+        ("src/Models/HasFactory.php", HAS_FACTORY),
+        ("src/Http/BaseController.php", BASE_CONTROLLER),
+        ("src/Http/Repository.php", REPOSITORY),
+        ("src/Http/Status.php", STATUS),
+        ("src/Http/HasLabel.php", HAS_LABEL),
+    ] {
+        let full_path = temp_dir.path().join(file_path);
+        std::fs::create_dir_all(full_path.parent().unwrap()).expect("to create directory");
+        prepare_php_file(&full_path, file_content);
+    }
+
+    (state, temp_dir, target_uri, RwLock::new(parser))
+}
+
+fn file_uri(temp_dir: &TempDir, file_path: &str) -> Url {
+    Url::from_file_path(temp_dir.path().join(file_path)).unwrap()
+}
+
+fn assert_definition_response(
+    response: Option<GotoDefinitionResponse>,
+    expected_uri: &Url,
+    expected_line: u32,
+    expected_character: u32,
+) {
+    assert!(response.is_some());
+    if let GotoDefinitionResponse::Scalar(location) = response.unwrap() {
+        assert_eq!(location.uri.as_str(), expected_uri.as_str());
+        assert_eq!(location.range.start.line, expected_line);
+        assert_eq!(location.range.start.character, expected_character);
+    } else {
+        panic!("response is not a location");
+    }
+}
+
+fn prepare_php_file(file_path: &Path, file_contents: &str) {
+    let mut file = std::fs::File::create(file_path).expect("to create file");
+    file.write_all(file_contents.as_bytes())
+        .expect("to write to file");
+}
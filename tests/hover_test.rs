@@ -0,0 +1,193 @@
+use dashmap::DashMap;
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use pherris::analyzer::parser::Parser;
+use pherris::handlers::request::handle_hover;
+use pherris::lsp::state::State;
+use std::io::Write;
+use std::path::Path;
+use std::sync::RwLock;
+use tempfile::TempDir;
+use tower_lsp::lsp_types::{Hover, HoverContents, Position, Url};
+
+const TEST: &str = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+        use function App\format_name;
+
+        function show(User $user): string
+        {
+            $user->rename('Jane');
+            echo 'Zürich', User::ROLE, $user->name;
+            return format_name($user);
+        }
+    "#;
+
+const USER: &str = r#"<?php
+        namespace App\Models;
+
+        /**
+         * A registered user.
+         */
+        #[Entity]
+        final class User extends Model implements \JsonSerializable
+        {
+            /** The role every user starts with. */
+            public const ROLE = 'user';
+
+            /** @var string The display name */
+            public string $name = '';
+
+            /**
+             * Changes the name of the user.
+             *
+             * @param string $name The new name
+             * @return static
+             */
+            public function rename(
+                string $name,
+                bool $notify = false,
+            ): static {
+                return $this;
+            }
+        }
+    "#;
+
+const HELPERS: &str = r#"<?php
+        namespace App;
+
+        function format_name(Models\User $user, string $separator = ' '): string
+        {
+            return $user->name;
+        }
+    "#;
+
+#[test]
+fn test_hover_class() {
+    let (state, _temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_hover(&target_uri, &Position::new(6, 24), &state);
+
+    assert_hover(
+        response,
+        "```php\n<?php\nnamespace App\\Models;\n\nfinal class User extends Model implements \\JsonSerializable\n```\n\nA registered user.",
+    );
+}
+
+#[test]
+fn test_hover_method() {
+    let (state, _temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_hover(&target_uri, &Position::new(8, 22), &state);
+
+    assert_hover(
+        response,
+        "```php\n<?php\npublic function rename(string $name, bool $notify = false): static\n```\n\nChanges the name of the user.\n\n*@param* `string` `$name` — The new name\n\n*@return* `static`",
+    );
+}
+
+#[test]
+fn test_hover_class_constant_and_property() {
+    let (state, _temp_dir, target_uri) = setup_test_environment();
+
+    // lsp characters count utf-16 units, `ü` is two bytes but only one unit
+    let response = handle_hover(&target_uri, &Position::new(9, 33), &state);
+    assert_hover(
+        response,
+        "```php\n<?php\npublic const ROLE = 'user'\n```\n\nThe role every user starts with.",
+    );
+
+    let response = handle_hover(&target_uri, &Position::new(9, 46), &state);
+    assert_hover(
+        response,
+        "```php\n<?php\npublic string $name = ''\n```\n\n*@var* `string` — The display name",
+    );
+}
+
+#[test]
+fn test_hover_function() {
+    let (state, _temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_hover(&target_uri, &Position::new(10, 22), &state);
+
+    assert_hover(
+        response,
+        "```php\n<?php\nnamespace App;\n\nfunction format_name(Models\\User $user, string $separator = ' '): string\n```",
+    );
+}
+
+#[test]
+fn test_hover_variable() {
+    let (state, _temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_hover(&target_uri, &Position::new(8, 14), &state);
+
+    assert_hover(response, "```php\n<?php\nApp\\Models\\User $user\n```");
+}
+
+fn setup_test_environment() -> (State, TempDir, Url) {
+    let temp_dir = TempDir::new().expect("to initialize temp dir");
+    let path_str = format!("{}/{}", temp_dir.path().to_str().unwrap(), "test.php");
+    let target_path = Path::new(&path_str);
+    let target_uri = Url::from_file_path(target_path).unwrap();
+
+    let mut parser = Parser::new().expect("to create a parser");
+    let tree = parser.parse(TEST).expect("to parse file");
+
+    let ast_map = DashMap::new();
+    ast_map.insert(target_uri.clone(), tree);
+    let document_map = DashMap::new();
+    document_map.insert(target_uri.clone(), String::from(TEST));
+
+    let interner = ThreadedInterner::new();
+    let input = Input::new(SourceIdentifier::dummy(), TEST.as_bytes());
+    let (program, _) = parse(&interner, input);
+    let document_program = DashMap::new();
+    document_program.insert(target_uri.clone(), program);
+
+    let state = State::new(
+        document_program,
+        document_map,
+        RwLock::new(String::from(temp_dir.path().to_str().unwrap())),
+        DashMap::new(),
+        ast_map,
+    );
+    state.psr4_map.insert(
+        String::from("App\\"),
+        vec![format!("{}/src", temp_dir.path().to_str().unwrap())],
+    );
+    state.function_map.insert(
+        String::from("app\\format_name"),
+        format!("{}/src/helpers.php", temp_dir.path().to_str().unwrap()),
+    );
+
+    prepare_php_file(target_path, TEST);
+    for (file_path, file_content) in [("src/Models/User.php", USER), ("src/helpers.php", HELPERS)] {
+        let full_path = temp_dir.path().join(file_path);
+        std::fs::create_dir_all(full_path.parent().unwrap()).expect("to create directory");
+        prepare_php_file(&full_path, file_content);
+    }
+
+    (state, temp_dir, target_uri)
+}
+
+fn assert_hover(response: Option<Hover>, expected: &str) {
+    let Some(Hover {
+        contents: HoverContents::Markup(content),
+        ..
+    }) = response
+    else {
+        panic!("expected a markdown hover");
+    };
+
+    assert_eq!(content.value, expected);
+}
+
+fn prepare_php_file(file_path: &Path, file_contents: &str) {
+    let mut file = std::fs::File::create(file_path).expect("to create file");
+    file.write_all(file_contents.as_bytes())
+        .expect("to write to file");
+}
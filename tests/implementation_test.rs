@@ -0,0 +1,259 @@
+use dashmap::DashMap;
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use pherris::analyzer::parser::Parser;
+use pherris::handlers::request::handle_implementation;
+use pherris::lsp::indexing::load_workspace;
+use pherris::lsp::state::State;
+use std::io::Write;
+use std::path::Path;
+use std::sync::RwLock;
+use tempfile::TempDir;
+use tower_lsp::lsp_types::{request::GotoImplementationResponse, Position, Url};
+
+// composer lists the classes of the project only when it is optimized
+const AUTOLOAD_CLASSMAP: &str = r#"<?php
+        $vendorDir = dirname(__DIR__);
+        $baseDir = dirname($vendorDir);
+
+        return array(
+            'Acme\\Client' => $vendorDir . '/acme/lib/Client.php',
+        );
+    "#;
+
+const TEST: &str = r#"<?php
+        namespace App;
+
+        use App\Models\HasName;
+        use App\Models\Model;
+
+        function describe(HasName $named, Model $model)
+        {
+            echo $named->getName();
+            $model->save();
+        }
+    "#;
+
+const HAS_NAME: &str = r#"<?php
+        namespace App\Models;
+
+        interface HasName
+        {
+            public function getName(): string;
+        }
+    "#;
+
+const MODEL: &str = r#"<?php
+        namespace App\Models;
+
+        abstract class Model implements HasName
+        {
+            abstract public function save(): void;
+
+            public function getName(): string
+            {
+                return static::class;
+            }
+        }
+    "#;
+
+const USER: &str = r#"<?php
+        namespace App\Models;
+
+        class User extends Model
+        {
+            public function save(): void {}
+        }
+    "#;
+
+const ADMIN: &str = r#"<?php
+        namespace App\Models;
+
+        final class Admin extends User
+        {
+            public function save(): void {}
+
+            public function getName(): string
+            {
+                return 'admin';
+            }
+        }
+    "#;
+
+const POST: &str = r#"<?php
+        namespace App\Models;
+
+        enum Post: string implements HasName
+        {
+            case Draft = 'draft';
+
+            public function getName(): string
+            {
+                return $this->value;
+            }
+        }
+    "#;
+
+#[test]
+fn test_find_interface_implementations() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_implementation(&target_uri, &Position::new(6, 28), &state);
+
+    assert_implementations(
+        response,
+        &temp_dir,
+        &[
+            ("src/Models/Admin.php", 3, 20),
+            ("src/Models/Model.php", 3, 23),
+            ("src/Models/Post.php", 3, 13),
+            ("src/Models/User.php", 3, 14),
+        ],
+    );
+}
+
+#[test]
+fn test_find_abstract_class_implementations() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_implementation(&target_uri, &Position::new(4, 24), &state);
+
+    assert_implementations(
+        response,
+        &temp_dir,
+        &[
+            ("src/Models/Admin.php", 3, 20),
+            ("src/Models/User.php", 3, 14),
+        ],
+    );
+}
+
+#[test]
+fn test_find_interface_method_implementations() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_implementation(&target_uri, &Position::new(8, 26), &state);
+
+    assert_implementations(
+        response,
+        &temp_dir,
+        &[
+            ("src/Models/Admin.php", 7, 28),
+            ("src/Models/Model.php", 7, 28),
+            ("src/Models/Post.php", 7, 28),
+        ],
+    );
+}
+
+#[test]
+fn test_find_abstract_method_implementations() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_implementation(&target_uri, &Position::new(9, 21), &state);
+
+    assert_implementations(
+        response,
+        &temp_dir,
+        &[
+            ("src/Models/Admin.php", 5, 28),
+            ("src/Models/User.php", 5, 28),
+        ],
+    );
+}
+
+fn setup_test_environment() -> (State, TempDir, Url) {
+    let temp_dir = TempDir::new().expect("to initialize temp dir");
+    let path_str = format!("{}/{}", temp_dir.path().to_str().unwrap(), "test.php");
+    let target_path = Path::new(&path_str);
+    let target_uri = Url::from_file_path(target_path).unwrap();
+
+    let mut parser = Parser::new().expect("to create a parser");
+    let tree = parser.parse(TEST).expect("to parse file");
+
+    let ast_map = DashMap::new();
+    ast_map.insert(target_uri.clone(), tree);
+    let document_map = DashMap::new();
+    document_map.insert(target_uri.clone(), String::from(TEST));
+
+    let interner = ThreadedInterner::new();
+    let input = Input::new(SourceIdentifier::dummy(), TEST.as_bytes());
+    let (program, _) = parse(&interner, input);
+    let document_program = DashMap::new();
+    document_program.insert(target_uri.clone(), program);
+
+    let state = State::new(
+        document_program,
+        document_map,
+        RwLock::new(String::from(temp_dir.path().to_str().unwrap())),
+        DashMap::new(),
+        ast_map,
+    );
+    state.psr4_map.insert(
+        String::from("App\\"),
+        vec![format!("{}/src", temp_dir.path().to_str().unwrap())],
+    );
+    prepare_php_file(target_path, TEST);
+    for (file_path, file_content) in [
+        ("src/Models/Model.php", MODEL),
+        ("src/Models/User.php", USER),
+        ("src/Models/Admin.php", ADMIN),
+        ("src/Models/Post.php", POST),
+        ("src/Models/HasName.php", HAS_NAME),
+        ("vendor/composer/autoload_classmap.php", AUTOLOAD_CLASSMAP),
+    ] {
+        let full_path = temp_dir.path().join(file_path);
+        std::fs::create_dir_all(full_path.parent().unwrap()).expect("to create directory");
+        prepare_php_file(&full_path, file_content);
+    }
+
+    // keep the index cache of the test workspace out of the user's cache
+    std::env::set_var("XDG_CACHE_HOME", std::env::temp_dir().join("pherris-tests"));
+    load_workspace(&RwLock::new(parser), &state);
+    assert!(state.class_map.get("App\\Models\\User").is_some());
+    assert!(state.class_map.get("Acme\\Client").is_some());
+
+    (state, temp_dir, target_uri)
+}
+
+fn assert_implementations(
+    response: Option<GotoImplementationResponse>,
+    temp_dir: &TempDir,
+    expected: &[(&str, u32, u32)],
+) {
+    let Some(GotoImplementationResponse::Array(locations)) = response else {
+        panic!("expected an array of locations");
+    };
+
+    let mut locations = locations
+        .into_iter()
+        .map(|location| {
+            let path = location.uri.to_file_path().unwrap();
+            let relative = path
+                .strip_prefix(temp_dir.path())
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string();
+            (
+                relative,
+                location.range.start.line,
+                location.range.start.character,
+            )
+        })
+        .collect::<Vec<_>>();
+    locations.sort();
+
+    let expected = expected
+        .iter()
+        .map(|(path, line, character)| (path.to_string(), *line, *character))
+        .collect::<Vec<_>>();
+    assert_eq!(locations, expected);
+}
+
+fn prepare_php_file(file_path: &Path, file_contents: &str) {
+    let mut file = std::fs::File::create(file_path).expect("to create file");
+    file.write_all(file_contents.as_bytes())
+        .expect("to write to file");
+}
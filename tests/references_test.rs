@@ -0,0 +1,295 @@
+use dashmap::DashMap;
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use pherris::analyzer::parser::Parser;
+use pherris::handlers::request::handle_references;
+use pherris::lsp::state::State;
+use std::io::Write;
+use std::path::Path;
+use std::sync::RwLock;
+use tempfile::TempDir;
+use tower_lsp::lsp_types::{Location, Position, Url};
+
+const TEST: &str = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+
+        function helper(User $user): User
+        {
+            $user->greet();
+            $user->save();
+            echo $user->name, User::ROLE;
+            $copy = new User();
+            $copy->greet();
+            return $copy;
+        }
+
+        function other($user)
+        {
+            return $user;
+        }
+    "#;
+
+const USER: &str = r#"<?php
+        namespace App\Models;
+
+        class User extends Model
+        {
+            public const ROLE = 'user';
+            public string $name;
+
+            public function greet(): string
+            {
+                return $this->name;
+            }
+        }
+    "#;
+
+const MODEL: &str = r#"<?php
+        namespace App\Models;
+
+        abstract class Model
+        {
+            public function save() {}
+        }
+    "#;
+
+const BOOTSTRAP: &str = r#"<?php
+        use function App\Http\helper;
+
+        helper(new App\Models\User());
+    "#;
+
+#[test]
+fn test_find_class_references() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_references(&target_uri, &Position::new(10, 24), true, &state);
+
+    assert_references(
+        response,
+        &temp_dir,
+        &[
+            ("src/Models/User.php", 3, 14),
+            ("src/bootstrap.php", 3, 19),
+            ("test.php", 3, 12),
+            ("test.php", 5, 24),
+            ("test.php", 5, 37),
+            ("test.php", 9, 30),
+            ("test.php", 10, 24),
+        ],
+    );
+}
+
+#[test]
+fn test_find_method_references() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_references(&target_uri, &Position::new(7, 20), true, &state);
+    assert_references(
+        response,
+        &temp_dir,
+        &[
+            ("src/Models/User.php", 8, 28),
+            ("test.php", 7, 19),
+            ("test.php", 11, 19),
+        ],
+    );
+
+    let response = handle_references(&target_uri, &Position::new(7, 20), false, &state);
+    assert_references(
+        response,
+        &temp_dir,
+        &[("test.php", 7, 19), ("test.php", 11, 19)],
+    );
+}
+
+#[test]
+fn test_find_inherited_method_references() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_references(&target_uri, &Position::new(8, 20), true, &state);
+
+    assert_references(
+        response,
+        &temp_dir,
+        &[("src/Models/Model.php", 5, 28), ("test.php", 8, 19)],
+    );
+}
+
+#[test]
+fn test_find_property_references() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_references(&target_uri, &Position::new(9, 25), true, &state);
+
+    assert_references(
+        response,
+        &temp_dir,
+        &[
+            ("src/Models/User.php", 6, 26),
+            ("src/Models/User.php", 10, 30),
+            ("test.php", 9, 24),
+        ],
+    );
+}
+
+#[test]
+fn test_find_class_constant_references() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_references(&target_uri, &Position::new(9, 37), true, &state);
+
+    assert_references(
+        response,
+        &temp_dir,
+        &[("src/Models/User.php", 5, 25), ("test.php", 9, 36)],
+    );
+}
+
+#[test]
+fn test_find_function_references() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_references(&target_uri, &Position::new(5, 18), true, &state);
+
+    assert_references(
+        response,
+        &temp_dir,
+        &[
+            ("src/bootstrap.php", 1, 21),
+            ("src/bootstrap.php", 3, 8),
+            ("test.php", 5, 17),
+        ],
+    );
+}
+
+#[test]
+fn test_find_references_in_open_documents_without_workspace() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+    *state.root_path.write().unwrap() = String::new();
+
+    let response = handle_references(&target_uri, &Position::new(7, 20), true, &state);
+
+    assert_references(
+        response,
+        &temp_dir,
+        &[("test.php", 7, 19), ("test.php", 11, 19)],
+    );
+}
+
+#[test]
+fn test_find_variable_references_in_enclosing_function() {
+    let (state, temp_dir, target_uri) = setup_test_environment();
+
+    let response = handle_references(&target_uri, &Position::new(9, 18), true, &state);
+    assert_references(
+        response,
+        &temp_dir,
+        &[
+            ("test.php", 5, 29),
+            ("test.php", 7, 12),
+            ("test.php", 8, 12),
+            ("test.php", 9, 17),
+        ],
+    );
+
+    let response = handle_references(&target_uri, &Position::new(12, 20), false, &state);
+    assert_references(
+        response,
+        &temp_dir,
+        &[("test.php", 11, 12), ("test.php", 12, 19)],
+    );
+}
+
+fn setup_test_environment() -> (State, TempDir, Url) {
+    let temp_dir = TempDir::new().expect("to initialize temp dir");
+    let path_str = format!("{}/{}", temp_dir.path().to_str().unwrap(), "test.php");
+    let target_path = Path::new(&path_str);
+    let target_uri = Url::from_file_path(target_path).unwrap();
+
+    let mut parser = Parser::new().expect("to create a parser");
+    let tree = parser.parse(TEST).expect("to parse file");
+
+    let ast_map = DashMap::new();
+    ast_map.insert(target_uri.clone(), tree);
+    let document_map = DashMap::new();
+    document_map.insert(target_uri.clone(), String::from(TEST));
+
+    let interner = ThreadedInterner::new();
+    let input = Input::new(SourceIdentifier::dummy(), TEST.as_bytes());
+    let (program, _) = parse(&interner, input);
+    let document_program = DashMap::new();
+    document_program.insert(target_uri.clone(), program);
+
+    let state = State::new(
+        document_program,
+        document_map,
+        RwLock::new(String::from(temp_dir.path().to_str().unwrap())),
+        DashMap::new(),
+        ast_map,
+    );
+    state.psr4_map.insert(
+        String::from("App\\"),
+        vec![format!("{}/src", temp_dir.path().to_str().unwrap())],
+    );
+    state
+        .function_map
+        .insert(String::from("app\\http\\helper"), path_str.clone());
+
+    prepare_php_file(target_path, TEST);
+    for (file_path, file_content) in [
+        ("src/Models/User.php", USER),
+        ("src/Models/Model.php", MODEL),
+        ("src/bootstrap.php", BOOTSTRAP),
+    ] {
+        let full_path = temp_dir.path().join(file_path);
+        std::fs::create_dir_all(full_path.parent().unwrap()).expect("to create directory");
+        prepare_php_file(&full_path, file_content);
+    }
+
+    (state, temp_dir, target_uri)
+}
+
+fn assert_references(
+    response: Option<Vec<Location>>,
+    temp_dir: &TempDir,
+    expected: &[(&str, u32, u32)],
+) {
+    assert!(response.is_some());
+
+    let mut locations = response
+        .unwrap()
+        .into_iter()
+        .map(|location| {
+            let path = location.uri.to_file_path().unwrap();
+            let relative = path
+                .strip_prefix(temp_dir.path())
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string();
+            (
+                relative,
+                location.range.start.line,
+                location.range.start.character,
+            )
+        })
+        .collect::<Vec<_>>();
+    locations.sort();
+
+    let expected = expected
+        .iter()
+        .map(|(path, line, character)| (path.to_string(), *line, *character))
+        .collect::<Vec<_>>();
+    assert_eq!(locations, expected);
+}
+
+fn prepare_php_file(file_path: &Path, file_contents: &str) {
+    let mut file = std::fs::File::create(file_path).expect("to create file");
+    file.write_all(file_contents.as_bytes())
+        .expect("to write to file");
+}
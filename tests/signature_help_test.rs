@@ -0,0 +1,282 @@
+use dashmap::DashMap;
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use pherris::analyzer::parser::Parser;
+use pherris::handlers::request::handle_signature_help;
+use pherris::lsp::state::State;
+use std::io::Write;
+use std::path::Path;
+use std::sync::RwLock;
+use tempfile::TempDir;
+use tower_lsp::lsp_types::{
+    Documentation, MarkupContent, MarkupKind, ParameterLabel, Position, SignatureHelp,
+    SignatureInformation, Url,
+};
+
+const USER: &str = r#"<?php
+        namespace App\Models;
+
+        class User
+        {
+            /**
+             * @param string $name The name shown to other users
+             * @param string|null $email
+             */
+            public function __construct(string $name, ?string $email = null)
+            {
+            }
+
+            /**
+             * Renames the user.
+             *
+             * @param string $name The new name
+             * @param bool $notify Whether to send an email about it
+             */
+            public function rename(string $name, bool $notify = false): static
+            {
+                return $this;
+            }
+
+            public static function create(array $attributes = [], bool $save = true): self
+            {
+                return new self('');
+            }
+        }
+    "#;
+
+const HELPERS: &str = r#"<?php
+        namespace App;
+
+        /**
+         * @param string $separator The text put between the names
+         * @param string ...$names
+         */
+        function join_names(string $separator, string ...$names): string
+        {
+            return implode($separator, $names);
+        }
+    "#;
+
+#[test]
+fn test_signature_of_functions() {
+    let document = r#"<?php
+        namespace App\Http;
+
+        use function App\join_names;
+
+        function show(array $names)
+        {
+            $title = join_names(', ', 'Jane', );
+        }
+    "#;
+    let (state, _temp_dir, target_uri) = setup_test_environment(document);
+
+    let help = signature_help(&target_uri, &Position::new(7, 45), &state);
+
+    let signature = &help.signatures[0];
+    assert_eq!(
+        signature.label,
+        "join_names(string $separator, string ...$names): string"
+    );
+    assert_eq!(
+        parameter_labels(signature),
+        vec!["string $separator", "string ...$names"]
+    );
+    assert_eq!(
+        parameter_documentation(signature),
+        vec![Some("The text put between the names"), None]
+    );
+    // every argument after the first one goes to the variadic parameter
+    assert_eq!(help.active_parameter, Some(1));
+}
+
+#[test]
+fn test_signature_of_methods() {
+    let document = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+
+        function show(User $user)
+        {
+            $user->rename('Jane',
+        }
+    "#;
+    let (state, _temp_dir, target_uri) = setup_test_environment(document);
+
+    let help = signature_help(&target_uri, &Position::new(7, 34), &state);
+
+    let signature = &help.signatures[0];
+    assert_eq!(
+        signature.label,
+        "rename(string $name, bool $notify = false): static"
+    );
+    assert_eq!(
+        parameter_documentation(signature),
+        vec![
+            Some("The new name"),
+            Some("Whether to send an email about it")
+        ]
+    );
+    assert_eq!(
+        signature.documentation,
+        Some(Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: String::from("Renames the user."),
+        }))
+    );
+    assert_eq!(help.active_parameter, Some(1));
+}
+
+#[test]
+fn test_signature_of_constructors_and_static_methods() {
+    let document = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+
+        function show()
+        {
+            $user = new User(
+        }
+    "#;
+    let (state, _temp_dir, target_uri) = setup_test_environment(document);
+
+    let help = signature_help(&target_uri, &Position::new(7, 29), &state);
+
+    let signature = &help.signatures[0];
+    assert_eq!(signature.label, "User(string $name, ?string $email = null)");
+    assert_eq!(
+        parameter_documentation(signature),
+        vec![Some("The name shown to other users"), None]
+    );
+    assert_eq!(help.active_parameter, Some(0));
+
+    let document = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+
+        function show()
+        {
+            $user = User::create([1, 2],
+        }
+    "#;
+    let (state, _temp_dir, target_uri) = setup_test_environment(document);
+
+    let help = signature_help(&target_uri, &Position::new(7, 41), &state);
+
+    assert_eq!(
+        help.signatures[0].label,
+        "create(array $attributes = [], bool $save = true): self"
+    );
+    assert_eq!(help.active_parameter, Some(1));
+}
+
+#[test]
+fn test_signature_of_named_arguments() {
+    let document = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+
+        function show(User $user)
+        {
+            $user->rename(notify: true, name:
+        }
+    "#;
+    let (state, _temp_dir, target_uri) = setup_test_environment(document);
+
+    let help = signature_help(&target_uri, &Position::new(7, 46), &state);
+
+    assert_eq!(help.active_parameter, Some(0));
+    assert!(handle_signature_help(&target_uri, &Position::new(7, 12), &state).is_none());
+}
+
+fn signature_help(uri: &Url, position: &Position, state: &State) -> SignatureHelp {
+    handle_signature_help(uri, position, state).expect("to find the signature")
+}
+
+fn parameter_labels(signature: &SignatureInformation) -> Vec<&str> {
+    signature
+        .parameters
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|parameter| match parameter.label {
+            ParameterLabel::LabelOffsets([start, end]) => {
+                &signature.label[start as usize..end as usize]
+            }
+            ParameterLabel::Simple(ref label) => label.as_str(),
+        })
+        .collect()
+}
+
+fn parameter_documentation(signature: &SignatureInformation) -> Vec<Option<&str>> {
+    signature
+        .parameters
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|parameter| match &parameter.documentation {
+            Some(Documentation::String(documentation)) => Some(documentation.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn setup_test_environment(document: &str) -> (State, TempDir, Url) {
+    let temp_dir = TempDir::new().expect("to initialize temp dir");
+    let target_path = &temp_dir.path().join("src/Http/show.php");
+    let target_uri = Url::from_file_path(target_path).unwrap();
+
+    let mut parser = Parser::new().expect("to create a parser");
+    let tree = parser.parse(document).expect("to parse file");
+
+    let ast_map = DashMap::new();
+    ast_map.insert(target_uri.clone(), tree);
+    let document_map = DashMap::new();
+    document_map.insert(target_uri.clone(), String::from(document));
+
+    let interner = ThreadedInterner::new();
+    let input = Input::new(SourceIdentifier::dummy(), document.as_bytes());
+    let (program, _) = parse(&interner, input);
+    let document_program = DashMap::new();
+    document_program.insert(target_uri.clone(), program);
+
+    let state = State::new(
+        document_program,
+        document_map,
+        RwLock::new(String::from(temp_dir.path().to_str().unwrap())),
+        DashMap::new(),
+        ast_map,
+    );
+    state.psr4_map.insert(
+        String::from("App\\"),
+        vec![format!("{}/src", temp_dir.path().to_str().unwrap())],
+    );
+    state.function_map.insert(
+        String::from("app\\join_names"),
+        format!("{}/src/helpers.php", temp_dir.path().to_str().unwrap()),
+    );
+
+    for (file_path, file_content) in [
+        ("src/Http/show.php", document),
+        ("src/Models/User.php", USER),
+        ("src/helpers.php", HELPERS),
+    ] {
+        let full_path = temp_dir.path().join(file_path);
+        std::fs::create_dir_all(full_path.parent().unwrap()).expect("to create directory");
+        prepare_php_file(&full_path, file_content);
+    }
+
+    (state, temp_dir, target_uri)
+}
+
+fn prepare_php_file(file_path: &Path, file_contents: &str) {
+    let mut file = std::fs::File::create(file_path).expect("to create file");
+    file.write_all(file_contents.as_bytes())
+        .expect("to write to file");
+}
@@ -0,0 +1,213 @@
+use dashmap::DashMap;
+use mago_interner::ThreadedInterner;
+use mago_lexer::input::Input;
+use mago_parser::parse;
+use mago_source::SourceIdentifier;
+use pherris::analyzer::inference::type_at;
+use pherris::analyzer::parser::Parser;
+use pherris::analyzer::types::format_types;
+use pherris::lsp::state::State;
+use std::io::Write;
+use std::path::Path;
+use std::sync::RwLock;
+use tempfile::TempDir;
+use tower_lsp::lsp_types::{Position, Url};
+
+const TEST: &str = r#"<?php
+        namespace App\Http;
+
+        use App\Models\User;
+        use App\Models\Repository;
+
+        function show(Repository $repo, $id): void
+        {
+            $owner = $repo->find(1)->getOwner();
+            $user = $repo->find($id) ?? new User();
+            /** @var User|null $maybe */
+            $maybe = $repo->cached();
+            if ($maybe instanceof User) {
+                echo $maybe;
+            }
+            if ($maybe === null) {
+                return;
+            }
+            echo $maybe;
+            if (is_string($id)) {
+                echo $id;
+            }
+        }
+
+        function later(Repository $repo): void
+        {
+            $item = $repo->cached();
+            $callback = function () {
+                /** @var User $item */
+                $item = null;
+            };
+            echo $item;
+            /** @var Repository $item */
+            echo $item;
+        }
+    "#;
+
+const USER: &str = r#"<?php
+        namespace App\Models;
+
+        class User
+        {
+            public function getOwner(): ?self
+            {
+                return null;
+            }
+        }
+    "#;
+
+const REPOSITORY: &str = r#"<?php
+        namespace App\Models;
+
+        class Repository
+        {
+            /**
+             * @param int|string $id
+             * @return User|null
+             */
+            public function find($id)
+            {
+                return null;
+            }
+
+            public function cached(): mixed
+            {
+                return null;
+            }
+        }
+    "#;
+
+#[test]
+fn test_method_chain_type() {
+    let (state, _temp_dir, target_uri) = setup_test_environment();
+
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(8, 14),
+        "App\\Models\\User|null",
+    );
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(8, 40),
+        "App\\Models\\User|null",
+    );
+}
+
+#[test]
+fn test_null_coalescing_type() {
+    let (state, _temp_dir, target_uri) = setup_test_environment();
+
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(9, 14),
+        "App\\Models\\User",
+    );
+}
+
+#[test]
+fn test_var_annotation_type() {
+    let (state, _temp_dir, target_uri) = setup_test_environment();
+
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(11, 14),
+        "App\\Models\\User|null",
+    );
+}
+
+#[test]
+fn test_var_annotations_of_nested_functions_are_ignored() {
+    let (state, _temp_dir, target_uri) = setup_test_environment();
+
+    assert_type(&state, &target_uri, Position::new(31, 19), "mixed");
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(33, 19),
+        "App\\Models\\Repository",
+    );
+}
+
+#[test]
+fn test_narrowed_types() {
+    let (state, _temp_dir, target_uri) = setup_test_environment();
+
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(13, 23),
+        "App\\Models\\User",
+    );
+    assert_type(
+        &state,
+        &target_uri,
+        Position::new(18, 19),
+        "App\\Models\\User",
+    );
+    assert_type(&state, &target_uri, Position::new(20, 23), "string");
+}
+
+fn assert_type(state: &State, uri: &Url, position: Position, expected: &str) {
+    assert_eq!(format_types(&type_at(uri, &position, state)), expected);
+}
+
+fn setup_test_environment() -> (State, TempDir, Url) {
+    let temp_dir = TempDir::new().expect("to initialize temp dir");
+    let path_str = format!("{}/{}", temp_dir.path().to_str().unwrap(), "test.php");
+    let target_path = Path::new(&path_str);
+    let target_uri = Url::from_file_path(target_path).unwrap();
+
+    let mut parser = Parser::new().expect("to create a parser");
+    let tree = parser.parse(TEST).expect("to parse file");
+
+    let ast_map = DashMap::new();
+    ast_map.insert(target_uri.clone(), tree);
+    let document_map = DashMap::new();
+    document_map.insert(target_uri.clone(), String::from(TEST));
+
+    let interner = ThreadedInterner::new();
+    let input = Input::new(SourceIdentifier::dummy(), TEST.as_bytes());
+    let (program, _) = parse(&interner, input);
+    let document_program = DashMap::new();
+    document_program.insert(target_uri.clone(), program);
+
+    let state = State::new(
+        document_program,
+        document_map,
+        RwLock::new(String::from(temp_dir.path().to_str().unwrap())),
+        DashMap::new(),
+        ast_map,
+    );
+    state.psr4_map.insert(
+        String::from("App\\"),
+        vec![format!("{}/src", temp_dir.path().to_str().unwrap())],
+    );
+
+    prepare_php_file(target_path, TEST);
+    for (file_path, file_content) in [
+        ("src/Models/User.php", USER),
+        ("src/Models/Repository.php", REPOSITORY),
+    ] {
+        let full_path = temp_dir.path().join(file_path);
+        std::fs::create_dir_all(full_path.parent().unwrap()).expect("to create directory");
+        prepare_php_file(&full_path, file_content);
+    }
+
+    (state, temp_dir, target_uri)
+}
+
+fn prepare_php_file(file_path: &Path, file_contents: &str) {
+    let mut file = std::fs::File::create(file_path).expect("to create file");
+    file.write_all(file_contents.as_bytes())
+        .expect("to write to file");
+}
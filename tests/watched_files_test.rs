@@ -0,0 +1,137 @@
+use pherris::analyzer::composer::load_autoload_class_map;
+use pherris::analyzer::parser::Parser;
+use pherris::handlers::notification::handle_did_change_watched_files;
+use pherris::lsp::state::State;
+use std::path::Path;
+use std::sync::RwLock;
+use tempfile::TempDir;
+use tower_lsp::lsp_types::{DidChangeWatchedFilesParams, FileChangeType, FileEvent, Url};
+
+#[test]
+fn test_changed_file_is_reindexed() {
+    let (state, temp_dir, parser_lock) = setup_test_environment();
+    let path = temp_dir.path().join("src/Foo.php");
+    assert!(state.class_map.get("App\\Foo").is_some());
+
+    std::fs::write(&path, "<?php\nnamespace App;\n\nclass Bar {}\n").expect("to write file");
+    notify(&state, &parser_lock, &path, FileChangeType::CHANGED);
+
+    assert!(state.class_map.get("App\\Foo").is_none());
+    assert_eq!(
+        state.class_map.get("App\\Bar").unwrap().as_str(),
+        path.to_str().unwrap()
+    );
+    assert_eq!(
+        state
+            .namespaces
+            .read()
+            .unwrap()
+            .find("App")
+            .unwrap()
+            .classes(),
+        ["App\\Bar"]
+    );
+
+    std::fs::remove_file(&path).expect("to remove file");
+    notify(&state, &parser_lock, &path, FileChangeType::DELETED);
+    assert!(state.namespaces.read().unwrap().find("App").is_none());
+}
+
+#[test]
+fn test_created_and_deleted_files() {
+    let (state, temp_dir, parser_lock) = setup_test_environment();
+    let path = temp_dir.path().join("src/Created.php");
+
+    std::fs::write(&path, "<?php\nnamespace App;\n\nfunction created() {}\n")
+        .expect("to write file");
+    notify(&state, &parser_lock, &path, FileChangeType::CREATED);
+    assert!(state.function_map.get("app\\created").is_some());
+
+    std::fs::remove_file(&path).expect("to remove file");
+    notify(&state, &parser_lock, &path, FileChangeType::DELETED);
+    assert!(state.function_map.get("app\\created").is_none());
+    assert!(state.symbol_index.get(path.to_str().unwrap()).is_none());
+}
+
+#[test]
+fn test_dependencies_are_not_indexed_eagerly() {
+    let (state, temp_dir, parser_lock) = setup_test_environment();
+    let path = temp_dir.path().join("vendor/acme/lib/Client.php");
+    std::fs::create_dir_all(path.parent().unwrap()).expect("to create directory");
+
+    std::fs::write(&path, "<?php\nnamespace Acme;\n\nclass Client {}\n").expect("to write file");
+    notify(&state, &parser_lock, &path, FileChangeType::CREATED);
+
+    assert!(state.class_map.get("Acme\\Client").is_none());
+}
+
+#[test]
+fn test_regenerated_class_map_is_reloaded() {
+    let (state, temp_dir, parser_lock) = setup_test_environment();
+    let composer_dir = temp_dir.path().join("vendor/composer");
+    std::fs::create_dir_all(&composer_dir).expect("to create directory");
+
+    let classmap_path = composer_dir.join("autoload_classmap.php");
+    std::fs::write(
+        &classmap_path,
+        r#"<?php
+        $vendorDir = dirname(__DIR__);
+        $baseDir = dirname($vendorDir);
+
+        return array(
+            'Acme\\Client' => $vendorDir . '/acme/lib/Client.php',
+        );
+        "#,
+    )
+    .expect("to write file");
+    state.class_map.insert(
+        String::from("Acme\\Removed"),
+        String::from("/vendor/acme/lib/Removed.php"),
+    );
+    notify(
+        &state,
+        &parser_lock,
+        &classmap_path,
+        FileChangeType::CREATED,
+    );
+
+    assert_eq!(
+        state.class_map.get("Acme\\Client").unwrap().as_str(),
+        format!(
+            "{}/vendor/acme/lib/Client.php",
+            temp_dir.path().to_str().unwrap()
+        )
+    );
+    // classes we indexed ourselves survive the reload
+    assert!(state.class_map.get("App\\Foo").is_some());
+    assert!(state.class_map.get("Acme\\Removed").is_none());
+}
+
+fn notify(state: &State, parser_lock: &RwLock<Parser>, path: &Path, typ: FileChangeType) {
+    handle_did_change_watched_files(
+        &DidChangeWatchedFilesParams {
+            changes: vec![FileEvent::new(Url::from_file_path(path).unwrap(), typ)],
+        },
+        state,
+        parser_lock,
+    );
+}
+
+fn setup_test_environment() -> (State, TempDir, RwLock<Parser>) {
+    let temp_dir = TempDir::new().expect("to initialize temp dir");
+    std::fs::create_dir_all(temp_dir.path().join("src")).expect("to create directory");
+    std::fs::write(
+        temp_dir.path().join("src/Foo.php"),
+        "<?php\nnamespace App;\n\nclass Foo {}\n",
+    )
+    .expect("to write file");
+
+    let state = State {
+        root_path: RwLock::new(temp_dir.path().to_str().unwrap().to_string()),
+        ..Default::default()
+    };
+    let parser_lock = RwLock::new(Parser::new().expect("to create a parser"));
+    load_autoload_class_map(&parser_lock, &state);
+
+    (state, temp_dir, parser_lock)
+}